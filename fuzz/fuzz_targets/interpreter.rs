@@ -0,0 +1,20 @@
+#![no_main]
+
+use chip8::chip8::*;
+use libfuzzer_sys::fuzz_target;
+
+/// Enough to get through a few hundred frames while keeping each run short
+/// enough that a ROM spinning on Fx0A or a jump-to-self is not reported as a hang.
+const MAX_INSTRUCTIONS: u64 = 20_000;
+const CYCLES_PER_FRAME: u64 = 16;
+
+fuzz_target!(|rom: &[u8]| {
+    // A fixed seed keeps Cxkk deterministic, so every crash reproduces.
+    let mut chip8 = with_seed(0);
+    load_rom(&mut chip8, rom);
+
+    for _ in 0..MAX_INSTRUCTIONS / CYCLES_PER_FRAME {
+        run(&mut chip8, CYCLES_PER_FRAME);
+        update_timers(&mut chip8);
+    }
+});
@@ -0,0 +1,568 @@
+//! Assembler for a subset of the Octo language: labels, constants, register
+//! aliases, every CHIP-8/SCHIP instruction, `if ... then`, `if ... begin ...
+//! else ... end` and `loop ... while ... again`. Macros and `:calc` are not
+//! supported.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+const START_ADDRESS: u16 = 0x200;
+
+/// An assembled program, loaded at 0x200.
+#[derive(Debug, Clone, Default)]
+pub struct Program {
+    pub bytes: Vec<u8>,
+    pub labels: BTreeMap<String, u16>,
+    /// Address of each emitted instruction to its 1-based source line.
+    pub lines: BTreeMap<u16, usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+#[derive(Clone, Copy)]
+struct Token<'a> {
+    text: &'a str,
+    line: usize,
+}
+
+enum Block {
+    If { jump: usize },
+    Else { jump: usize },
+    Loop { start: u16, exits: Vec<usize> },
+}
+
+struct Fixup {
+    offset: usize,
+    label: String,
+    line: usize,
+}
+
+struct Assembler<'a> {
+    tokens: Vec<Token<'a>>,
+    pos: usize,
+    line: usize,
+    bytes: Vec<u8>,
+    labels: BTreeMap<String, u16>,
+    constants: HashMap<String, i32>,
+    aliases: HashMap<String, u8>,
+    fixups: Vec<Fixup>,
+    lines: BTreeMap<u16, usize>,
+    blocks: Vec<Block>,
+}
+
+/// Skip instructions for a condition: the one that skips when the condition
+/// holds and the one that skips when it does not.
+struct Condition {
+    skip_if_true: u16,
+    skip_if_false: u16,
+}
+
+fn parse_number(text: &str) -> Option<i32> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text),
+    };
+    let value = if let Some(hex) = digits.strip_prefix("0x") {
+        i32::from_str_radix(hex, 16).ok()?
+    } else if let Some(binary) = digits.strip_prefix("0b") {
+        i32::from_str_radix(binary, 2).ok()?
+    } else {
+        digits.parse::<i32>().ok()?
+    };
+    Some(if negative { -value } else { value })
+}
+
+fn parse_register(text: &str) -> Option<u8> {
+    let digit = text.strip_prefix('v').or_else(|| text.strip_prefix('V'))?;
+    if digit.len() != 1 {
+        return None;
+    }
+    u8::from_str_radix(digit, 16).ok()
+}
+
+fn tokenize(source: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    for (index, line) in source.lines().enumerate() {
+        let code = line.split('#').next().unwrap_or("");
+        for text in code.split_whitespace() {
+            tokens.push(Token {
+                text,
+                line: index + 1,
+            });
+        }
+    }
+    tokens
+}
+
+impl<'a> Assembler<'a> {
+    fn error<T>(&self, message: impl Into<String>) -> Result<T, AsmError> {
+        Err(AsmError {
+            line: self.line,
+            message: message.into(),
+        })
+    }
+
+    fn address(&self) -> u16 {
+        START_ADDRESS + self.bytes.len() as u16
+    }
+
+    fn next(&mut self) -> Result<&'a str, AsmError> {
+        match self.tokens.get(self.pos) {
+            Some(token) => {
+                self.pos += 1;
+                self.line = token.line;
+                Ok(token.text)
+            }
+            None => self.error("unexpected end of file"),
+        }
+    }
+
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.pos).map(|token| token.text)
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<(), AsmError> {
+        let token = self.next()?;
+        if token != expected {
+            return self.error(format!("expected `{}`, found `{}`", expected, token));
+        }
+        Ok(())
+    }
+
+    fn emit(&mut self, opcode: u16) {
+        self.lines.insert(self.address(), self.line);
+        self.bytes.push((opcode >> 8) as u8);
+        self.bytes.push(opcode as u8);
+    }
+
+    fn register_value(&self, text: &str) -> Option<u8> {
+        parse_register(text).or_else(|| self.aliases.get(text).copied())
+    }
+
+    fn register(&mut self) -> Result<u16, AsmError> {
+        let token = self.next()?;
+        match self.register_value(token) {
+            Some(register) => Ok(register as u16),
+            None => self.error(format!("expected a register, found `{}`", token)),
+        }
+    }
+
+    fn number_value(&self, text: &str) -> Option<i32> {
+        parse_number(text).or_else(|| self.constants.get(text).copied())
+    }
+
+    fn byte(&mut self) -> Result<u16, AsmError> {
+        let token = self.next()?;
+        match self.number_value(token) {
+            Some(value) if (-128..=255).contains(&value) => Ok((value as u8) as u16),
+            Some(value) => self.error(format!("{} does not fit in a byte", value)),
+            None => self.error(format!("expected a number, found `{}`", token)),
+        }
+    }
+
+    fn nibble(&mut self) -> Result<u16, AsmError> {
+        let token = self.next()?;
+        match self.number_value(token) {
+            Some(value) if (0..=15).contains(&value) => Ok(value as u16),
+            _ => self.error(format!("expected a number from 0 to 15, found `{}`", token)),
+        }
+    }
+
+    /// Emits `opcode | address`, resolving labels that are not defined yet at
+    /// the end of assembly.
+    fn emit_with_address(&mut self, opcode: u16) -> Result<(), AsmError> {
+        let token = self.next()?;
+        let address = match self.number_value(token) {
+            Some(value) if (0..=0xFFF).contains(&value) => value as u16,
+            Some(value) => return self.error(format!("address {:#X} out of range", value)),
+            None => match self.labels.get(token) {
+                Some(address) => *address,
+                None => {
+                    self.fixups.push(Fixup {
+                        offset: self.bytes.len(),
+                        label: token.to_owned(),
+                        line: self.line,
+                    });
+                    0
+                }
+            },
+        };
+        self.emit(opcode | address);
+        Ok(())
+    }
+
+    fn patch(&mut self, offset: usize, address: u16) {
+        self.bytes[offset] = (self.bytes[offset] & 0xF0) | ((address >> 8) as u8 & 0x0F);
+        self.bytes[offset + 1] = address as u8;
+    }
+
+    /// Emits a placeholder jump and returns its offset for patching.
+    fn emit_jump_placeholder(&mut self) -> usize {
+        let offset = self.bytes.len();
+        self.emit(0x1000);
+        offset
+    }
+
+    fn condition(&mut self) -> Result<Condition, AsmError> {
+        let x = self.register()? << 8;
+        let operator = self.next()?;
+        match operator {
+            "key" => {
+                return Ok(Condition {
+                    skip_if_true: 0xE09E | x,
+                    skip_if_false: 0xE0A1 | x,
+                })
+            }
+            "-key" => {
+                return Ok(Condition {
+                    skip_if_true: 0xE0A1 | x,
+                    skip_if_false: 0xE09E | x,
+                })
+            }
+            "==" | "!=" => {}
+            _ => return self.error(format!("unsupported comparison `{}`", operator)),
+        }
+
+        let rhs = self.peek().unwrap_or("");
+        let (equal, not_equal) = match self.register_value(rhs) {
+            Some(y) => {
+                self.pos += 1;
+                let y = (y as u16) << 4;
+                (0x5000 | x | y, 0x9000 | x | y)
+            }
+            None => {
+                let kk = self.byte()?;
+                (0x3000 | x | kk, 0x4000 | x | kk)
+            }
+        };
+
+        Ok(if operator == "==" {
+            Condition {
+                skip_if_true: equal,
+                skip_if_false: not_equal,
+            }
+        } else {
+            Condition {
+                skip_if_true: not_equal,
+                skip_if_false: equal,
+            }
+        })
+    }
+
+    fn register_statement(&mut self, x: u16) -> Result<(), AsmError> {
+        let x = x << 8;
+        let operator = self.next()?;
+        let rhs = self.peek().unwrap_or("");
+        let y = self.register_value(rhs).map(|y| (y as u16) << 4);
+        if y.is_some() {
+            self.pos += 1;
+        }
+
+        match (operator, y) {
+            (":=", Some(y)) => self.emit(0x8000 | x | y),
+            (":=", None) => match rhs {
+                "random" => {
+                    self.pos += 1;
+                    let kk = self.byte()?;
+                    self.emit(0xC000 | x | kk);
+                }
+                "delay" => {
+                    self.pos += 1;
+                    self.emit(0xF007 | x);
+                }
+                "key" => {
+                    self.pos += 1;
+                    self.emit(0xF00A | x);
+                }
+                _ => {
+                    let kk = self.byte()?;
+                    self.emit(0x6000 | x | kk);
+                }
+            },
+            ("+=", Some(y)) => self.emit(0x8004 | x | y),
+            ("+=", None) => {
+                let kk = self.byte()?;
+                self.emit(0x7000 | x | kk);
+            }
+            ("-=", Some(y)) => self.emit(0x8005 | x | y),
+            ("-=", None) => {
+                let kk = self.byte()?;
+                self.emit(0x7000 | x | (kk.wrapping_neg() & 0xFF));
+            }
+            ("=-", Some(y)) => self.emit(0x8007 | x | y),
+            ("|=", Some(y)) => self.emit(0x8001 | x | y),
+            ("&=", Some(y)) => self.emit(0x8002 | x | y),
+            ("^=", Some(y)) => self.emit(0x8003 | x | y),
+            (">>=", Some(y)) => self.emit(0x8006 | x | y),
+            ("<<=", Some(y)) => self.emit(0x800E | x | y),
+            _ => return self.error(format!("unsupported register operation `{} {}`", operator, rhs)),
+        }
+        Ok(())
+    }
+
+    fn statement(&mut self) -> Result<(), AsmError> {
+        let token = self.next()?;
+        match token {
+            ":" => {
+                let name = self.next()?;
+                if self.labels.insert(name.to_owned(), self.address()).is_some() {
+                    return self.error(format!("label `{}` is defined twice", name));
+                }
+            }
+            ":const" => {
+                let name = self.next()?;
+                let value_token = self.next()?;
+                match self.number_value(value_token) {
+                    Some(value) => {
+                        self.constants.insert(name.to_owned(), value);
+                    }
+                    None => return self.error(format!("expected a number, found `{}`", value_token)),
+                }
+            }
+            ":alias" => {
+                let name = self.next()?;
+                let register = self.register()?;
+                self.aliases.insert(name.to_owned(), register as u8);
+            }
+            ":org" => {
+                let value_token = self.next()?;
+                match self.number_value(value_token) {
+                    Some(value) if value >= self.address() as i32 && value <= 0xFFF => {
+                        self.bytes.resize((value as u16 - START_ADDRESS) as usize, 0);
+                    }
+                    _ => return self.error(format!("cannot move to address `{}`", value_token)),
+                }
+            }
+            ":byte" => {
+                let byte = self.byte()?;
+                self.bytes.push(byte as u8);
+            }
+            ":call" => self.emit_with_address(0x2000)?,
+            "return" | ";" => self.emit(0x00EE),
+            "clear" => self.emit(0x00E0),
+            "hires" => self.emit(0x00FF),
+            "lores" => self.emit(0x00FE),
+            "exit" => self.emit(0x00FD),
+            "scroll-left" => self.emit(0x00FC),
+            "scroll-right" => self.emit(0x00FB),
+            "scroll-down" => {
+                let n = self.nibble()?;
+                self.emit(0x00C0 | n);
+            }
+            "bcd" => {
+                let x = self.register()?;
+                self.emit(0xF033 | (x << 8));
+            }
+            "save" => {
+                let x = self.register()?;
+                self.emit(0xF055 | (x << 8));
+            }
+            "load" => {
+                let x = self.register()?;
+                self.emit(0xF065 | (x << 8));
+            }
+            "saveflags" => {
+                let x = self.register()?;
+                self.emit(0xF075 | (x << 8));
+            }
+            "loadflags" => {
+                let x = self.register()?;
+                self.emit(0xF085 | (x << 8));
+            }
+            "sprite" => {
+                let x = self.register()?;
+                let y = self.register()?;
+                let n = self.nibble()?;
+                self.emit(0xD000 | (x << 8) | (y << 4) | n);
+            }
+            "jump" => self.emit_with_address(0x1000)?,
+            "jump0" => self.emit_with_address(0xB000)?,
+            "native" => self.emit_with_address(0x0000)?,
+            "delay" | "buzzer" => {
+                self.expect(":=")?;
+                let x = self.register()? << 8;
+                self.emit(if token == "delay" { 0xF015 | x } else { 0xF018 | x });
+            }
+            "i" => {
+                let operator = self.next()?;
+                match (operator, self.peek()) {
+                    (":=", Some("hex")) => {
+                        self.pos += 1;
+                        let x = self.register()?;
+                        self.emit(0xF029 | (x << 8));
+                    }
+                    (":=", Some("bighex")) => {
+                        self.pos += 1;
+                        let x = self.register()?;
+                        self.emit(0xF030 | (x << 8));
+                    }
+                    (":=", _) => self.emit_with_address(0xA000)?,
+                    ("+=", _) => {
+                        let x = self.register()?;
+                        self.emit(0xF01E | (x << 8));
+                    }
+                    _ => return self.error(format!("unsupported operation `i {}`", operator)),
+                }
+            }
+            "if" => {
+                let condition = self.condition()?;
+                match self.next()? {
+                    "then" => {
+                        self.emit(condition.skip_if_false);
+                        self.statement()?;
+                    }
+                    "begin" => {
+                        self.emit(condition.skip_if_true);
+                        let jump = self.emit_jump_placeholder();
+                        self.blocks.push(Block::If { jump });
+                    }
+                    other => return self.error(format!("expected `then` or `begin`, found `{}`", other)),
+                }
+            }
+            "else" => match self.blocks.pop() {
+                Some(Block::If { jump }) => {
+                    let end_jump = self.emit_jump_placeholder();
+                    let address = self.address();
+                    self.patch(jump, address);
+                    self.blocks.push(Block::Else { jump: end_jump });
+                }
+                _ => return self.error("`else` without `if ... begin`"),
+            },
+            "end" => match self.blocks.pop() {
+                Some(Block::If { jump }) | Some(Block::Else { jump }) => {
+                    let address = self.address();
+                    self.patch(jump, address);
+                }
+                _ => return self.error("`end` without `if ... begin`"),
+            },
+            "loop" => {
+                let start = self.address();
+                self.blocks.push(Block::Loop {
+                    start,
+                    exits: Vec::new(),
+                });
+            }
+            "while" => {
+                let condition = self.condition()?;
+                self.emit(condition.skip_if_true);
+                let jump = self.emit_jump_placeholder();
+                match self.blocks.iter_mut().rev().find_map(|block| match block {
+                    Block::Loop { exits, .. } => Some(exits),
+                    _ => None,
+                }) {
+                    Some(exits) => exits.push(jump),
+                    None => return self.error("`while` outside of a loop"),
+                }
+            }
+            "again" => match self.blocks.pop() {
+                Some(Block::Loop { start, exits }) => {
+                    self.emit(0x1000 | start);
+                    let address = self.address();
+                    for exit in exits {
+                        self.patch(exit, address);
+                    }
+                }
+                _ => return self.error("`again` without `loop`"),
+            },
+            _ => {
+                if let Some(x) = self.register_value(token) {
+                    return self.register_statement(x as u16);
+                }
+                if let Some(value) = parse_number(token) {
+                    if !(-128..=255).contains(&value) {
+                        return self.error(format!("{} does not fit in a byte", value));
+                    }
+                    self.bytes.push(value as u8);
+                    return Ok(());
+                }
+                if token.starts_with(':') {
+                    return self.error(format!("unsupported directive `{}`", token));
+                }
+                // A bare identifier calls the subroutine with that label.
+                self.pos -= 1;
+                self.emit_with_address(0x2000)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Assembles Octo source into a ROM image loaded at 0x200.
+///
+/// If the program defines a `main` label anywhere but at the very start, a
+/// `jump main` is emitted first, as Octo does.
+pub fn assemble(source: &str) -> Result<Program, AsmError> {
+    let tokens = tokenize(source);
+    let mut assembler = Assembler {
+        tokens,
+        pos: 0,
+        line: 1,
+        bytes: Vec::new(),
+        labels: BTreeMap::new(),
+        constants: HashMap::new(),
+        aliases: HashMap::new(),
+        fixups: Vec::new(),
+        lines: BTreeMap::new(),
+        blocks: Vec::new(),
+    };
+
+    let defines_main = assembler
+        .tokens
+        .windows(2)
+        .any(|pair| pair[0].text == ":" && pair[1].text == "main");
+    let starts_with_main = assembler.tokens.len() >= 2
+        && assembler.tokens[0].text == ":"
+        && assembler.tokens[1].text == "main";
+    if defines_main && !starts_with_main {
+        assembler.fixups.push(Fixup {
+            offset: 0,
+            label: "main".to_owned(),
+            line: 1,
+        });
+        assembler.emit(0x1000);
+    }
+
+    while assembler.pos < assembler.tokens.len() {
+        assembler.statement()?;
+    }
+
+    if !assembler.blocks.is_empty() {
+        return assembler.error("unterminated `begin` or `loop` block");
+    }
+    if assembler.address() as usize > 0x1000 {
+        return assembler.error("program does not fit in memory");
+    }
+
+    for fixup in std::mem::take(&mut assembler.fixups) {
+        match assembler.labels.get(&fixup.label) {
+            Some(address) => {
+                let address = *address;
+                assembler.patch(fixup.offset, address);
+            }
+            None => {
+                return Err(AsmError {
+                    line: fixup.line,
+                    message: format!("undefined label `{}`", fixup.label),
+                })
+            }
+        }
+    }
+
+    Ok(Program {
+        bytes: assembler.bytes,
+        labels: assembler.labels,
+        lines: assembler.lines,
+    })
+}
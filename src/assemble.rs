@@ -0,0 +1,38 @@
+use std::io::{Read, Write};
+
+use chip8::asm::assemble;
+
+/// Assembles an Octo source file (`-` for stdin) and writes the ROM to
+/// `output`, or to stdout when no output is given.
+pub fn run(source: &str, output: Option<&str>) {
+    let text = if source == "-" {
+        let mut text = String::new();
+        if let Err(error) = std::io::stdin().read_to_string(&mut text) {
+            eprintln!("Unable to read stdin: {}", error);
+            crate::exit::ExitStatus::Error.exit();
+        }
+        text
+    } else {
+        std::fs::read_to_string(source).unwrap_or_else(|error| {
+            eprintln!("Unable to read {}: {}", source, error);
+            crate::exit::ExitStatus::Error.exit();
+        })
+    };
+
+    let program = match assemble(&text) {
+        Ok(program) => program,
+        Err(error) => {
+            eprintln!("{}: {}", source, error);
+            crate::exit::ExitStatus::Error.exit();
+        }
+    };
+
+    let written = match output {
+        Some(path) => std::fs::write(path, &program.bytes),
+        None => std::io::stdout().write_all(&program.bytes),
+    };
+    if let Err(error) = written {
+        eprintln!("Unable to write {}: {}", output.unwrap_or("the ROM"), error);
+        crate::exit::ExitStatus::Error.exit();
+    }
+}
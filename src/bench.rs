@@ -0,0 +1,62 @@
+use std::time::{Duration, Instant};
+
+use chip8::chip8::*;
+use crate::load_rom_from_file;
+
+/// Runs the ROM headlessly as fast as possible for `seconds` and prints
+/// instruction and frame throughput. A frame is `target_cycles_per_second / 60`
+/// instructions followed by a timer update, the same unit the windowed
+/// frontend works in.
+pub fn run(filename: &str, seconds: u64, target_cycles_per_second: u32) {
+    let mut chip8 = initialize();
+    load_rom_from_file(&mut chip8, filename).unwrap_or_else(|error| {
+        eprintln!("Unable to read {}: {}", filename, error);
+        crate::exit::ExitStatus::Error.exit();
+    });
+
+    let cycles_per_frame = (target_cycles_per_second / 60).max(1);
+    let duration = Duration::from_secs(seconds);
+
+    let mut instructions: u64 = 0;
+    let mut frames: u64 = 0;
+    let mut min_frame_time = Duration::MAX;
+    let mut max_frame_time = Duration::ZERO;
+
+    let start = Instant::now();
+    while start.elapsed() < duration {
+        let frame_start = Instant::now();
+        for _ in 0..cycles_per_frame {
+            step(&mut chip8);
+        }
+        update_timers(&mut chip8);
+        let frame_time = frame_start.elapsed();
+
+        instructions += cycles_per_frame as u64;
+        frames += 1;
+        min_frame_time = min_frame_time.min(frame_time);
+        max_frame_time = max_frame_time.max(frame_time);
+    }
+    let elapsed = start.elapsed().as_secs_f64();
+
+    println!("Benchmark: {} ({:.2} s)", filename, elapsed);
+    println!(
+        "Instructions: {} ({:.0} instructions/s)",
+        instructions,
+        instructions as f64 / elapsed
+    );
+    println!(
+        "Frames: {} ({:.0} frames/s, {:.1}x real time at {} cycles/frame)",
+        frames,
+        frames as f64 / elapsed,
+        frames as f64 / elapsed / 60.0,
+        cycles_per_frame
+    );
+    if frames > 0 {
+        println!(
+            "Frame time: min {:.2} us, avg {:.2} us, max {:.2} us",
+            min_frame_time.as_secs_f64() * 1e6,
+            elapsed * 1e6 / frames as f64,
+            max_frame_time.as_secs_f64() * 1e6
+        );
+    }
+}
@@ -0,0 +1,69 @@
+//! The call stack over time, for the `T` overlay and the terminal debugger:
+//! the shallowest and deepest the stack got in each recent frame, so
+//! runaway recursion or calls that never return show as a climbing line.
+
+use std::collections::VecDeque;
+
+use chip8::chip8::Call;
+use chip8::symbols::Symbols;
+
+/// Frames the timeline keeps, three seconds at 60Hz.
+pub const TIMELINE_FRAMES: usize = 180;
+
+pub struct CallTimeline {
+    /// Shallowest and deepest stack of each frame that ran instructions,
+    /// oldest first.
+    frames: VecDeque<(usize, usize)>,
+    current: Option<(usize, usize)>,
+}
+
+impl CallTimeline {
+    pub fn new() -> CallTimeline {
+        CallTimeline {
+            frames: VecDeque::with_capacity(TIMELINE_FRAMES),
+            current: None,
+        }
+    }
+
+    /// Notes the stack depth after an instruction ran.
+    pub fn record(&mut self, depth: usize) {
+        self.current = Some(match self.current {
+            Some((low, high)) => (low.min(depth), high.max(depth)),
+            None => (depth, depth),
+        });
+    }
+
+    /// Closes the frame. Frames where nothing ran, e.g. while paused, are
+    /// left out, so the timeline stands still instead of flattening.
+    pub fn end_frame(&mut self) {
+        let Some(frame) = self.current.take() else {
+            return;
+        };
+        if self.frames.len() == TIMELINE_FRAMES {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(frame);
+    }
+
+    /// Shallowest and deepest stack of each frame, oldest first.
+    pub fn frames(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.frames.iter().copied()
+    }
+
+    /// The deepest the stack got in any frame kept.
+    pub fn deepest(&self) -> usize {
+        self.frames.iter().map(|&(_, high)| high).max().unwrap_or(0)
+    }
+}
+
+/// A call as the call chain lists it, e.g. `20A call draw_player`.
+pub fn call_text(call: &Call, symbols: &Symbols) -> String {
+    let target = match call.target {
+        Some(target) => match symbols.name(target) {
+            Some(name) => name.to_string(),
+            None => format!("{:03X}", target),
+        },
+        None => "?".to_string(),
+    };
+    format!("{:03X} call {}", call.site, target)
+}
@@ -0,0 +1,161 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+
+use chip8::chip8::Chip8;
+
+use crate::image;
+
+/// GIF delays are in hundredths of a second and viewers slow down anything
+/// under two, so frames are placed on a 50Hz grid.
+const GIF_TICK: u64 = 2;
+
+enum Sink {
+    Gif {
+        encoder: gif::Encoder<BufWriter<File>>,
+        /// The last distinct frame, its palette and the frame it appeared
+        /// on, written once the next change shows how long it stayed.
+        pending: Option<(Vec<u8>, Vec<u8>, u64)>,
+    },
+    Ffmpeg {
+        child: Child,
+        stdin: ChildStdin,
+    },
+}
+
+/// Captures the display every frame, to an animated GIF or through ffmpeg to
+/// any video format it can write.
+pub struct Capture {
+    path: String,
+    width: usize,
+    height: usize,
+    frames: u64,
+    /// The display scaled to the capture size as of the last frame.
+    pixels: Vec<u8>,
+    sink: Sink,
+}
+
+impl Capture {
+    /// Starts capturing to `path`, a GIF if it ends in `.gif` and otherwise a
+    /// video encoded by `ffmpeg`, which must be on the `PATH`. Frames are the
+    /// size of the display with `scale` pixels per 64x32 pixel, like the
+    /// window; later frames are stretched to that size if the resolution
+    /// changes.
+    pub fn start(path: &str, chip8: &Chip8, scale: usize) -> std::io::Result<Capture> {
+        let scale = (scale * 64 / chip8.display_width).max(1);
+        let (width, height) = (chip8.display_width * scale, chip8.display_height * scale);
+        let too_big = || std::io::Error::other(format!("{}x{} is too big for a GIF", width, height));
+        let sink = if path.to_lowercase().ends_with(".gif") {
+            let file = BufWriter::new(File::create(path)?);
+            let mut encoder = gif::Encoder::new(
+                file,
+                width.try_into().map_err(|_| too_big())?,
+                height.try_into().map_err(|_| too_big())?,
+                &[],
+            )
+            .map_err(std::io::Error::other)?;
+            encoder.set_repeat(gif::Repeat::Infinite).map_err(std::io::Error::other)?;
+            Sink::Gif { encoder, pending: None }
+        } else {
+            let mut child = Command::new("ffmpeg")
+                .args(["-loglevel", "error", "-y", "-f", "rawvideo", "-pix_fmt", "rgb24"])
+                .args(["-video_size", &format!("{}x{}", width, height), "-framerate", "60"])
+                .args(["-i", "-", "-pix_fmt", "yuv420p", path])
+                .stdin(Stdio::piped())
+                .spawn()
+                .map_err(|error| std::io::Error::other(format!("unable to run ffmpeg: {}", error)))?;
+            let stdin = child.stdin.take().unwrap();
+            Sink::Ffmpeg { child, stdin }
+        };
+        tracing::info!(target: "frontend", "Capturing {}x{} frames to {}", width, height, path);
+        Ok(Capture {
+            path: path.to_owned(),
+            width,
+            height,
+            frames: 0,
+            pixels: Vec::new(),
+            sink,
+        })
+    }
+
+    /// Adds the display as it is now, with lit pixels in `color`. Unless
+    /// `changed`, as `take_display_dirty` tells, the last frame's pixels are
+    /// reused.
+    pub fn frame(&mut self, chip8: &Chip8, color: [u8; 3], changed: bool) -> std::io::Result<()> {
+        if changed || self.pixels.is_empty() {
+            self.pixels = image::scaled_pixels(chip8, self.width, self.height);
+        }
+        let pixels = &self.pixels;
+        let palette = image::palette_rgb(chip8, color);
+        let number = self.frames;
+        self.frames += 1;
+        match &mut self.sink {
+            Sink::Gif { encoder, pending } => {
+                if pending.as_ref().is_some_and(|(last, last_palette, _)| last == pixels && *last_palette == palette) {
+                    return Ok(());
+                }
+                if let Some(frame) = pending.take() {
+                    write_gif_frame(encoder, self.width, self.height, frame, number)?;
+                }
+                *pending = Some((pixels.clone(), palette, number));
+                Ok(())
+            }
+            Sink::Ffmpeg { stdin, .. } => {
+                let rgb: Vec<u8> = pixels
+                    .iter()
+                    .flat_map(|&pixel| &palette[pixel as usize * 3..pixel as usize * 3 + 3])
+                    .copied()
+                    .collect();
+                stdin.write_all(&rgb)
+            }
+        }
+    }
+
+    /// Writes out the last frame and closes the file, waiting for ffmpeg to
+    /// finish encoding.
+    pub fn finish(self) {
+        let result = match self.sink {
+            Sink::Gif { mut encoder, pending } => match pending {
+                Some(frame) => write_gif_frame(&mut encoder, self.width, self.height, frame, self.frames),
+                None => Ok(()),
+            },
+            Sink::Ffmpeg { mut child, stdin } => {
+                drop(stdin);
+                match child.wait() {
+                    Ok(status) if status.success() => Ok(()),
+                    Ok(status) => Err(std::io::Error::other(format!("ffmpeg failed with {}", status))),
+                    Err(error) => Err(error),
+                }
+            }
+        };
+        match result {
+            Ok(()) => tracing::info!(target: "frontend", "Captured {} frames to {}", self.frames, self.path),
+            Err(error) => tracing::error!(target: "frontend", "Unable to finish capturing to {}: {}", self.path, error),
+        }
+    }
+}
+
+/// Writes a frame shown from its frame number until `until`. A frame that
+/// would last under one GIF tick is dropped in favour of the next.
+fn write_gif_frame(
+    encoder: &mut gif::Encoder<BufWriter<File>>,
+    width: usize,
+    height: usize,
+    (pixels, palette, shown): (Vec<u8>, Vec<u8>, u64),
+    until: u64,
+) -> std::io::Result<()> {
+    let tick = |frame: u64| frame * 100 / 60 / GIF_TICK * GIF_TICK;
+    let delay = tick(until) - tick(shown);
+    if delay == 0 {
+        return Ok(());
+    }
+    let frame = gif::Frame {
+        width: width as u16,
+        height: height as u16,
+        delay: delay.min(u16::MAX as u64) as u16,
+        palette: Some(palette),
+        buffer: pixels.into(),
+        ..gif::Frame::default()
+    };
+    encoder.write_frame(&frame).map_err(std::io::Error::other)
+}
@@ -0,0 +1,114 @@
+//! Memory cheats: `freeze` cheats rewrite a byte every frame, `once` cheats
+//! poke it a single time after being enabled.
+//!
+//! Cheat files have one cheat per line, `#` starts a comment:
+//!
+//! ```text
+//! freeze 0x3A0 0x03 Infinite lives
+//! once   0x21C 0x00 Skip the intro
+//! ```
+
+use super::Chip8;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CheatKind {
+    Freeze,
+    Once,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cheat {
+    pub name: String,
+    pub address: u16,
+    pub value: u8,
+    pub kind: CheatKind,
+    pub enabled: bool,
+    /// Whether a `once` cheat has been applied since it was last enabled.
+    pub applied: bool,
+}
+
+impl Cheat {
+    pub fn toggle(&mut self) {
+        self.enabled = !self.enabled;
+        self.applied = false;
+    }
+}
+
+/// Splits off the next whitespace-separated field of `rest`.
+fn next_field<'a>(rest: &mut &'a str) -> Option<&'a str> {
+    let trimmed = rest.trim_start();
+    if trimmed.is_empty() {
+        return None;
+    }
+    let end = trimmed.find(char::is_whitespace).unwrap_or(trimmed.len());
+    let (field, tail) = trimmed.split_at(end);
+    *rest = tail;
+    Some(field)
+}
+
+fn parse_number(text: &str) -> Option<u32> {
+    match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
+
+/// Parses a cheat file. Every cheat starts out enabled.
+pub fn parse_cheats(text: &str) -> Result<Vec<Cheat>, String> {
+    let mut cheats = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let mut rest = line.split('#').next().unwrap_or("").trim();
+        if rest.is_empty() {
+            continue;
+        }
+
+        let error = || format!("line {}: expected `<freeze|once> <address> <value> [name]`", index + 1);
+        let kind = match next_field(&mut rest) {
+            Some("freeze") => CheatKind::Freeze,
+            Some("once") => CheatKind::Once,
+            _ => return Err(error()),
+        };
+        let address = next_field(&mut rest)
+            .and_then(parse_number)
+            .filter(|address| *address <= 0xFFFF)
+            .ok_or_else(error)?;
+        let value = next_field(&mut rest)
+            .and_then(parse_number)
+            .filter(|value| *value <= 0xFF)
+            .ok_or_else(error)?;
+        let name = match rest.trim() {
+            "" => format!("{:#05X} = {:#04X}", address, value),
+            name => name.to_owned(),
+        };
+
+        cheats.push(Cheat {
+            name,
+            address: address as u16,
+            value: value as u8,
+            kind,
+            enabled: true,
+            applied: false,
+        });
+    }
+    Ok(cheats)
+}
+
+/// Applies the enabled cheats; call before running and after every
+/// instruction, so a frozen byte holds even when the ROM writes it and reads
+/// it back within a frame. Cheats on addresses past the end of memory do
+/// nothing.
+pub fn apply_cheats(chip8: &mut Chip8, cheats: &mut [Cheat]) {
+    for cheat in cheats.iter_mut().filter(|cheat| cheat.enabled) {
+        let Some(byte) = chip8.memory.get_mut(cheat.address as usize) else {
+            continue;
+        };
+        match cheat.kind {
+            CheatKind::Freeze => *byte = cheat.value,
+            CheatKind::Once if !cheat.applied => {
+                *byte = cheat.value;
+                cheat.applied = true;
+            }
+            CheatKind::Once => {}
+        }
+    }
+}
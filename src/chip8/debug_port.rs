@@ -0,0 +1,29 @@
+//! A `printf` for ROM developers: two otherwise unused opcodes that log a
+//! register or a few memory bytes under the `rom` target.
+//!
+//! - `FxFF` logs Vx.
+//! - `FxFE` logs the x + 1 bytes starting at I, like Fx55 would store them.
+
+use tracing::info;
+
+use super::{current_pc, mem_index, register_opcode_handler, Chip8};
+
+/// Makes FxFF and FxFE print instead of faulting as unknown opcodes.
+pub fn enable(chip8: &mut Chip8) {
+    register_opcode_handler(chip8, 0xF0FF, 0xF0FF, print_register);
+    register_opcode_handler(chip8, 0xF0FF, 0xF0FE, print_memory);
+}
+
+fn print_register(chip8: &mut Chip8, opcode: u16) {
+    let x = ((opcode & 0x0F00) >> 8) as usize;
+    let value = chip8.v[x];
+    info!(target: "rom", "{:#05X}: V{:X} = {:#04X} ({})", current_pc(chip8), x, value, value);
+}
+
+fn print_memory(chip8: &mut Chip8, opcode: u16) {
+    let count = ((opcode & 0x0F00) >> 8) as u32 + 1;
+    let bytes: Vec<String> = (0..count)
+        .map(|offset| format!("{:02X}", chip8.memory[mem_index(chip8, chip8.i.wrapping_add(offset))]))
+        .collect();
+    info!(target: "rom", "{:#05X}: [{:#X}] = {}", current_pc(chip8), chip8.i, bytes.join(" "));
+}
@@ -0,0 +1,299 @@
+//! Breakpoints, watchpoints and the other reasons a debugging frontend
+//! pauses the ROM.
+//!
+//! The core never stops by itself: it records why execution should pause in
+//! `Chip8::stop` and the frontend, which owns the run loop, takes it with
+//! `take_stop` after each instruction and pauses.
+
+use std::fmt;
+use std::ops::RangeInclusive;
+
+use crate::expr::Expr;
+
+use super::{decode, peek_opcode, read_opcode, step, update_timers, wrap_pc, Chip8, Instruction};
+
+/// Why execution should pause.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stop {
+    /// PC reached a breakpoint; the instruction there has not run yet.
+    Breakpoint { pc: u16 },
+    /// A call stepped over with `step_over` returned to `pc`.
+    SteppedOver { pc: u16 },
+    /// PC reached an opcode nothing implements, with `break_on_unknown` set;
+    /// it has not run yet.
+    UnknownOpcode { pc: u16, opcode: u16 },
+    /// The Dxyn at `pc` drew, with `break_on_draw` set.
+    Draw { pc: u16, instruction: Instruction },
+    /// The 00E0 at `pc` cleared the display, with `break_on_clear` set.
+    Clear { pc: u16 },
+    /// The instruction at `pc` wrote `new` over `old` at a watched address.
+    /// Only the first such write of an instruction is reported.
+    MemoryWrite { pc: u16, address: u32, old: u8, new: u8 },
+    /// The instruction at `pc` changed a watched register from `old` to `new`.
+    RegisterChange {
+        pc: u16,
+        instruction: Instruction,
+        register: Register,
+        old: u32,
+        new: u32,
+    },
+}
+
+/// A register that can be watched: one of V0-VF, or I.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Register {
+    V(u8),
+    I,
+}
+
+impl Register {
+    pub fn value(self, chip8: &Chip8) -> u32 {
+        match self {
+            Register::V(x) => chip8.v[x as usize] as u32,
+            Register::I => chip8.i,
+        }
+    }
+
+    /// The largest value the register holds: a byte for V0-VF, 24 bits
+    /// for I, which MegaChip's Fnnn nnnn sets that wide.
+    pub fn max(self) -> u32 {
+        match self {
+            Register::V(_) => 0xFF,
+            Register::I => 0xFF_FFFF,
+        }
+    }
+
+    /// Sets the register, keeping the bits of `value` that fit, see `max`.
+    pub fn set(self, chip8: &mut Chip8, value: u32) {
+        match self {
+            Register::V(x) => chip8.v[x as usize] = value as u8,
+            Register::I => chip8.i = value & self.max(),
+        }
+    }
+}
+
+impl std::str::FromStr for Register {
+    type Err = String;
+
+    /// Parses `V0`-`VF` or `I`, in either case.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let upper = value.to_ascii_uppercase();
+        if upper == "I" {
+            return Ok(Register::I);
+        }
+        match upper.strip_prefix('V').map(|digit| u8::from_str_radix(digit, 16)) {
+            Some(Ok(x)) if x < 16 && upper.len() == 2 => Ok(Register::V(x)),
+            _ => Err(format!("expected V0-VF or I, got {}", value)),
+        }
+    }
+}
+
+impl fmt::Display for Register {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Register::V(x) => write!(f, "V{:X}", x),
+            Register::I => write!(f, "I"),
+        }
+    }
+}
+
+impl fmt::Display for Stop {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Stop::Breakpoint { pc } => write!(f, "Breakpoint at {:#05X}", pc),
+            Stop::SteppedOver { pc } => write!(f, "Returned to {:#05X}", pc),
+            Stop::UnknownOpcode { pc, opcode } => write!(f, "Unknown opcode {:04X} at {:#05X}", opcode, pc),
+            Stop::Draw { pc, instruction } => write!(f, "Drew a sprite with `{}` at {:#05X}", instruction, pc),
+            Stop::Clear { pc } => write!(f, "Cleared the display at {:#05X}", pc),
+            Stop::MemoryWrite { pc, address, old, new } => write!(
+                f,
+                "{:#05X} wrote {:#04X} over {:#04X} at {:#05X}",
+                pc, new, old, address
+            ),
+            Stop::RegisterChange { pc, instruction, register, old, new } => write!(
+                f,
+                "{} changed from {:#04X} to {:#04X} by `{}` at {:#05X}",
+                register, old, new, instruction, pc
+            ),
+        }
+    }
+}
+
+pub fn add_breakpoint(chip8: &mut Chip8, address: u16) {
+    chip8.breakpoints.insert(address);
+    chip8.breakpoint_conditions.remove(&address);
+}
+
+/// Adds a breakpoint that only pauses while `condition` is non-zero, or
+/// cannot be evaluated, e.g. `V3 == 5`.
+pub fn add_conditional_breakpoint(chip8: &mut Chip8, address: u16, condition: Expr) {
+    chip8.breakpoints.insert(address);
+    chip8.breakpoint_conditions.insert(address, condition);
+}
+
+/// Removes a breakpoint, returning whether there was one.
+pub fn remove_breakpoint(chip8: &mut Chip8, address: u16) -> bool {
+    chip8.breakpoint_conditions.remove(&address);
+    chip8.breakpoints.remove(&address)
+}
+
+/// Whether the breakpoint at PC should pause: it has no condition, or its
+/// condition holds or fails to evaluate, which is worth a look too.
+fn condition_holds(chip8: &Chip8) -> bool {
+    match chip8.breakpoint_conditions.get(&chip8.pc) {
+        Some(condition) => condition.eval(chip8) != Ok(0),
+        None => true,
+    }
+}
+
+/// Call before fetching each instruction: whether PC is at a breakpoint, in
+/// which case `Chip8::stop` is set and nothing should run. Calling it again
+/// at the same PC lets the instruction run, so resuming steps off the
+/// breakpoint instead of stopping on it forever.
+pub fn check_breakpoint(chip8: &mut Chip8) -> bool {
+    if chip8.breakpoint_hit.take() == Some(chip8.pc) {
+        return false;
+    }
+    let stop = if chip8.step_over == Some((chip8.pc, chip8.stack.len())) {
+        chip8.step_over = None;
+        Stop::SteppedOver { pc: chip8.pc }
+    } else if chip8.breakpoints.contains(&chip8.pc) && condition_holds(chip8) {
+        Stop::Breakpoint { pc: chip8.pc }
+    } else if chip8.break_on_unknown && is_unknown(chip8, peek_opcode(chip8)) {
+        Stop::UnknownOpcode { pc: chip8.pc, opcode: peek_opcode(chip8) }
+    } else {
+        return false;
+    };
+    chip8.breakpoint_hit = Some(chip8.pc);
+    chip8.stop = Some(stop);
+    true
+}
+
+/// Whether `opcode` would fault as unknown: no instruction decodes from it
+/// and no custom opcode handler takes it.
+fn is_unknown(chip8: &Chip8, opcode: u16) -> bool {
+    matches!(decode(opcode), Instruction::Unknown { .. })
+        && !chip8.opcode_handlers.iter().any(|custom| opcode & custom.mask == custom.pattern)
+}
+
+/// Runs the instruction at PC as a debugger's single step: a breakpoint there
+/// does not stop it, and a Dxyn waiting for the display gets the timer tick
+/// that ends the wait.
+pub fn step_into(chip8: &mut Chip8) {
+    if chip8.vblank_wait {
+        update_timers(chip8);
+    }
+    chip8.breakpoint_hit = Some(chip8.pc);
+    check_breakpoint(chip8);
+    step(chip8);
+}
+
+/// Steps over the 2nnn call at PC: sets a one-off breakpoint after it that
+/// `check_breakpoint` stops at once the call has returned, so the frontend
+/// can simply resume. Returns false, leaving the machine untouched, when PC
+/// is not at a call and a plain `step` does the job.
+pub fn step_over(chip8: &mut Chip8) -> bool {
+    if !matches!(decode(peek_opcode(chip8)), Instruction::Call { .. }) {
+        return false;
+    }
+    let next = wrap_pc(chip8, chip8.pc.wrapping_add(2));
+    chip8.step_over = Some((next, chip8.stack.len()));
+    true
+}
+
+/// A call the stack holds: the address of the 2nnn that made it, and the
+/// address it went to while that is still a 2nnn.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Call {
+    pub site: u16,
+    pub target: Option<u16>,
+}
+
+/// The calls the stack holds, the most recent first, worked out from the
+/// return addresses. A target overwritten since, or a return address the ROM
+/// pushed some other way, leaves `target` empty.
+pub fn call_chain(chip8: &Chip8) -> Vec<Call> {
+    chip8
+        .stack
+        .iter()
+        .rev()
+        .map(|&address| {
+            let site = wrap_pc(chip8, address.wrapping_sub(2));
+            let target = match decode(read_opcode(chip8, site)) {
+                Instruction::Call { nnn } => Some(nnn),
+                _ => None,
+            };
+            Call { site, target }
+        })
+        .collect()
+}
+
+/// Pauses after any instruction that writes to `addresses`, e.g. with Fx33
+/// or Fx55.
+pub fn add_watchpoint(chip8: &mut Chip8, addresses: RangeInclusive<u32>) {
+    chip8.watchpoints.push(addresses);
+}
+
+/// Removes a watchpoint added for exactly `addresses`, returning whether there
+/// was one.
+pub fn remove_watchpoint(chip8: &mut Chip8, addresses: &RangeInclusive<u32>) -> bool {
+    let before = chip8.watchpoints.len();
+    chip8.watchpoints.retain(|watched| watched != addresses);
+    chip8.watchpoints.len() != before
+}
+
+/// Called by the core before the instruction at `pc` writes `new` to memory.
+pub(crate) fn check_write(chip8: &mut Chip8, pc: u16, address: usize, new: u8) {
+    let address = address as u32;
+    if chip8.stop.is_none() && chip8.watchpoints.iter().any(|watched| watched.contains(&address)) {
+        let old = chip8.memory[address as usize];
+        chip8.stop = Some(Stop::MemoryWrite { pc, address, old, new });
+    }
+}
+
+/// Pauses after any instruction that changes `register`.
+pub fn watch_register(chip8: &mut Chip8, register: Register) {
+    if !chip8.watched_registers.contains(&register) {
+        chip8.watched_registers.push(register);
+    }
+}
+
+/// Stops watching `register`, returning whether it was watched.
+pub fn unwatch_register(chip8: &mut Chip8, register: Register) -> bool {
+    let before = chip8.watched_registers.len();
+    chip8.watched_registers.retain(|&watched| watched != register);
+    chip8.watched_registers.len() != before
+}
+
+/// Called by the core after `instruction` at `pc` ran, with the watched
+/// registers' values from before it.
+pub(crate) fn check_registers(chip8: &mut Chip8, pc: u16, instruction: Instruction, before: &[u32]) {
+    if chip8.stop.is_some() {
+        return;
+    }
+    for (&register, &old) in chip8.watched_registers.iter().zip(before) {
+        let new = register.value(chip8);
+        if new != old {
+            chip8.stop = Some(Stop::RegisterChange { pc, instruction, register, old, new });
+            return;
+        }
+    }
+}
+
+/// Called by the core after `instruction` at `pc` ran, to pause after draws
+/// and clears when asked to.
+pub(crate) fn check_display(chip8: &mut Chip8, pc: u16, instruction: Instruction) {
+    if chip8.stop.is_some() {
+        return;
+    }
+    chip8.stop = match instruction {
+        Instruction::Draw { .. } if chip8.break_on_draw => Some(Stop::Draw { pc, instruction }),
+        Instruction::ClearScreen if chip8.break_on_clear => Some(Stop::Clear { pc }),
+        _ => None,
+    };
+}
+
+/// The reason execution should pause, if there is one, clearing it.
+pub fn take_stop(chip8: &mut Chip8) -> Option<Stop> {
+    chip8.stop.take()
+}
@@ -0,0 +1,37 @@
+//! Counts of how often each memory address is read through I, written and
+//! fetched as an instruction, to tell a ROM's code from its data and from
+//! bytes it never touches.
+
+use super::Chip8;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Heatmap {
+    pub reads: Vec<u32>,
+    pub writes: Vec<u32>,
+    pub executes: Vec<u32>,
+}
+
+impl Heatmap {
+    /// Follows memory growing or shrinking to `size` bytes.
+    pub(crate) fn resize(&mut self, size: usize) {
+        self.reads.resize(size, 0);
+        self.writes.resize(size, 0);
+        self.executes.resize(size, 0);
+    }
+}
+
+/// Starts counting accesses to every address of the current memory.
+pub fn enable(chip8: &mut Chip8) {
+    let size = chip8.memory.len();
+    chip8.heatmap = Some(Heatmap {
+        reads: vec![0; size],
+        writes: vec![0; size],
+        executes: vec![0; size],
+    });
+}
+
+pub(crate) fn count(counts: &mut [u32], index: usize) {
+    if let Some(count) = counts.get_mut(index) {
+        *count = count.saturating_add(1);
+    }
+}
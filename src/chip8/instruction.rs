@@ -0,0 +1,249 @@
+//! Decoding opcodes into instructions, separately from executing them.
+
+/// One decoded instruction. `x` and `y` are register numbers, `nnn` an
+/// address, `kk` a byte and `n` a nibble.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Instruction {
+    /// 0nnn - call machine code routine, ignored by modern interpreters
+    Sys { nnn: u16 },
+    /// 00E0 - clear screen
+    ClearScreen,
+    /// 00EE - return from subroutine
+    Return,
+    /// 00Cn - scroll the display down by n pixels (SCHIP)
+    ScrollDown { n: u8 },
+    /// 00Bn - scroll the display up by n pixels (MegaChip)
+    ScrollUp { n: u8 },
+    /// 00FB - scroll the display right by 4 pixels (SCHIP)
+    ScrollRight,
+    /// 00FC - scroll the display left by 4 pixels (SCHIP)
+    ScrollLeft,
+    /// 00FD - exit the interpreter (SCHIP)
+    Exit,
+    /// 00FE - switch to 64x32 low resolution (SCHIP)
+    LowResolution,
+    /// 00FF - switch to 128x64 high resolution (SCHIP)
+    HighResolution,
+    /// 0010 - leave MegaChip mode
+    MegaChipOff,
+    /// 0011 - enter MegaChip mode
+    MegaChipOn,
+    /// 01nn nnnn - set I = 24-bit address, the low 16 bits in the next word (MegaChip)
+    LoadLongI { high: u8 },
+    /// 02nn - load nn palette colors from I (MegaChip)
+    LoadPalette { count: u8 },
+    /// 03nn - set sprite width to nn, 0 meaning 256 (MegaChip)
+    SpriteWidth { width: u8 },
+    /// 04nn - set sprite height to nn, 0 meaning 256 (MegaChip)
+    SpriteHeight { height: u8 },
+    /// 1nnn - jump to address nnn
+    Jump { nnn: u16 },
+    /// 2nnn - call subroutine at nnn
+    Call { nnn: u16 },
+    /// 3xkk - skip next instruction if Vx = kk
+    SkipIfEqual { x: u8, kk: u8 },
+    /// 4xkk - skip next instruction if Vx != kk
+    SkipIfNotEqual { x: u8, kk: u8 },
+    /// 5xy0 - skip next instruction if Vx = Vy
+    SkipIfRegistersEqual { x: u8, y: u8 },
+    /// 6xkk - set Vx = kk
+    Load { x: u8, kk: u8 },
+    /// 7xkk - set Vx = Vx + kk
+    Add { x: u8, kk: u8 },
+    /// 8xy0 - set Vx = Vy
+    Move { x: u8, y: u8 },
+    /// 8xy1 - set Vx = Vx OR Vy
+    Or { x: u8, y: u8 },
+    /// 8xy2 - set Vx = Vx AND Vy
+    And { x: u8, y: u8 },
+    /// 8xy3 - set Vx = Vx XOR Vy
+    Xor { x: u8, y: u8 },
+    /// 8xy4 - set Vx = Vx + Vy, set VF = carry
+    AddRegisters { x: u8, y: u8 },
+    /// 8xy5 - set Vx = Vx - Vy, set VF = NOT borrow
+    Subtract { x: u8, y: u8 },
+    /// 8xy6 - set Vx = Vx SHR 1
+    ShiftRight { x: u8, y: u8 },
+    /// 8xy7 - set Vx = Vy - Vx, set VF = NOT borrow
+    SubtractReversed { x: u8, y: u8 },
+    /// 8xyE - set Vx = Vx SHL 1
+    ShiftLeft { x: u8, y: u8 },
+    /// 9xy0 - skip next instruction if Vx != Vy
+    SkipIfRegistersNotEqual { x: u8, y: u8 },
+    /// Annn - set I = nnn
+    LoadI { nnn: u16 },
+    /// Bnnn - jump to location nnn + V0 (or xnn + Vx)
+    JumpWithOffset { x: u8, nnn: u16 },
+    /// Cxkk - set Vx = random byte AND kk
+    Random { x: u8, kk: u8 },
+    /// Dxyn - display n-byte sprite starting at memory location I at (Vx, Vy)
+    Draw { x: u8, y: u8, n: u8 },
+    /// Ex9E - skip next instruction if key with the value of Vx is pressed
+    SkipIfKeyDown { x: u8 },
+    /// ExA1 - skip next instruction if key with the value of Vx is not pressed
+    SkipIfKeyUp { x: u8 },
+    /// Fx07 - set Vx = delay timer value
+    LoadDelayTimer { x: u8 },
+    /// Fx0A - wait for a key press, store the value of the key in Vx
+    WaitForKey { x: u8 },
+    /// Fx15 - set delay timer = Vx
+    SetDelayTimer { x: u8 },
+    /// Fx18 - set sound timer = Vx
+    SetSoundTimer { x: u8 },
+    /// Fx1E - set I = I + Vx
+    AddToI { x: u8 },
+    /// Fx29 - set I = location of sprite for digit Vx
+    LoadFont { x: u8 },
+    /// Fx30 - set I = location of the 8x10 big sprite for digit Vx (SCHIP)
+    LoadBigFont { x: u8 },
+    /// Fx33 - store BCD representation of Vx in memory locations I, I+1, and I+2
+    StoreBcd { x: u8 },
+    /// Fx55 - store registers V0 through Vx in memory starting at location I
+    StoreRegisters { x: u8 },
+    /// Fx65 - read registers V0 through Vx from memory starting at location I
+    LoadRegisters { x: u8 },
+    /// Fx75 - store V0 through Vx in the RPL user flags (SCHIP)
+    StoreFlags { x: u8 },
+    /// Fx85 - read V0 through Vx from the RPL user flags (SCHIP)
+    LoadFlags { x: u8 },
+    /// Anything else.
+    Unknown { opcode: u16 },
+}
+
+impl Instruction {
+    /// The instruction's family as the comments above name it (`8xy4`,
+    /// `Dxyn`, ...), or `None` for an unknown opcode.
+    pub fn pattern(&self) -> Option<&'static str> {
+        Some(match self {
+            Instruction::Sys { .. } => "0nnn",
+            Instruction::ClearScreen => "00E0",
+            Instruction::Return => "00EE",
+            Instruction::ScrollDown { .. } => "00Cn",
+            Instruction::ScrollUp { .. } => "00Bn",
+            Instruction::ScrollRight => "00FB",
+            Instruction::ScrollLeft => "00FC",
+            Instruction::Exit => "00FD",
+            Instruction::LowResolution => "00FE",
+            Instruction::HighResolution => "00FF",
+            Instruction::MegaChipOff => "0010",
+            Instruction::MegaChipOn => "0011",
+            Instruction::LoadLongI { .. } => "01nn",
+            Instruction::LoadPalette { .. } => "02nn",
+            Instruction::SpriteWidth { .. } => "03nn",
+            Instruction::SpriteHeight { .. } => "04nn",
+            Instruction::Jump { .. } => "1nnn",
+            Instruction::Call { .. } => "2nnn",
+            Instruction::SkipIfEqual { .. } => "3xkk",
+            Instruction::SkipIfNotEqual { .. } => "4xkk",
+            Instruction::SkipIfRegistersEqual { .. } => "5xy0",
+            Instruction::Load { .. } => "6xkk",
+            Instruction::Add { .. } => "7xkk",
+            Instruction::Move { .. } => "8xy0",
+            Instruction::Or { .. } => "8xy1",
+            Instruction::And { .. } => "8xy2",
+            Instruction::Xor { .. } => "8xy3",
+            Instruction::AddRegisters { .. } => "8xy4",
+            Instruction::Subtract { .. } => "8xy5",
+            Instruction::ShiftRight { .. } => "8xy6",
+            Instruction::SubtractReversed { .. } => "8xy7",
+            Instruction::ShiftLeft { .. } => "8xyE",
+            Instruction::SkipIfRegistersNotEqual { .. } => "9xy0",
+            Instruction::LoadI { .. } => "Annn",
+            Instruction::JumpWithOffset { .. } => "Bnnn",
+            Instruction::Random { .. } => "Cxkk",
+            Instruction::Draw { .. } => "Dxyn",
+            Instruction::SkipIfKeyDown { .. } => "Ex9E",
+            Instruction::SkipIfKeyUp { .. } => "ExA1",
+            Instruction::LoadDelayTimer { .. } => "Fx07",
+            Instruction::WaitForKey { .. } => "Fx0A",
+            Instruction::SetDelayTimer { .. } => "Fx15",
+            Instruction::SetSoundTimer { .. } => "Fx18",
+            Instruction::AddToI { .. } => "Fx1E",
+            Instruction::LoadFont { .. } => "Fx29",
+            Instruction::LoadBigFont { .. } => "Fx30",
+            Instruction::StoreBcd { .. } => "Fx33",
+            Instruction::StoreRegisters { .. } => "Fx55",
+            Instruction::LoadRegisters { .. } => "Fx65",
+            Instruction::StoreFlags { .. } => "Fx75",
+            Instruction::LoadFlags { .. } => "Fx85",
+            Instruction::Unknown { .. } => return None,
+        })
+    }
+}
+
+/// Decodes an opcode. MegaChip instructions are decoded whatever the mode;
+/// outside MegaChip mode they execute like 0nnn.
+pub fn decode(opcode: u16) -> Instruction {
+    let x = ((opcode & 0x0F00) >> 8) as u8;
+    let y = ((opcode & 0x00F0) >> 4) as u8;
+    let n = (opcode & 0x000F) as u8;
+    let nnn = opcode & 0x0FFF;
+    let kk = (opcode & 0x00FF) as u8;
+
+    match opcode & 0xF000 {
+        0x0000 => match opcode {
+            0x00E0 => Instruction::ClearScreen,
+            0x00EE => Instruction::Return,
+            0x00FB => Instruction::ScrollRight,
+            0x00FC => Instruction::ScrollLeft,
+            0x00FD => Instruction::Exit,
+            0x00FE => Instruction::LowResolution,
+            0x00FF => Instruction::HighResolution,
+            0x0010 => Instruction::MegaChipOff,
+            0x0011 => Instruction::MegaChipOn,
+            _ if opcode & 0xFFF0 == 0x00C0 => Instruction::ScrollDown { n },
+            _ if opcode & 0xFFF0 == 0x00B0 => Instruction::ScrollUp { n },
+            _ => match opcode & 0xFF00 {
+                0x0100 => Instruction::LoadLongI { high: kk },
+                0x0200 => Instruction::LoadPalette { count: kk },
+                0x0300 => Instruction::SpriteWidth { width: kk },
+                0x0400 => Instruction::SpriteHeight { height: kk },
+                _ => Instruction::Sys { nnn },
+            },
+        },
+        0x1000 => Instruction::Jump { nnn },
+        0x2000 => Instruction::Call { nnn },
+        0x3000 => Instruction::SkipIfEqual { x, kk },
+        0x4000 => Instruction::SkipIfNotEqual { x, kk },
+        0x5000 => Instruction::SkipIfRegistersEqual { x, y },
+        0x6000 => Instruction::Load { x, kk },
+        0x7000 => Instruction::Add { x, kk },
+        0x8000 => match n {
+            0x0 => Instruction::Move { x, y },
+            0x1 => Instruction::Or { x, y },
+            0x2 => Instruction::And { x, y },
+            0x3 => Instruction::Xor { x, y },
+            0x4 => Instruction::AddRegisters { x, y },
+            0x5 => Instruction::Subtract { x, y },
+            0x6 => Instruction::ShiftRight { x, y },
+            0x7 => Instruction::SubtractReversed { x, y },
+            0xE => Instruction::ShiftLeft { x, y },
+            _ => Instruction::Unknown { opcode },
+        },
+        0x9000 => Instruction::SkipIfRegistersNotEqual { x, y },
+        0xA000 => Instruction::LoadI { nnn },
+        0xB000 => Instruction::JumpWithOffset { x, nnn },
+        0xC000 => Instruction::Random { x, kk },
+        0xD000 => Instruction::Draw { x, y, n },
+        0xE000 => match kk {
+            0x9E => Instruction::SkipIfKeyDown { x },
+            0xA1 => Instruction::SkipIfKeyUp { x },
+            _ => Instruction::Unknown { opcode },
+        },
+        _ => match kk {
+            0x07 => Instruction::LoadDelayTimer { x },
+            0x0A => Instruction::WaitForKey { x },
+            0x15 => Instruction::SetDelayTimer { x },
+            0x18 => Instruction::SetSoundTimer { x },
+            0x1E => Instruction::AddToI { x },
+            0x29 => Instruction::LoadFont { x },
+            0x30 => Instruction::LoadBigFont { x },
+            0x33 => Instruction::StoreBcd { x },
+            0x55 => Instruction::StoreRegisters { x },
+            0x65 => Instruction::LoadRegisters { x },
+            0x75 => Instruction::StoreFlags { x },
+            0x85 => Instruction::LoadFlags { x },
+            _ => Instruction::Unknown { opcode },
+        },
+    }
+}
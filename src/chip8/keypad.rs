@@ -0,0 +1,87 @@
+//! The 16-key hex keypad, with the presses and releases since the core last
+//! looked so that a tap between two checks isn't lost, and the presses no
+//! instruction has checked yet for input latching.
+
+/// Keys are numbered 0x0-0xF like on the original keypad.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Keypad {
+    down: u16,
+    pressed: u16,
+    released: u16,
+    latched: u16,
+}
+
+impl Keypad {
+    /// Holds `key` down.
+    pub fn press(&mut self, key: usize) {
+        let bit = 1 << key;
+        if self.down & bit == 0 {
+            self.pressed |= bit;
+            self.latched |= bit;
+        }
+        self.down |= bit;
+    }
+
+    /// Lets go of `key`.
+    pub fn release(&mut self, key: usize) {
+        let bit = 1 << key;
+        if self.down & bit != 0 {
+            self.released |= bit;
+        }
+        self.down &= !bit;
+    }
+
+    /// Presses or releases `key`.
+    pub fn set(&mut self, key: usize, down: bool) {
+        if down {
+            self.press(key);
+        } else {
+            self.release(key);
+        }
+    }
+
+    /// Presses the keys whose bits are set in `down` and releases the rest.
+    pub fn set_all(&mut self, down: u16) {
+        for key in 0..16 {
+            self.set(key, down & 1 << key != 0);
+        }
+    }
+
+    pub fn is_down(&self, key: usize) -> bool {
+        self.down & 1 << key != 0
+    }
+
+    /// The keys held down, one bit per key.
+    pub fn down(&self) -> u16 {
+        self.down
+    }
+
+    /// Whether `key` went down since the edges were last cleared, even if it
+    /// has been released again since.
+    pub fn was_pressed(&self, key: usize) -> bool {
+        self.pressed & 1 << key != 0
+    }
+
+    /// Whether `key` came up since the edges were last cleared.
+    pub fn was_released(&self, key: usize) -> bool {
+        self.released & 1 << key != 0
+    }
+
+    /// Whether `key` was pressed and no instruction has checked it since.
+    pub fn is_latched(&self, key: usize) -> bool {
+        self.latched & 1 << key != 0
+    }
+
+    /// Marks a press of `key` as seen, returning whether it was unseen.
+    pub fn take_latched(&mut self, key: usize) -> bool {
+        let latched = self.is_latched(key);
+        self.latched &= !(1 << key);
+        latched
+    }
+
+    /// Forgets the presses and releases seen so far, keeping the held keys.
+    pub fn clear_edges(&mut self) {
+        self.pressed = 0;
+        self.released = 0;
+    }
+}
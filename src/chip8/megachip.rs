@@ -0,0 +1,56 @@
+//! MegaChip8: a 256x192 display of palette indexes, sprites of any size
+//! blitted from one byte per pixel, and a 24-bit I register for ROMs far
+//! bigger than 4K. Digitized sound, screen alpha and blend modes are accepted
+//! but ignored.
+
+use super::{read_i, set_resolution, Chip8};
+
+pub const MEGACHIP_WIDTH: usize = 256;
+pub const MEGACHIP_HEIGHT: usize = 192;
+
+/// The largest memory a MegaChip ROM can address with a 24-bit I.
+pub const MEGACHIP_MEMORY_SIZE: usize = 0x100_0000;
+
+/// Switches MegaChip mode on or off, clearing the display.
+pub fn set_megachip(chip8: &mut Chip8, enabled: bool) {
+    chip8.megachip = enabled;
+    if enabled {
+        set_resolution(chip8, MEGACHIP_WIDTH, MEGACHIP_HEIGHT);
+    } else {
+        set_resolution(chip8, 64, 32);
+    }
+}
+
+/// Loads `count` ARGB colors from I into palette entries 1 onwards; entry 0
+/// is always the transparent background.
+pub fn load_palette(chip8: &mut Chip8, count: usize) {
+    for entry in 0..count {
+        let mut argb = [0; 4];
+        for (offset, byte) in argb.iter_mut().enumerate() {
+            *byte = read_i(chip8, (entry * 4 + offset) as u32);
+        }
+        chip8.palette[entry + 1] = u32::from_be_bytes(argb);
+    }
+}
+
+/// Blits a `sprite_width` by `sprite_height` sprite of palette indexes from I
+/// to (x, y). Index 0 is transparent; VF is set if any drawn pixel covered a
+/// lit one.
+pub fn draw_sprite(chip8: &mut Chip8, x: usize, y: usize) -> u8 {
+    let (width, height) = (chip8.display_width, chip8.display_height);
+    let mut collision = 0;
+    for row in 0..chip8.sprite_height.min(height - y) {
+        for column in 0..chip8.sprite_width.min(width - x) {
+            let color = read_i(chip8, (row * chip8.sprite_width + column) as u32);
+            if color != 0 {
+                let index = x + column + (y + row) * width;
+                if chip8.display[index] != 0 {
+                    collision = 1;
+                }
+                chip8.display_dirty |= chip8.display[index] != color;
+                chip8.display[index] = color;
+            }
+        }
+    }
+    collision
+}
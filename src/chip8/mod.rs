@@ -1,20 +1,264 @@
-use rand::Rng;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::fmt;
+use std::ops::RangeInclusive;
+
 use raylib::prelude::*;
+use tracing::{trace, warn};
+
+use crate::expr::Expr;
+
+pub mod cheats;
+pub mod debug_port;
+pub mod debugger;
+pub mod fonts;
+pub mod heatmap;
+pub mod instruction;
+pub mod keypad;
+pub mod megachip;
+pub mod quirks;
+pub mod register_history;
+pub mod rng;
+pub mod state;
+pub mod timing;
+
+pub use debugger::{
+    add_breakpoint, add_conditional_breakpoint, add_watchpoint, call_chain, check_breakpoint, remove_breakpoint,
+    remove_watchpoint, step_into, step_over, take_stop, unwatch_register, watch_register, Call, Register, Stop,
+};
+pub use instruction::{decode, Instruction};
+pub use keypad::Keypad;
+pub use megachip::{MEGACHIP_HEIGHT, MEGACHIP_MEMORY_SIZE, MEGACHIP_WIDTH};
+pub use quirks::{MemoryIncrement, Quirks};
+pub use rng::Rng;
+
+/// Something the ROM did that a real interpreter could not have handled.
+/// The instruction is skipped and execution carries on, but the first fault
+/// is kept in `Chip8::fault` for the frontend to report.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fault {
+    UnknownOpcode { pc: u16, opcode: u16 },
+    StackOverflow { pc: u16 },
+    StackUnderflow { pc: u16 },
+    MemoryOutOfBounds { pc: u16, address: u32 },
+    /// Strict mode only.
+    WriteBelowStart { pc: u16, address: u32 },
+    /// Strict mode only.
+    UnalignedJump { pc: u16, target: u16 },
+}
+
+impl Fault {
+    /// Address of the instruction that faulted.
+    pub fn pc(&self) -> u16 {
+        match *self {
+            Fault::UnknownOpcode { pc, .. }
+            | Fault::StackOverflow { pc }
+            | Fault::StackUnderflow { pc }
+            | Fault::MemoryOutOfBounds { pc, .. }
+            | Fault::WriteBelowStart { pc, .. }
+            | Fault::UnalignedJump { pc, .. } => pc,
+        }
+    }
+}
+
+/// What I-relative reads and writes past the end of memory do.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutOfBounds {
+    /// Wrap around to the start of memory.
+    #[default]
+    Wrap,
+    /// Use the last byte of memory.
+    Clamp,
+    /// Raise a fault; reads give 0 and writes are dropped.
+    Fault,
+}
+
+impl std::str::FromStr for OutOfBounds {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "wrap" => Ok(OutOfBounds::Wrap),
+            "clamp" => Ok(OutOfBounds::Clamp),
+            "fault" => Ok(OutOfBounds::Fault),
+            _ => Err(format!("expected wrap, clamp or fault, got {}", value)),
+        }
+    }
+}
 
+impl std::error::Error for Fault {}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Fault::UnknownOpcode { pc, opcode } => write!(f, "unknown opcode {:04X} at {:#05X}", opcode, pc),
+            Fault::StackOverflow { pc } => write!(f, "stack overflow at {:#05X}", pc),
+            Fault::StackUnderflow { pc } => write!(f, "return with an empty stack at {:#05X}", pc),
+            Fault::MemoryOutOfBounds { pc, address } => {
+                write!(f, "access to {:#X} past the end of memory at {:#05X}", address, pc)
+            }
+            Fault::WriteBelowStart { pc, address } => {
+                write!(f, "write to {:#05X}, below the program, at {:#05X}", address, pc)
+            }
+            Fault::UnalignedJump { pc, target } => write!(f, "jump to odd address {:#05X} at {:#05X}", target, pc),
+        }
+    }
+}
+
+/// Something the frontend should react to when it happens instead of polling
+/// for it, e.g. to start and stop a tone exactly once.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Event {
+    SoundStarted,
+    SoundStopped,
+}
+
+/// Events kept for a frontend that never takes them, before the oldest are dropped.
+const MAX_EVENTS: usize = 256;
+
+/// Instructions `Chip8::history` keeps by default.
+pub const HISTORY_LENGTH: usize = 64;
+
+/// An embedder-provided instruction: opcodes where `opcode & mask == pattern`
+/// call `handler` instead of faulting, with PC already past the opcode.
+#[derive(Clone, Copy, Debug)]
+pub struct OpcodeHandler {
+    pub mask: u16,
+    pub pattern: u16,
+    pub handler: fn(&mut Chip8, u16),
+}
+
+impl PartialEq for OpcodeHandler {
+    fn eq(&self, other: &Self) -> bool {
+        self.mask == other.mask && self.pattern == other.pattern && std::ptr::fn_addr_eq(self.handler, other.handler)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub struct Chip8 {
-    pub memory: [u8; 4096], // 4K memory
+    pub memory: Vec<u8>, // 4K memory, more for big MegaChip ROMs
     pub v: [u8; 16], // 16 8-bit registers
     pub pc: u16, // program counter
-    pub i: u16, // index register
-    pub stack: [u16; 16], // stack
+    pub i: u32, // index register, 24-bit in MegaChip mode
+    pub stack: Vec<u16>, // return addresses, most recent last
+    pub stack_depth: Option<usize>, // most return addresses the stack holds, None for no limit
     pub timer_delay: u8, // delay timer
     pub timer_sound: u8, // sound timer
-    pub display: [u8; 64 * 32], // display
+    pub display: Vec<u8>, // display, one byte per pixel, row by row
+    pub display_width: usize, // 64, 128 in SCHIP hires mode or 256 in MegaChip mode
+    pub display_height: usize, // 32, 64 in SCHIP hires mode or 192 in MegaChip mode
+    pub display_dirty: bool, // the display changed since take_display_dirty last looked
+    pub initial_resolution: (usize, usize), // display size a reset goes back to
     pub fontset: [u8; 80], // fontset
-    pub keypad: [u8; 16], // keypad
+    pub keypad: Keypad, // keypad
+    pub rpl: [u8; 8], // SCHIP RPL user flags
+    pub quirks: Quirks, // interpreter-specific behaviors
+    pub fault: Option<Fault>, // first fault encountered
+    pub fault_count: u64, // faults raised, the first included
+    pub start_address: u16, // where ROMs are loaded and started
+    pub rom: Vec<u8>, // the loaded ROM, kept for resets
+    pub vblank_wait: bool, // blocked after Dxyn until the next timer tick
+    pub key_wait: bool, // Fx0A is waiting for a key
+    pub rng: Rng, // random numbers for Cxkk
+    pub seed: u64, // what rng started from, restored by a reset
+    pub out_of_bounds: OutOfBounds, // what I-relative accesses past the end of memory do
+    pub strict: bool, // suspicious but legal behavior is a fault too
+    pub latch_input: bool, // a key press stays visible until an instruction has checked it
+    pub megachip: bool, // MegaChip mode, display pixels are palette indexes
+    pub palette: [u32; 256], // MegaChip ARGB colors
+    pub sprite_width: usize, // MegaChip sprite width
+    pub sprite_height: usize, // MegaChip sprite height
+    pub opcode_handlers: Vec<OpcodeHandler>, // custom opcodes, tried before unknown opcodes fault
+    pub sound_playing: bool, // whether the last event sent was SoundStarted
+    pub events: Vec<Event>, // events not yet taken by the frontend
+    pub history: VecDeque<(u16, u16)>, // last fetched (address, opcode) pairs, oldest first
+    pub history_length: usize, // most pairs the history keeps, 0 to keep none
+    pub register_history: register_history::RegisterHistory, // last changes of each V register
+    pub heatmap: Option<heatmap::Heatmap>, // per-address access counts, once enabled
+    pub breakpoints: BTreeSet<u16>, // addresses to pause at before fetching
+    pub breakpoint_conditions: BTreeMap<u16, Expr>, // breakpoints that only pause while their condition is non-zero
+    pub breakpoint_hit: Option<u16>, // breakpoint just stopped at, passed on the next check
+    pub step_over: Option<(u16, usize)>, // where a stepped over call returns to, and the stack depth there
+    pub watchpoints: Vec<RangeInclusive<u32>>, // addresses whose writes pause execution
+    pub watched_registers: Vec<Register>, // registers whose changes pause execution
+    pub break_on_unknown: bool, // pause before fetching an unknown opcode instead of faulting
+    pub break_on_draw: bool, // pause after each Dxyn
+    pub break_on_clear: bool, // pause after each 00E0
+    pub stop: Option<Stop>, // why execution should pause, for the frontend to take
 }
 
 
+/// Addresses wrap around the end of memory instead of indexing past it.
+/// Memory is always a power of two in size.
+fn mem_index(chip8: &Chip8, address: u32) -> usize {
+    address as usize & (chip8.memory.len() - 1)
+}
+
+/// Where the I-relative access at I + `offset` goes, following the
+/// out-of-bounds policy; `None` once it has faulted. An address past u32,
+/// only reachable with I set from outside, is out of bounds like any other.
+fn i_index(chip8: &mut Chip8, offset: u32) -> Option<usize> {
+    if let Some(address) = chip8.i.checked_add(offset).filter(|&address| (address as usize) < chip8.memory.len()) {
+        return Some(address as usize);
+    }
+    let address = chip8.i.wrapping_add(offset);
+    match chip8.out_of_bounds {
+        OutOfBounds::Wrap => Some(mem_index(chip8, address)),
+        OutOfBounds::Clamp => Some(chip8.memory.len() - 1),
+        OutOfBounds::Fault => {
+            let pc = current_pc(chip8);
+            raise_fault(chip8, Fault::MemoryOutOfBounds { pc, address });
+            None
+        }
+    }
+}
+
+/// Reads the byte at I + `offset`.
+fn read_i(chip8: &mut Chip8, offset: u32) -> u8 {
+    let Some(index) = i_index(chip8, offset) else {
+        return 0;
+    };
+    if let Some(heatmap) = chip8.heatmap.as_mut() {
+        heatmap::count(&mut heatmap.reads, index);
+    }
+    chip8.memory[index]
+}
+
+/// Writes the byte at I + `offset`. Strict mode refuses writes below the
+/// program, where the interpreter and font live.
+fn write_i(chip8: &mut Chip8, offset: u32, value: u8) {
+    let Some(index) = i_index(chip8, offset) else {
+        return;
+    };
+    if chip8.strict && index < chip8.start_address as usize {
+        let pc = current_pc(chip8);
+        raise_fault(chip8, Fault::WriteBelowStart { pc, address: index as u32 });
+        return;
+    }
+    if !chip8.watchpoints.is_empty() {
+        let pc = current_pc(chip8);
+        debugger::check_write(chip8, pc, index, value);
+    }
+    if let Some(heatmap) = chip8.heatmap.as_mut() {
+        heatmap::count(&mut heatmap.writes, index);
+    }
+    chip8.memory[index] = value;
+}
+
+/// Moves PC to `target`. Strict mode faults on odd targets, which are almost
+/// always a mistake since instructions are two bytes.
+fn jump_to(chip8: &mut Chip8, target: u16) {
+    if chip8.strict && target % 2 == 1 {
+        let pc = current_pc(chip8);
+        raise_fault(chip8, Fault::UnalignedJump { pc, target });
+    }
+    chip8.pc = target;
+}
+
+/// Like `mem_index`, but PC never leaves the first 64K.
+fn wrap_pc(chip8: &Chip8, address: u16) -> u16 {
+    (address as usize & (chip8.memory.len().min(0x10000) - 1)) as u16
+}
+
 const FONT_SET: [u8; 80] = [
     0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
     0x20, 0x60, 0x20, 0x20, 0x70, // 1
@@ -34,23 +278,97 @@ const FONT_SET: [u8; 80] = [
     0xF0, 0x80, 0xF0, 0x80, 0x80  // F
 ];
 
+/// SCHIP 8x10 digits for Fx30, stored right after the small font.
+const BIG_FONT_ADDRESS: usize = 0x50;
+const BIG_FONT_SET: [u8; 160] = [
+    0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, // 0
+    0x18, 0x78, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0xFF, 0xFF, // 1
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // 2
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 3
+    0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0x03, 0x03, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 5
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 6
+    0xFF, 0xFF, 0x03, 0x03, 0x06, 0x0C, 0x18, 0x18, 0x18, 0x18, // 7
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 8
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 9
+    0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, // A
+    0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, // B
+    0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C, // C
+    0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, // D
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // E
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0  // F
+];
+
 pub fn initialize () -> Chip8 {
     let mut chip8 = Chip8 {
-        memory: [0; 4096],
+        memory: vec![0; 4096],
         v: [0; 16],
         pc: 0x200,
         i: 0,
-        stack: [0; 16],
+        stack: Vec::with_capacity(16),
+        stack_depth: Some(16),
         timer_delay: 0,
         timer_sound: 0,
-        display: [0; 64 * 32],
+        display: vec![0; 64 * 32],
+        display_width: 64,
+        display_height: 32,
+        display_dirty: true,
+        initial_resolution: (64, 32),
         fontset: FONT_SET,
-        keypad: [0; 16],
+        keypad: Keypad::default(),
+        rpl: [0; 8],
+        quirks: Quirks::default(),
+        fault: None,
+        fault_count: 0,
+        start_address: 0x200,
+        rom: Vec::new(),
+        vblank_wait: false,
+        key_wait: false,
+        rng: Rng::new(0),
+        seed: 0,
+        out_of_bounds: OutOfBounds::Wrap,
+        strict: false,
+        latch_input: false,
+        megachip: false,
+        palette: [0; 256],
+        sprite_width: 0,
+        sprite_height: 0,
+        opcode_handlers: Vec::new(),
+        sound_playing: false,
+        events: Vec::new(),
+        history: VecDeque::with_capacity(HISTORY_LENGTH),
+        history_length: HISTORY_LENGTH,
+        register_history: register_history::RegisterHistory::default(),
+        heatmap: None,
+        breakpoints: BTreeSet::new(),
+        breakpoint_conditions: BTreeMap::new(),
+        breakpoint_hit: None,
+        step_over: None,
+        watchpoints: Vec::new(),
+        watched_registers: Vec::new(),
+        break_on_unknown: false,
+        break_on_draw: false,
+        break_on_clear: false,
+        stop: None,
     };
 
     initialize_memory(&mut chip8);
+    set_seed(&mut chip8, rand::random());
+
+    chip8
+}
 
-    return chip8;
+/// Like `initialize`, but Cxkk produces the same numbers on every run.
+pub fn with_seed(seed: u64) -> Chip8 {
+    let mut chip8 = initialize();
+    set_seed(&mut chip8, seed);
+    chip8
+}
+
+/// Restarts Cxkk's numbers from `seed`; resets start them over from it.
+pub fn set_seed(chip8: &mut Chip8, seed: u64) {
+    chip8.seed = seed;
+    chip8.rng = Rng::new(seed);
 }
 
 pub fn initialize_memory (chip8: &mut Chip8) {
@@ -59,325 +377,859 @@ pub fn initialize_memory (chip8: &mut Chip8) {
         chip8.memory[i] = chip8.fontset[i];
     }
 
-    for i in 81..4096 {
+    for i in 81..chip8.memory.len() {
         chip8.memory[i] = 0;
     }
+
+    chip8.memory[BIG_FONT_ADDRESS..BIG_FONT_ADDRESS + BIG_FONT_SET.len()].copy_from_slice(&BIG_FONT_SET);
+}
+
+/// Copies `rom` to the start address. Memory grows past 4K for ROMs that need
+/// it, up to the MegaChip address space; anything that does not fit is dropped.
+pub fn load_rom(chip8: &mut Chip8, rom: &[u8]) {
+    let start = chip8.start_address as usize;
+    let size = (start + rom.len()).next_power_of_two().min(megachip::MEGACHIP_MEMORY_SIZE);
+    if size > chip8.memory.len() {
+        chip8.memory.resize(size, 0);
+        if let Some(heatmap) = chip8.heatmap.as_mut() {
+            heatmap.resize(size);
+        }
+    }
+    let len = rom.len().min(chip8.memory.len() - start);
+    chip8.memory[start..start + len].copy_from_slice(&rom[..len]);
+    chip8.rom = rom.to_vec();
 }
 
+/// Restarts the loaded ROM as if the machine had just been switched on,
+/// keeping the configuration and the SCHIP RPL user flags.
+pub fn reset(chip8: &mut Chip8) {
+    chip8.v = [0; 16];
+    chip8.i = 0;
+    chip8.pc = chip8.start_address;
+    chip8.stack.clear();
+    chip8.timer_delay = 0;
+    chip8.timer_sound = 0;
+    chip8.fault = None;
+    chip8.fault_count = 0;
+    chip8.rng = Rng::new(chip8.seed);
+    chip8.stop = None;
+    chip8.breakpoint_hit = None;
+    chip8.step_over = None;
+    chip8.history.clear();
+    chip8.register_history.clear();
+    chip8.vblank_wait = false;
+    chip8.key_wait = false;
+    chip8.keypad.clear_edges();
+    chip8.megachip = false;
+    chip8.palette = [0; 256];
+    chip8.sprite_width = 0;
+    chip8.sprite_height = 0;
+    let (width, height) = chip8.initial_resolution;
+    set_resolution(chip8, width, height);
+    initialize_memory(chip8);
+    let rom = std::mem::take(&mut chip8.rom);
+    load_rom(chip8, &rom);
+    update_sound(chip8);
+}
+
+/// Replaces the small hex digit font Fx29 points at.
+pub fn set_font(chip8: &mut Chip8, font: [u8; 80]) {
+    chip8.fontset = font;
+    chip8.memory[..font.len()].copy_from_slice(&font);
+}
+
+/// Resizes memory to `size` bytes, e.g. 64K for XO-CHIP, keeping what it
+/// holds. Addresses wrap at the new size, so it must be a power of two.
+pub fn set_memory_size(chip8: &mut Chip8, size: usize) {
+    assert!(size.is_power_of_two(), "memory size must be a power of two");
+    chip8.memory.resize(size, 0);
+    if let Some(heatmap) = chip8.heatmap.as_mut() {
+        heatmap.resize(size);
+    }
+}
+
+/// Adds a handler for opcodes the interpreter doesn't know, so homebrew or
+/// experimental instructions don't need a fork. Handlers registered first win.
+pub fn register_opcode_handler(chip8: &mut Chip8, mask: u16, pattern: u16, handler: fn(&mut Chip8, u16)) {
+    chip8.opcode_handlers.push(OpcodeHandler { mask, pattern, handler });
+}
+
+/// Loads and starts ROMs at `address` instead of 0x200.
+pub fn set_start_address(chip8: &mut Chip8, address: u16) {
+    chip8.start_address = address;
+    chip8.pc = address;
+}
+
+/// ETI-660 programs start at 0x600 and draw on a 64x48 display.
+pub fn set_eti660(chip8: &mut Chip8) {
+    set_start_address(chip8, 0x600);
+    set_resolution(chip8, 64, 48);
+    chip8.initial_resolution = (64, 48);
+}
+
+/// The opcode at `address`, wrapping around the end of memory.
+pub fn read_opcode(chip8: &Chip8, address: u16) -> u16 {
+    let address = address as u32;
+    (chip8.memory[mem_index(chip8, address)] as u16) << 8 | (chip8.memory[mem_index(chip8, address + 1)] as u16)
+}
+
+/// The instruction at PC, without moving past it.
+pub fn peek_opcode(chip8: &Chip8) -> u16 {
+    read_opcode(chip8, chip8.pc)
+}
+
+/// The instruction at PC, moving PC past it and adding it to the history.
 pub fn fetch_opcode(chip8: &mut Chip8) -> u16 {
-    let opcode = (chip8.memory[chip8.pc as usize] as u16) << 8 | (chip8.memory[(chip8.pc + 1) as usize] as u16);
-    chip8.pc += 2;
-    return opcode;
+    let opcode = read_opcode(chip8, chip8.pc);
+    if chip8.history_length > 0 {
+        while chip8.history.len() >= chip8.history_length {
+            chip8.history.pop_front();
+        }
+        chip8.history.push_back((chip8.pc, opcode));
+    }
+    let (first, second) = (mem_index(chip8, chip8.pc as u32), mem_index(chip8, chip8.pc as u32 + 1));
+    if let Some(heatmap) = chip8.heatmap.as_mut() {
+        heatmap::count(&mut heatmap.executes, first);
+        heatmap::count(&mut heatmap.executes, second);
+    }
+    chip8.pc = wrap_pc(chip8, chip8.pc.wrapping_add(2));
+    opcode
+}
+
+/// Writes `bytes` starting at `address`, wrapping around the end of memory.
+pub fn write_memory(chip8: &mut Chip8, address: u16, bytes: &[u8]) {
+    for (offset, byte) in bytes.iter().enumerate() {
+        let index = mem_index(chip8, address.wrapping_add(offset as u16) as u32);
+        chip8.memory[index] = *byte;
+    }
+}
+
+/// Pushes a return address, returning false if the stack is full.
+pub fn push_stack(chip8: &mut Chip8, address: u16) -> bool {
+    if chip8.stack_depth.is_none_or(|depth| chip8.stack.len() < depth) {
+        chip8.stack.push(address);
+        true
+    } else {
+        false
+    }
+}
+
+/// Pops the most recent return address, if any.
+pub fn pop_stack(chip8: &mut Chip8) -> Option<u16> {
+    chip8.stack.pop()
+}
+
+/// Fetches and executes a single instruction, or does nothing while waiting
+/// for the display.
+pub fn step(chip8: &mut Chip8) {
+    if chip8.vblank_wait {
+        return;
+    }
+    let opcode = fetch_opcode(chip8);
+    execute_opcode(opcode, chip8);
+}
+
+/// Executes one already decoded instruction as if it had been fetched from PC,
+/// with `keypad` as the keypad. Lets debuggers, fuzzers and teaching tools
+/// drive the core without assembling raw opcodes.
+pub fn step_instruction(chip8: &mut Chip8, instruction: Instruction, keypad: &Keypad) {
+    chip8.keypad = *keypad;
+    chip8.pc = wrap_pc(chip8, chip8.pc.wrapping_add(2));
+    execute_instruction(instruction, chip8);
+}
+
+/// Executes at most `instructions` instructions without touching the timers,
+/// so untrusted ROMs that loop forever still return control to the caller.
+pub fn run(chip8: &mut Chip8, instructions: u64) {
+    for _ in 0..instructions {
+        step(chip8);
+    }
+}
+
+/// Whether the ROM has finished: the instruction at PC jumps to itself, the
+/// usual way CHIP-8 programs and test ROMs end, or it has exited.
+pub fn is_halted(chip8: &Chip8) -> bool {
+    read_opcode(chip8, chip8.pc) == 0x1000 | chip8.pc || has_exited(chip8)
+}
+
+/// Whether the ROM asked the interpreter to exit with 00FD (SCHIP).
+pub fn has_exited(chip8: &Chip8) -> bool {
+    read_opcode(chip8, chip8.pc) == 0x00FD
+}
+
+/// Switches between the 64x32, SCHIP 128x64 and MegaChip displays, clearing it.
+pub fn set_resolution(chip8: &mut Chip8, width: usize, height: usize) {
+    chip8.display_width = width;
+    chip8.display_height = height;
+    chip8.display = vec![0; width * height];
+    chip8.display_dirty = true;
+}
+
+/// Whether the display changed since the last call, e.g. to skip redrawing
+/// identical frames. A new machine starts out dirty.
+pub fn take_display_dirty(chip8: &mut Chip8) -> bool {
+    std::mem::take(&mut chip8.display_dirty)
+}
+
+/// Scrolling moves pixels unless the display is blank or it scrolls by 0.
+fn mark_scrolled(chip8: &mut Chip8, distance: usize) {
+    chip8.display_dirty |= distance > 0 && chip8.display.iter().any(|&pixel| pixel != 0);
+}
+
+fn scroll_down(chip8: &mut Chip8, rows: usize) {
+    mark_scrolled(chip8, rows);
+    let width = chip8.display_width;
+    let len = chip8.display.len();
+    let shift = (rows * width).min(len);
+    chip8.display.copy_within(..len - shift, shift);
+    chip8.display[..shift].fill(0);
+}
+
+fn scroll_up(chip8: &mut Chip8, rows: usize) {
+    mark_scrolled(chip8, rows);
+    let width = chip8.display_width;
+    let len = chip8.display.len();
+    let shift = (rows * width).min(len);
+    chip8.display.copy_within(shift.., 0);
+    chip8.display[len - shift..].fill(0);
+}
+
+fn scroll_horizontally(chip8: &mut Chip8, columns: usize, right: bool) {
+    mark_scrolled(chip8, columns);
+    let width = chip8.display_width;
+    for row in chip8.display.chunks_mut(width) {
+        if right {
+            row.copy_within(..width - columns, columns);
+            row[..columns].fill(0);
+        } else {
+            row.copy_within(columns.., 0);
+            row[width - columns..].fill(0);
+        }
+    }
+}
+
+fn raise_fault(chip8: &mut Chip8, fault: Fault) {
+    warn!(target: "core", "{}", fault);
+    chip8.fault_count += 1;
+    if chip8.fault.is_none() {
+        chip8.fault = Some(fault);
+    }
+}
+
+/// Address of the instruction being executed; PC already points past it.
+fn current_pc(chip8: &Chip8) -> u16 {
+    wrap_pc(chip8, chip8.pc.wrapping_sub(2))
+}
+
+/// Moves I past the registers Fx55/Fx65 just touched, as the quirk says.
+fn advance_i_after_registers(chip8: &mut Chip8, x: u8) {
+    match chip8.quirks.memory_increment_i {
+        MemoryIncrement::None => {},
+        MemoryIncrement::X => chip8.i = chip8.i.wrapping_add(x as u32) & 0xFF_FFFF,
+        MemoryIncrement::XPlusOne => chip8.i = chip8.i.wrapping_add(x as u32 + 1) & 0xFF_FFFF,
+    }
+}
+
+fn skip_next_instruction(chip8: &mut Chip8) {
+    chip8.pc = wrap_pc(chip8, chip8.pc.wrapping_add(2));
 }
 
 pub fn execute_opcode(opcode: u16, chip8: &mut Chip8) {
-    let first_nibble = ((opcode & 0xF000) >> 12) as u8;
-    let second_nibble = ((opcode & 0x0F00) >> 8) as u8;
-    let third_nibble = ((opcode & 0x00F0) >> 4) as u8;
-    let fourth_nibble = (opcode & 0x000F) as u8;
-    let nnn = opcode & 0x0FFF;
-    let kk = (opcode & 0x00FF) as u8;
-
-    match first_nibble  {
-        0 => {
-            if second_nibble == 0 {
-                if fourth_nibble == 0xE {
-                    // 00EE - return from subroutine
-                    chip8.pc = chip8.stack[chip8.stack.len() - 1];
-                } else {
-                    // clear screen
-                    for i in 0..chip8.display.len() {
-                        chip8.display[i] = 0;
-                    }
-                } 
+    execute_instruction(decode(opcode), chip8);
+}
+
+/// Whether Ex9E and ExA1 see `key` as down: held, or with input latching
+/// pressed since it was last checked, so taps between instructions at low
+/// speeds still register once.
+fn key_down(chip8: &mut Chip8, key: usize) -> bool {
+    let latched = chip8.keypad.take_latched(key);
+    chip8.keypad.is_down(key) || (chip8.latch_input && latched)
+}
+
+/// Executes an already decoded instruction. PC should already point past it,
+/// as it does after `fetch_opcode`.
+pub fn execute_instruction(instruction: Instruction, chip8: &mut Chip8) {
+    let pc = current_pc(chip8);
+    let registers = chip8.v;
+    if chip8.watched_registers.is_empty() {
+        execute(instruction, chip8);
+    } else {
+        let before: Vec<u32> = chip8.watched_registers.iter().map(|register| register.value(chip8)).collect();
+        execute(instruction, chip8);
+        debugger::check_registers(chip8, pc, instruction, &before);
+    }
+    chip8.register_history.record(pc, &registers, &chip8.v);
+    if chip8.break_on_draw || chip8.break_on_clear {
+        debugger::check_display(chip8, pc, instruction);
+    }
+}
+
+fn execute(instruction: Instruction, chip8: &mut Chip8) {
+    match instruction {
+        Instruction::ClearScreen => {
+            // 00E0 - clear screen
+            chip8.display_dirty |= chip8.display.iter().any(|&pixel| pixel != 0);
+            chip8.display.fill(0);
+        },
+        Instruction::Return => {
+            // 00EE - return from subroutine
+            match pop_stack(chip8) {
+                Some(address) => jump_to(chip8, address),
+                None => {
+                    let pc = current_pc(chip8);
+                    raise_fault(chip8, Fault::StackUnderflow { pc });
+                }
             }
         },
-        1 => {
+        Instruction::ScrollRight => {
+            // 00FB - scroll the display right by 4 pixels (SCHIP)
+            scroll_horizontally(chip8, 4, true);
+        },
+        Instruction::ScrollLeft => {
+            // 00FC - scroll the display left by 4 pixels (SCHIP)
+            scroll_horizontally(chip8, 4, false);
+        },
+        Instruction::Exit => {
+            // 00FD - exit the interpreter (SCHIP); stay on this instruction
+            chip8.pc = current_pc(chip8);
+        },
+        Instruction::LowResolution => {
+            // 00FE - switch to 64x32 low resolution (SCHIP)
+            set_resolution(chip8, 64, 32);
+        },
+        Instruction::HighResolution => {
+            // 00FF - switch to 128x64 high resolution (SCHIP)
+            set_resolution(chip8, 128, 64);
+        },
+        Instruction::ScrollDown { n } => {
+            // 00Cn - scroll the display down by n pixels (SCHIP)
+            scroll_down(chip8, n as usize);
+        },
+        Instruction::MegaChipOff => {
+            // 0010 - leave MegaChip mode
+            megachip::set_megachip(chip8, false);
+        },
+        Instruction::MegaChipOn => {
+            // 0011 - enter MegaChip mode
+            megachip::set_megachip(chip8, true);
+        },
+        Instruction::ScrollUp { n } if chip8.megachip => {
+            // 00Bn - scroll the display up by n pixels (MegaChip)
+            scroll_up(chip8, n as usize);
+        },
+        Instruction::LoadLongI { high } if chip8.megachip => {
+            // 01nn nnnn - set I = 24-bit address nnnnnn (MegaChip)
+            let low = read_opcode(chip8, chip8.pc);
+            chip8.i = (high as u32) << 16 | low as u32;
+            skip_next_instruction(chip8);
+        },
+        Instruction::LoadPalette { count } if chip8.megachip => {
+            // 02nn - load nn palette colors from I (MegaChip)
+            megachip::load_palette(chip8, count as usize);
+        },
+        Instruction::SpriteWidth { width } if chip8.megachip => {
+            // 03nn - set sprite width to nn, 0 meaning 256 (MegaChip)
+            chip8.sprite_width = if width == 0 { 256 } else { width as usize };
+        },
+        Instruction::SpriteHeight { height } if chip8.megachip => {
+            // 04nn - set sprite height to nn, 0 meaning 256 (MegaChip)
+            chip8.sprite_height = if height == 0 { 256 } else { height as usize };
+        },
+        Instruction::Sys { .. }
+        | Instruction::ScrollUp { .. }
+        | Instruction::LoadLongI { .. }
+        | Instruction::LoadPalette { .. }
+        | Instruction::SpriteWidth { .. }
+        | Instruction::SpriteHeight { .. } => {
+            // 0nnn - call machine code routine, ignored by modern interpreters
+            // 05nn, 060n, 0700, 080n - MegaChip alpha, sound and blend mode, ignored
+            // MegaChip instructions outside MegaChip mode are 0nnn too.
+        },
+        Instruction::Jump { nnn } => {
             // 1nnn - jump to address nnn
-            chip8.pc = nnn;
+            jump_to(chip8, nnn);
         },
-        2 => {
+        Instruction::Call { nnn } => {
             // 2nnn - call subroutine at nnn
-            chip8.stack[chip8.stack.len() - 1] = chip8.pc;
-            chip8.pc = nnn;
+            if push_stack(chip8, chip8.pc) {
+                jump_to(chip8, nnn);
+            } else {
+                let pc = current_pc(chip8);
+                raise_fault(chip8, Fault::StackOverflow { pc });
+            }
         },
-        3 => {
+        Instruction::SkipIfEqual { x, kk } => {
             // 3xkk - skip next instruction if Vx = kk
-            if chip8.v[second_nibble as usize] == kk {
-                chip8.pc += 2;
+            if chip8.v[x as usize] == kk {
+                skip_next_instruction(chip8);
             }
         },
-        4 => {
+        Instruction::SkipIfNotEqual { x, kk } => {
             // 4xkk - skip next instruction if Vx != kk
-            if chip8.v[second_nibble as usize] != kk {
-                chip8.pc += 2;
+            if chip8.v[x as usize] != kk {
+                skip_next_instruction(chip8);
             }
         },
-        5 => {
+        Instruction::SkipIfRegistersEqual { x, y } => {
             // 5xy0 - skip next instruction if Vx = Vy
-            if chip8.v[second_nibble as usize] == chip8.v[third_nibble as usize] {
-                chip8.pc += 2;
+            if chip8.v[x as usize] == chip8.v[y as usize] {
+                skip_next_instruction(chip8);
             }
         },
-        6 => {
+        Instruction::Load { x, kk } => {
             // 6xkk - set Vx = kk
-            chip8.v[second_nibble as usize] = kk;
+            chip8.v[x as usize] = kk;
         },
-        7 => {
+        Instruction::Add { x, kk } => {
             // 7xkk - set Vx = Vx + kk
-            let vx = chip8.v[second_nibble as usize] as u16;
+            let vx = chip8.v[x as usize] as u16;
             let val = kk as u16;
             let sum = vx + val;
-            chip8.v[second_nibble as usize] = sum as u8;
+            chip8.v[x as usize] = sum as u8;
 
         },
-        8 => {
-            match fourth_nibble {
-                0 => {
-                    // 8xy0 - set Vx = Vy
-                    chip8.v[second_nibble as usize] = chip8.v[third_nibble as usize];
-                },
-                1 => {
-                    // 8xy1 - set Vx = Vx OR Vy
-                    chip8.v[second_nibble as usize] |= chip8.v[third_nibble as usize];
-                },
-                2 => {
-                    // 8xy2 - set Vx = Vx AND Vy
-                    chip8.v[second_nibble as usize] &= chip8.v[third_nibble as usize];
-                },
-                3 => {
-                    // 8xy3 - set Vx = Vx XOR Vy
-                    chip8.v[second_nibble as usize] ^= chip8.v[third_nibble as usize];
-                },
-                4 => {
-                    // 8xy4 - set Vx = Vx + Vy, set VF = carry
-                    let vx = chip8.v[second_nibble as usize] as u16;
-                    let vy = chip8.v[third_nibble as usize] as u16;
-                    let result = vx + vy;
-                    chip8.v[0x0F] = if result > 255 { 1 } else { 0 };
-                    chip8.v[second_nibble as usize] = result as u8;
-                },
-                5 => {
-                    // 8xy5 - set Vx = Vx - Vy, set VF = NOT borrow
-                    chip8.v[second_nibble as usize] = chip8.v[second_nibble as usize].wrapping_sub(chip8.v[third_nibble as usize]);
-                    chip8.v[0x0F] = if chip8.v[second_nibble as usize] > chip8.v[third_nibble as usize] { 1 } else { 0 };
-                },
-                6 => {
-                    // 8xy6 - set Vx = Vx SHR 1
-                    chip8.v[0x0F] = chip8.v[second_nibble as usize] & 1;
-                    chip8.v[second_nibble as usize] >>= 1;
-                },
-                7 => {
-                    // 8xy7 - set Vx = Vy - Vx, set VF = NOT borrow
-                    chip8.v[0x0F] = if chip8.v[third_nibble as usize] > chip8.v[second_nibble as usize] { 1 } else { 0 };
-                    chip8.v[second_nibble as usize] = chip8.v[third_nibble as usize].wrapping_sub(chip8.v[second_nibble as usize]);
-                },
-                0xE => {
-                    // 8xyE - set Vx = Vx SHL 1
-                    chip8.v[0x0F] = (chip8.v[second_nibble as usize] & 0b10000000) >> 7;
-                    chip8.v[second_nibble as usize] <<= 1;
-                },
-                _ => println!("Unknown opcode: {:X}", opcode),
+        Instruction::Move { x, y } => {
+            // 8xy0 - set Vx = Vy
+            chip8.v[x as usize] = chip8.v[y as usize];
+        },
+        Instruction::Or { x, y } => {
+            // 8xy1 - set Vx = Vx OR Vy, VF = 0 with the vf_reset quirk
+            chip8.v[x as usize] |= chip8.v[y as usize];
+            if chip8.quirks.vf_reset {
+                chip8.v[0xF] = 0;
+            }
+        },
+        Instruction::And { x, y } => {
+            // 8xy2 - set Vx = Vx AND Vy, VF = 0 with the vf_reset quirk
+            chip8.v[x as usize] &= chip8.v[y as usize];
+            if chip8.quirks.vf_reset {
+                chip8.v[0xF] = 0;
+            }
+        },
+        Instruction::Xor { x, y } => {
+            // 8xy3 - set Vx = Vx XOR Vy, VF = 0 with the vf_reset quirk
+            chip8.v[x as usize] ^= chip8.v[y as usize];
+            if chip8.quirks.vf_reset {
+                chip8.v[0xF] = 0;
             }
         },
-        9 => {
+        Instruction::AddRegisters { x, y } => {
+            // 8xy4 - set Vx = Vx + Vy, set VF = carry
+            let vx = chip8.v[x as usize] as u16;
+            let vy = chip8.v[y as usize] as u16;
+            let result = vx + vy;
+            chip8.v[0x0F] = if result > 255 { 1 } else { 0 };
+            chip8.v[x as usize] = result as u8;
+        },
+        Instruction::Subtract { x, y } => {
+            // 8xy5 - set Vx = Vx - Vy, set VF = NOT borrow
+            chip8.v[x as usize] = chip8.v[x as usize].wrapping_sub(chip8.v[y as usize]);
+            chip8.v[0x0F] = if chip8.v[x as usize] > chip8.v[y as usize] { 1 } else { 0 };
+        },
+        Instruction::ShiftRight { x, y } => {
+            // 8xy6 - set Vx = Vx SHR 1 (Vy SHR 1 with the shift_vy quirk)
+            let source = if chip8.quirks.shift_vy { y } else { x };
+            let value = chip8.v[source as usize];
+            chip8.v[0x0F] = value & 1;
+            chip8.v[x as usize] = value >> 1;
+        },
+        Instruction::SubtractReversed { x, y } => {
+            // 8xy7 - set Vx = Vy - Vx, set VF = NOT borrow
+            chip8.v[0x0F] = if chip8.v[y as usize] > chip8.v[x as usize] { 1 } else { 0 };
+            chip8.v[x as usize] = chip8.v[y as usize].wrapping_sub(chip8.v[x as usize]);
+        },
+        Instruction::ShiftLeft { x, y } => {
+            // 8xyE - set Vx = Vx SHL 1 (Vy SHL 1 with the shift_vy quirk)
+            let source = if chip8.quirks.shift_vy { y } else { x };
+            let value = chip8.v[source as usize];
+            chip8.v[0x0F] = (value & 0b10000000) >> 7;
+            chip8.v[x as usize] = value << 1;
+        },
+        Instruction::SkipIfRegistersNotEqual { x, y } => {
             // 9xy0 - skip next instruction if Vx != Vy
-            if chip8.v[second_nibble as usize] != chip8.v[third_nibble as usize] {
-                chip8.pc += 2;
+            if chip8.v[x as usize] != chip8.v[y as usize] {
+                skip_next_instruction(chip8);
             }
         },
-        0xA => {
+        Instruction::LoadI { nnn } => {
             // Annn - set I = nnn
-            chip8.i = nnn;
+            chip8.i = nnn as u32;
         },
-        0xB => {
-            // Bnnn - jump to location nnn + V0
-            chip8.pc = nnn + chip8.v[0] as u16;
+        Instruction::JumpWithOffset { x, nnn } => {
+            // Bnnn - jump to location nnn + V0 (Bxnn - xnn + Vx with the jump_with_vx quirk)
+            let offset = if chip8.quirks.jump_with_vx { chip8.v[x as usize] } else { chip8.v[0] };
+            let target = wrap_pc(chip8, nnn + offset as u16);
+            jump_to(chip8, target);
         },
-        0xC => {
+        Instruction::Random { x, kk } => {
             // Cxkk - set Vx = random byte AND kk
-            let mut rng = rand::thread_rng();
-            let random: u8 = rng.gen_range(0..255);
-            chip8.v[second_nibble as usize] = random & kk;
+            chip8.v[x as usize] = chip8.rng.next_u8() & kk;
         },
-        0xD => {
+        Instruction::Draw { x, y, n } => {
             // Dxyn - display n-byte sprite starting at memory location I at (Vx, Vy), set VF = collision
-            let x = chip8.v[second_nibble as usize] as usize;
-            let y = chip8.v[third_nibble as usize] as usize;
-            let height = fourth_nibble as usize;
+            // Dxy0 - display a 16x16 sprite of 32 bytes (SCHIP)
+            // The sprite starts at (Vx, Vy) wrapped to the screen and is clipped at its edges.
+            // With the display_wait quirk nothing more runs until the next timer tick.
+            chip8.vblank_wait = chip8.quirks.display_wait;
+            let width = chip8.display_width;
+            let x = chip8.v[x as usize] as usize % width;
+            let y = chip8.v[y as usize] as usize % chip8.display_height;
+            if chip8.megachip {
+                // Dxyn - blit the sprite set up by 03nn/04nn, n is ignored (MegaChip)
+                chip8.v[0xF] = megachip::draw_sprite(chip8, x, y);
+                return;
+            }
+            let (height, sprite_width) = if n == 0 { (16, 16) } else { (n as usize, 8) };
+            let visible_rows = height.min(chip8.display_height - y);
             let mut collision: u8 = 0;
-            for yline in 0..height {
-                let pixel = chip8.memory[chip8.i as usize + yline];
-                for xline in 0..8 {
-                    if (pixel & (0x80 >> xline)) != 0 {
-                        let index = (x + xline + ((y + yline) * 64)) as usize;
-                        // check for out of bounds
-                        if index < chip8.display.len() {
-                            if chip8.display[index] == 1 {
-                                collision = 1;
-                            }
-                            chip8.display[index] ^= 1;
-                        } else {
-                            println!("Out of bounds: {}", index);
+            let mut collided_rows: u8 = 0;
+            for yline in 0..visible_rows {
+                let mut row_collided = false;
+                let row = if sprite_width == 16 {
+                    let offset = yline as u32 * 2;
+                    (read_i(chip8, offset) as u16) << 8 | read_i(chip8, offset + 1) as u16
+                } else {
+                    (read_i(chip8, yline as u32) as u16) << 8
+                };
+                for xline in 0..sprite_width.min(width - x) {
+                    if (row & (0x8000 >> xline)) != 0 {
+                        let index = x + xline + ((y + yline) * width);
+                        if chip8.display[index] != 0 {
+                            collision = 1;
+                            row_collided = true;
                         }
+                        chip8.display[index] ^= 1;
+                        chip8.display_dirty = true;
                     }
                 }
+                collided_rows += row_collided as u8;
             }
 
-            chip8.v[0xF] = collision;
+            // SCHIP hires mode counts the rows that collided or fell off the bottom
+            // with the collision_row_count quirk.
+            chip8.v[0xF] = if chip8.quirks.collision_row_count && width == 128 {
+                collided_rows + (height - visible_rows) as u8
+            } else {
+                collision
+            };
         },
-        0xE => {
-            match kk {
-                0x9E => {
-                    // Ex9E - skip next instruction if key with the value of Vx is pressed
-                    if chip8.keypad[chip8.v[second_nibble as usize] as usize] != 0 {
-                        //println!("Key pressed: {}", chip8.keypad[chip8.v[second_nibble as usize] as usize]);
-                        chip8.pc += 2;
-                    }
-                },
-                0xA1 => {
-                    // ExA1 - skip next instruction if key with the value of Vx is not pressed
-                    if chip8.keypad[chip8.v[second_nibble as usize] as usize] == 0 {
-                        //println!("Key not pressed: {}", chip8.keypad[chip8.v[second_nibble as usize] as usize]);
-                        chip8.pc += 2;
-                    }
-                },
-                _ => println!("Unknown opcode: {:X}", opcode),
+        Instruction::SkipIfKeyDown { x } => {
+            // Ex9E - skip next instruction if key with the value of Vx is pressed
+            if key_down(chip8, (chip8.v[x as usize] & 0xF) as usize) {
+                skip_next_instruction(chip8);
             }
         },
-        0xF => {
-            match kk {
-                0x07 => {
-                    // Fx07 - set Vx = delay timer value
-                    chip8.v[second_nibble as usize] = chip8.timer_delay;
-                },
-                0x0A => {
-                    // Fx0A - wait for a key press, store the value of the key in Vx
-                    let mut key_pressed = false;
-                    for i in 0..chip8.keypad.len() {
-                        if chip8.keypad[i] != 0 {
-                            chip8.v[second_nibble as usize] = i as u8;
-                            key_pressed = true;
-                            //println!("Key pressed: {} - {}", i, chip8.keypad[chip8.v[second_nibble as usize] as usize]);
-                        }
-                    }
-                    if !key_pressed {
-                        //println!("Waiting for key press...");
-                        chip8.pc -= 2;
-                    }
-                },
-                0x15 => {
-                    // Fx15 - set delay timer = Vx
-                    chip8.timer_delay = chip8.v[second_nibble as usize];
-                },
-                0x18 => {
-                    // Fx18 - set sound timer = Vx
-                    chip8.timer_sound = chip8.v[second_nibble as usize];
-                },
-                0x1E => {
-                    // Fx1E - set I = I + Vx
-                    chip8.i += chip8.v[second_nibble as usize] as u16;
-                },
-                0x29 => {
-                    // Fx29 - set I = location of sprite for digit Vx
-                    chip8.i = chip8.v[second_nibble as usize] as u16 * 0x5;
-                },
-                0x33 => {
-                    // Fx33 - store BCD representation of Vx in memory locations I, I+1, and I+2
-                    let num = chip8.v[second_nibble as usize];
-                    chip8.memory[chip8.i as usize] = num / 100;
-                    chip8.memory[(chip8.i + 1) as usize] = (num % 100) / 10;
-                    chip8.memory[(chip8.i + 2) as usize] = num % 10;
-                },
-                0x55 => {
-                    // Fx55 - store registers V0 through Vx in memory starting at location I
-                    for i in 0..second_nibble + 1 {
-                        chip8.memory[chip8.i as usize + i as usize] = chip8.v[i as usize];
-                    }
-                },
-                0x65 => {
-                    // Fx65 - read registers V0 through Vx from memory starting at location I
-                    for i in 0..second_nibble + 1 {
-                        chip8.v[i as usize] = chip8.memory[chip8.i as usize + i as usize];
-                    }
+        Instruction::SkipIfKeyUp { x } => {
+            // ExA1 - skip next instruction if key with the value of Vx is not pressed
+            if !key_down(chip8, (chip8.v[x as usize] & 0xF) as usize) {
+                skip_next_instruction(chip8);
+            }
+        },
+        Instruction::LoadDelayTimer { x } => {
+            // Fx07 - set Vx = delay timer value
+            chip8.v[x as usize] = chip8.timer_delay;
+        },
+        Instruction::WaitForKey { x } => {
+            // Fx0A - wait for a key press, store the value of the key in Vx
+            // Keys held when the wait starts don't count; a key counts when it goes
+            // down, or when it comes back up with the key_release quirk. With input
+            // latching a press no instruction has checked yet counts too.
+            if !chip8.key_wait {
+                chip8.key_wait = true;
+                chip8.keypad.clear_edges();
+            }
+            let release = chip8.quirks.key_release;
+            let key = (0..16).find(|&key| {
+                if release {
+                    chip8.keypad.was_released(key)
+                } else {
+                    chip8.keypad.was_pressed(key) || (chip8.latch_input && chip8.keypad.is_latched(key))
+                }
+            });
+            match key {
+                Some(key) => {
+                    chip8.keypad.take_latched(key);
+                    chip8.v[x as usize] = key as u8;
+                    chip8.key_wait = false;
                 },
-                _ => println!("Unknown opcode: {:X}", opcode),
+                None => chip8.pc = current_pc(chip8),
             }
-        }
-       _ => println!("Unknown opcode: {:X}", opcode),
+        },
+        Instruction::SetDelayTimer { x } => {
+            // Fx15 - set delay timer = Vx
+            chip8.timer_delay = chip8.v[x as usize];
+        },
+        Instruction::SetSoundTimer { x } => {
+            // Fx18 - set sound timer = Vx
+            chip8.timer_sound = chip8.v[x as usize];
+            update_sound(chip8);
+        },
+        Instruction::AddToI { x } => {
+            // Fx1E - set I = I + Vx, VF = 1 past 0xFFF with the index_overflow quirk
+            chip8.i = chip8.i.wrapping_add(chip8.v[x as usize] as u32) & 0xFF_FFFF;
+            if chip8.quirks.index_overflow {
+                chip8.v[0xF] = if chip8.i > 0xFFF { 1 } else { 0 };
+            }
+        },
+        Instruction::LoadFont { x } => {
+            // Fx29 - set I = location of sprite for digit Vx
+            chip8.i = chip8.v[x as usize] as u32 * 0x5;
+        },
+        Instruction::LoadBigFont { x } => {
+            // Fx30 - set I = location of the 8x10 big sprite for digit Vx (SCHIP)
+            chip8.i = (BIG_FONT_ADDRESS + (chip8.v[x as usize] & 0xF) as usize * 10) as u32;
+        },
+        Instruction::StoreBcd { x } => {
+            // Fx33 - store BCD representation of Vx in memory locations I, I+1, and I+2
+            let num = chip8.v[x as usize];
+            for (offset, digit) in [num / 100, (num % 100) / 10, num % 10].into_iter().enumerate() {
+                write_i(chip8, offset as u32, digit);
+            }
+        },
+        Instruction::StoreRegisters { x } => {
+            // Fx55 - store registers V0 through Vx in memory starting at location I
+            for i in 0..x + 1 {
+                write_i(chip8, i as u32, chip8.v[i as usize]);
+            }
+            advance_i_after_registers(chip8, x);
+        },
+        Instruction::LoadRegisters { x } => {
+            // Fx65 - read registers V0 through Vx from memory starting at location I
+            for i in 0..x + 1 {
+                chip8.v[i as usize] = read_i(chip8, i as u32);
+            }
+            advance_i_after_registers(chip8, x);
+        },
+        Instruction::StoreFlags { x } => {
+            // Fx75 - store V0 through Vx in the RPL user flags, x <= 7 (SCHIP)
+            let count = (x as usize + 1).min(chip8.rpl.len());
+            chip8.rpl[..count].copy_from_slice(&chip8.v[..count]);
+        },
+        Instruction::LoadFlags { x } => {
+            // Fx85 - read V0 through Vx from the RPL user flags, x <= 7 (SCHIP)
+            let count = (x as usize + 1).min(chip8.rpl.len());
+            chip8.v[..count].copy_from_slice(&chip8.rpl[..count]);
+        },
+        Instruction::Unknown { opcode } => {
+            let custom = chip8.opcode_handlers.iter().find(|custom| opcode & custom.mask == custom.pattern);
+            if let Some(custom) = custom {
+                (custom.handler)(chip8, opcode);
+                return;
+            }
+            let pc = current_pc(chip8);
+            raise_fault(chip8, Fault::UnknownOpcode { pc, opcode });
+        },
     }
 }
 
-pub fn handle_keypads (chip8: & mut Chip8, rl_context: &RaylibHandle) {
-    match rl_context.is_key_down(KeyboardKey::KEY_ONE) {
-        true => chip8.keypad[0x1] = 1,
-        false => chip8.keypad[0x1] = 0,
-    }
-    match rl_context.is_key_down(KeyboardKey::KEY_TWO) {
-        true => chip8.keypad[0x2] = 1,
-        false => chip8.keypad[0x2] = 0,
-    }
-    match rl_context.is_key_down(KeyboardKey::KEY_THREE) {
-        true => chip8.keypad[0x3] = 1,
-        false => chip8.keypad[0x3] = 0,
-    }
-    match rl_context.is_key_down(KeyboardKey::KEY_C) {
-        true => chip8.keypad[0xC] = 1,
-        false => chip8.keypad[0xC] = 0,
-    }
-    match rl_context.is_key_down(KeyboardKey::KEY_FOUR) {
-        true => chip8.keypad[0x4] = 1,
-        false => chip8.keypad[0x4] = 0,
+/// Ticks the timers at 60Hz. This is also the vertical blank that ends a
+/// display wait.
+pub fn update_timers(chip8: &mut Chip8) {
+    chip8.vblank_wait = false;
+
+    if chip8.timer_delay > 0 {
+        chip8.timer_delay -= 1;
     }
-    match rl_context.is_key_down(KeyboardKey::KEY_FIVE) {
-        true => chip8.keypad[0x5] = 1,
-        false => chip8.keypad[0x5] = 0,
+
+    if chip8.timer_sound > 0 {
+        chip8.timer_sound -= 1;
     }
-    match rl_context.is_key_down(KeyboardKey::KEY_SIX) {
-        true => chip8.keypad[0x6] = 1,
-        false => chip8.keypad[0x6] = 0,
+    update_sound(chip8);
+}
+
+/// Queues SoundStarted or SoundStopped when the sound timer starts or stops
+/// running.
+fn update_sound(chip8: &mut Chip8) {
+    let playing = chip8.timer_sound > 0;
+    if playing != chip8.sound_playing {
+        chip8.sound_playing = playing;
+        if chip8.events.len() == MAX_EVENTS {
+            chip8.events.remove(0);
+        }
+        chip8.events.push(if playing { Event::SoundStarted } else { Event::SoundStopped });
     }
-    match rl_context.is_key_down(KeyboardKey::KEY_D) {
-        true => chip8.keypad[0xD] = 1,
-        false => chip8.keypad[0xD] = 0,
+}
+
+/// Takes the events that happened since the last call, oldest first.
+pub fn take_events(chip8: &mut Chip8) -> Vec<Event> {
+    std::mem::take(&mut chip8.events)
+}
+
+/// Stable 64-bit FNV-1a hash of the framebuffer, for comparing rendering
+/// output across runs and emulator versions.
+pub fn display_hash(chip8: &Chip8) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for pixel in chip8.display.iter() {
+        hash ^= *pixel as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
     }
-    match rl_context.is_key_down(KeyboardKey::KEY_SEVEN) {
-        true => chip8.keypad[0x7] = 1,
-        false => chip8.keypad[0x7] = 0,
+    hash
+}
+
+/// Renders the display as text, one line per row with `█` for lit pixels
+/// and a space for unlit ones, e.g. for printing over SSH.
+pub fn display_to_string(chip8: &Chip8) -> String {
+    display_to_string_with(chip8, '█', ' ')
+}
+
+/// Renders the display as text with the given characters for lit and unlit
+/// pixels.
+pub fn display_to_string_with(chip8: &Chip8, lit: char, unlit: char) -> String {
+    let width = chip8.display_width;
+    let mut text = String::with_capacity((width * lit.len_utf8().max(unlit.len_utf8()) + 1) * chip8.display_height);
+    for row in chip8.display.chunks(width) {
+        text.extend(row.iter().map(|&pixel| if pixel != 0 { lit } else { unlit }));
+        text.push('\n');
     }
-    match rl_context.is_key_down(KeyboardKey::KEY_EIGHT) {
-        true => chip8.keypad[0x8] = 1,
-        false => chip8.keypad[0x8] = 0,
+    text
+}
+
+/// FNV-1a hash of everything a ROM can observe: registers, stack, timers,
+/// memory and display. Two runs that hash the same after every frame have
+/// behaved identically.
+pub fn state_hash(chip8: &Chip8) -> u64 {
+    let registers = [chip8.pc as u32, chip8.i, chip8.timer_delay as u32, chip8.timer_sound as u32];
+    let bytes = chip8
+        .v
+        .iter()
+        .chain(&chip8.rpl)
+        .copied()
+        .chain(registers.iter().flat_map(|value| value.to_le_bytes()))
+        .chain(chip8.stack.iter().flat_map(|address| address.to_le_bytes()))
+        .chain(chip8.memory.iter().copied())
+        .chain(chip8.display.iter().copied());
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in bytes {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
     }
-    match rl_context.is_key_down(KeyboardKey::KEY_NINE) {
-        true => chip8.keypad[0x9] = 1,
-        false => chip8.keypad[0x9] = 0,
+    hash
+}
+
+/// Host keys for keypad keys 0x0-0xF: each hex key is the same key on the
+/// keyboard.
+const KEY_MAP: [KeyboardKey; 16] = [
+    KeyboardKey::KEY_ZERO,
+    KeyboardKey::KEY_ONE,
+    KeyboardKey::KEY_TWO,
+    KeyboardKey::KEY_THREE,
+    KeyboardKey::KEY_FOUR,
+    KeyboardKey::KEY_FIVE,
+    KeyboardKey::KEY_SIX,
+    KeyboardKey::KEY_SEVEN,
+    KeyboardKey::KEY_EIGHT,
+    KeyboardKey::KEY_NINE,
+    KeyboardKey::KEY_A,
+    KeyboardKey::KEY_B,
+    KeyboardKey::KEY_C,
+    KeyboardKey::KEY_D,
+    KeyboardKey::KEY_E,
+    KeyboardKey::KEY_F,
+];
+
+/// The host key keypad key `key` is mapped to.
+pub fn host_key(key: usize) -> KeyboardKey {
+    KEY_MAP[key]
+}
+
+/// The keypad keys held down on the host keyboard, one bit per key.
+pub fn read_keypad(rl_context: &RaylibHandle) -> u16 {
+    KEY_MAP
+        .iter()
+        .enumerate()
+        .filter(|(_, host_key)| rl_context.is_key_down(**host_key))
+        .fold(0, |down, (key, _)| down | 1 << key)
+}
+
+/// The keypad keys held down through extra (keypad key, host key) bindings,
+/// e.g. a ROM's keymap, one bit per key.
+pub fn read_bound_keys(rl_context: &RaylibHandle, bindings: &[(usize, KeyboardKey)]) -> u16 {
+    bindings
+        .iter()
+        .filter(|(_, host_key)| rl_context.is_key_down(*host_key))
+        .fold(0, |down, (key, _)| down | 1 << key)
+}
+
+/// Sets the keys held down, one bit per key, logging the ones that changed.
+pub fn set_keypad(chip8: &mut Chip8, down: u16) {
+    for key in 0..16 {
+        let pressed = down & 1 << key != 0;
+        if pressed != chip8.keypad.is_down(key) {
+            trace!(target: "input", key, pressed, "keypad");
+        }
     }
-    match rl_context.is_key_down(KeyboardKey::KEY_E) {
-        true => chip8.keypad[0xE] = 1,
-        false => chip8.keypad[0xE] = 0,
+    chip8.keypad.set_all(down);
+}
+
+pub fn handle_keypads(chip8: &mut Chip8, rl_context: &RaylibHandle) {
+    set_keypad(chip8, read_keypad(rl_context));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Runs Fx1E with V0 = `v0` and I = `i`, returning I and VF after it.
+    fn add_to_i(index_overflow: bool, i: u32, v0: u8) -> (u32, u8) {
+        let mut chip8 = initialize();
+        chip8.quirks.index_overflow = index_overflow;
+        chip8.i = i;
+        chip8.v[0] = v0;
+        chip8.v[0xF] = 0xAA;
+        execute_opcode(0xF01E, &mut chip8);
+        (chip8.i, chip8.v[0xF])
     }
-    match rl_context.is_key_down(KeyboardKey::KEY_A) {
-        true => chip8.keypad[0xA] = 1,
-        false => chip8.keypad[0xA] = 0,
+
+    #[test]
+    fn add_to_i_sets_vf_past_0xfff_with_index_overflow() {
+        assert_eq!(add_to_i(true, 0xFFE, 2), (0x1000, 1));
     }
-    match rl_context.is_key_down(KeyboardKey::KEY_ZERO) {
-        true => chip8.keypad[0x0] = 1,
-        false => chip8.keypad[0x0] = 0,
+
+    #[test]
+    fn add_to_i_clears_vf_below_0x1000_with_index_overflow() {
+        assert_eq!(add_to_i(true, 0xFFD, 2), (0xFFF, 0));
     }
-    match rl_context.is_key_down(KeyboardKey::KEY_B) {
-        true => chip8.keypad[0xB] = 1,
-        false => chip8.keypad[0xB] = 0,
+
+    #[test]
+    fn add_to_i_leaves_vf_without_index_overflow() {
+        assert_eq!(add_to_i(false, 0xFFE, 2), (0x1000, 0xAA));
+        assert_eq!(add_to_i(false, 0xFFD, 2), (0xFFF, 0xAA));
     }
-    match rl_context.is_key_down(KeyboardKey::KEY_F) {
-        true => chip8.keypad[0xF] = 1,
-        false => chip8.keypad[0xF] = 0,
+
+    #[test]
+    fn reset_restarts_random_numbers_from_the_seed() {
+        let mut chip8 = with_seed(42);
+        let first: Vec<u8> = (0..8).map(|_| chip8.rng.next_u8()).collect();
+        reset(&mut chip8);
+        let again: Vec<u8> = (0..8).map(|_| chip8.rng.next_u8()).collect();
+        assert_eq!(first, again);
     }
 
-}
+    #[test]
+    fn decode_accepts_only_known_state_formats() {
+        let chip8 = with_seed(0);
+        let mut bytes = state::encode(&state::snapshot(&chip8), &"0".repeat(40));
+        assert!(state::decode(&bytes).is_ok());
+        for version in [0, state::FORMAT_VERSION + 1] {
+            bytes[4..6].copy_from_slice(&version.to_le_bytes());
+            assert!(state::decode(&bytes).is_err(), "format {}", version);
+        }
+    }
 
-pub fn play_beep ( chip8: & mut Chip8) {
-    if chip8.timer_sound > 0 {
-        println!("BEEP");
+    #[test]
+    fn restore_refuses_unsupported_resolutions() {
+        let mut chip8 = with_seed(0);
+        let mut state = state::snapshot(&chip8);
+        for (width, height) in [(0, 32), (64, 0), (0, 0), (100, 50)] {
+            state.display_width = width;
+            state.display_height = height;
+            state.display = vec![0; width * height];
+            assert!(state::restore(&mut chip8, &state).is_err(), "{}x{}", width, height);
+        }
+        state.display_width = 128;
+        state.display_height = 64;
+        state.display = vec![0; 128 * 64];
+        assert_eq!(state::restore(&mut chip8, &state), Ok(()));
+        assert_eq!((chip8.display_width, chip8.display_height), (128, 64));
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,128 @@
+//! Behaviors that differ between CHIP-8 interpreters. ROMs written for one
+//! interpreter often misbehave on another, so they are configurable.
+
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+/// What Fx55/Fx65 leave in I after storing or loading V0 through Vx.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MemoryIncrement {
+    /// I is unchanged (SCHIP 1.1).
+    #[default]
+    None,
+    /// I = I + x (CHIP-48).
+    X,
+    /// I = I + x + 1 (COSMAC VIP).
+    XPlusOne,
+}
+
+impl FromStr for MemoryIncrement {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "none" => Ok(MemoryIncrement::None),
+            "x" => Ok(MemoryIncrement::X),
+            "x-plus-one" => Ok(MemoryIncrement::XPlusOne),
+            _ => Err(format!("expected none, x or x-plus-one, got {}", value)),
+        }
+    }
+}
+
+/// The defaults match what this emulator has always done, except that Fx0A no
+/// longer takes a key that is already held down.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Quirks {
+    /// 8xy6/8xyE shift Vy into Vx (COSMAC VIP) instead of shifting Vx in place.
+    pub shift_vy: bool,
+    /// Bnnn jumps to xnn + Vx (CHIP-48/SCHIP) instead of nnn + V0.
+    pub jump_with_vx: bool,
+    /// How far Fx55/Fx65 move I.
+    pub memory_increment_i: MemoryIncrement,
+    /// 8xy1/8xy2/8xy3 clear VF afterwards (COSMAC VIP).
+    pub vf_reset: bool,
+    /// Dxyn waits for the next 60Hz tick before the ROM carries on (COSMAC VIP).
+    pub display_wait: bool,
+    /// Fx1E sets VF to 1 when I goes past 0xFFF, and to 0 otherwise (Amiga).
+    pub index_overflow: bool,
+    /// Fx0A takes a key when it is released (COSMAC VIP) instead of when it
+    /// goes down.
+    pub key_release: bool,
+    /// Dxyn in hires mode sets VF to the number of sprite rows that collided
+    /// or were clipped off the bottom (SCHIP 1.1) instead of 1.
+    pub collision_row_count: bool,
+}
+
+impl Quirks {
+    /// The original COSMAC VIP interpreter.
+    pub fn vip() -> Self {
+        Quirks {
+            shift_vy: true,
+            jump_with_vx: false,
+            memory_increment_i: MemoryIncrement::XPlusOne,
+            vf_reset: true,
+            display_wait: true,
+            index_overflow: false,
+            key_release: true,
+            collision_row_count: false,
+        }
+    }
+
+    /// SUPER-CHIP 1.1.
+    pub fn schip() -> Self {
+        Quirks {
+            shift_vy: false,
+            jump_with_vx: true,
+            memory_increment_i: MemoryIncrement::None,
+            vf_reset: false,
+            display_wait: false,
+            index_overflow: false,
+            key_release: false,
+            collision_row_count: true,
+        }
+    }
+
+    /// XO-CHIP, as implemented by Octo.
+    pub fn xochip() -> Self {
+        Quirks {
+            shift_vy: true,
+            jump_with_vx: false,
+            memory_increment_i: MemoryIncrement::XPlusOne,
+            vf_reset: false,
+            display_wait: false,
+            index_overflow: false,
+            key_release: false,
+            collision_row_count: false,
+        }
+    }
+
+    /// The HP48 CHIP-48 interpreter.
+    pub fn chip48() -> Self {
+        Quirks {
+            shift_vy: false,
+            jump_with_vx: true,
+            memory_increment_i: MemoryIncrement::X,
+            vf_reset: false,
+            display_wait: false,
+            index_overflow: false,
+            key_release: false,
+            collision_row_count: false,
+        }
+    }
+}
+
+impl FromStr for Quirks {
+    type Err = String;
+
+    /// Parses a preset name.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "chip8" => Ok(Quirks::vip()),
+            "schip" => Ok(Quirks::schip()),
+            "xochip" => Ok(Quirks::xochip()),
+            "chip48" => Ok(Quirks::chip48()),
+            _ => Err(format!("expected chip8, schip, xochip or chip48, got {}", value)),
+        }
+    }
+}
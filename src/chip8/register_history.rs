@@ -0,0 +1,81 @@
+//! The last few changes of each V register, with the instruction that made
+//! them, so a debugger can answer "what set V7 to FF?" after the fact
+//! without a watchpoint set up in advance.
+
+use std::collections::VecDeque;
+use std::fmt;
+
+/// Changes each register's history keeps by default.
+pub const REGISTER_HISTORY_LENGTH: usize = 8;
+
+/// A V register changing from `old` to `new`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RegisterWrite {
+    /// Instructions run before the one that made the change, since power on
+    /// or the last reset.
+    pub cycle: u64,
+    /// Address of the instruction that made the change.
+    pub pc: u16,
+    pub old: u8,
+    pub new: u8,
+}
+
+/// As the debuggers list it, e.g. `2A4 00>FF #18342`.
+impl fmt::Display for RegisterWrite {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:03X} {:02X}>{:02X} #{}", self.pc, self.old, self.new, self.cycle)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RegisterHistory {
+    cycle: u64,
+    writes: [VecDeque<RegisterWrite>; 16],
+    /// Most changes kept per register, 0 to keep none.
+    pub length: usize,
+}
+
+impl Default for RegisterHistory {
+    fn default() -> RegisterHistory {
+        RegisterHistory {
+            cycle: 0,
+            writes: Default::default(),
+            length: REGISTER_HISTORY_LENGTH,
+        }
+    }
+}
+
+impl RegisterHistory {
+    /// The changes of Vx kept, oldest first.
+    pub fn writes(&self, x: usize) -> impl DoubleEndedIterator<Item = &RegisterWrite> + '_ {
+        self.writes[x].iter()
+    }
+
+    /// Instructions run since power on or the last reset.
+    pub fn cycle(&self) -> u64 {
+        self.cycle
+    }
+
+    pub fn clear(&mut self) {
+        self.cycle = 0;
+        self.writes.iter_mut().for_each(VecDeque::clear);
+    }
+
+    /// Called by the core after the instruction at `pc` ran, with the
+    /// registers from before and after it.
+    pub(crate) fn record(&mut self, pc: u16, before: &[u8; 16], after: &[u8; 16]) {
+        if self.length > 0 {
+            for (x, (&old, &new)) in before.iter().zip(after).enumerate() {
+                if old == new {
+                    continue;
+                }
+                let writes = &mut self.writes[x];
+                while writes.len() >= self.length {
+                    writes.pop_front();
+                }
+                writes.push_back(RegisterWrite { cycle: self.cycle, pc, old, new });
+            }
+        }
+        self.cycle += 1;
+    }
+}
@@ -0,0 +1,39 @@
+//! The random numbers behind Cxkk. The generator is part of the machine
+//! state so a run can be replayed exactly from its seed.
+
+use serde::{Deserialize, Serialize};
+
+/// A xorshift64* generator: fast, small and plenty random for games.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // A zero state would only ever produce zeros, so the one seed that
+        // mixes to zero gets the mixing constant instead.
+        let state = seed ^ 0x9E37_79B9_7F4A_7C15;
+        Rng {
+            state: if state == 0 { 0x9E37_79B9_7F4A_7C15 } else { state },
+        }
+    }
+
+    pub fn next_u8(&mut self) -> u8 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        (self.state.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 56) as u8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_seed_produces_random_numbers() {
+        let mut rng = Rng::new(0x9E37_79B9_7F4A_7C15);
+        assert!((0..16).any(|_| rng.next_u8() != 0));
+    }
+}
@@ -0,0 +1,178 @@
+//! Save states: everything a ROM can observe, so a game can be picked up
+//! exactly where it was left. Settings chosen on the command line, such as the
+//! start address or strict mode, are not part of a state.
+
+use std::io::{Read, Write};
+
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+
+use super::{update_sound, Chip8, Quirks, Rng, MEGACHIP_HEIGHT, MEGACHIP_WIDTH};
+
+const MAGIC: &[u8; 4] = b"C8ST";
+
+/// The newest save state file format this build reads and the one it writes.
+pub const FORMAT_VERSION: u16 = 1;
+
+/// The oldest save state file format this build reads; there was never a
+/// format 0.
+const OLDEST_FORMAT_VERSION: u16 = 1;
+
+/// Length of a ROM's SHA-1 in hex, as stored in a save state file.
+const ROM_HASH_LEN: usize = 40;
+
+/// Display sizes the core can switch to: 64x32, ETI-660 64x48, SCHIP 128x64
+/// and MegaChip.
+const RESOLUTIONS: [(usize, usize); 4] = [(64, 32), (64, 48), (128, 64), (MEGACHIP_WIDTH, MEGACHIP_HEIGHT)];
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct State {
+    pub memory: Vec<u8>,
+    pub v: [u8; 16],
+    pub pc: u16,
+    pub i: u32,
+    pub stack: Vec<u16>,
+    pub timer_delay: u8,
+    pub timer_sound: u8,
+    pub display: Vec<u8>,
+    pub display_width: usize,
+    pub display_height: usize,
+    pub rpl: [u8; 8],
+    pub quirks: Quirks,
+    pub vblank_wait: bool,
+    pub key_wait: bool,
+    pub rng: Rng,
+    pub megachip: bool,
+    pub palette: Vec<u32>,
+    pub sprite_width: usize,
+    pub sprite_height: usize,
+}
+
+/// Captures the machine's state.
+pub fn snapshot(chip8: &Chip8) -> State {
+    State {
+        memory: chip8.memory.clone(),
+        v: chip8.v,
+        pc: chip8.pc,
+        i: chip8.i,
+        stack: chip8.stack.clone(),
+        timer_delay: chip8.timer_delay,
+        timer_sound: chip8.timer_sound,
+        display: chip8.display.clone(),
+        display_width: chip8.display_width,
+        display_height: chip8.display_height,
+        rpl: chip8.rpl,
+        quirks: chip8.quirks,
+        vblank_wait: chip8.vblank_wait,
+        key_wait: chip8.key_wait,
+        rng: chip8.rng,
+        megachip: chip8.megachip,
+        palette: chip8.palette.to_vec(),
+        sprite_width: chip8.sprite_width,
+        sprite_height: chip8.sprite_height,
+    }
+}
+
+/// Puts the machine back in a captured state, clearing any fault. Keys held
+/// now stay held, but presses from before the restore are forgotten. States
+/// the core could not run from, e.g. edited by hand, are refused.
+pub fn restore(chip8: &mut Chip8, state: &State) -> Result<(), String> {
+    if !state.memory.len().is_power_of_two() || state.memory.len() < 0x1000 {
+        return Err(format!("memory of {} bytes is not a power of two of at least 4K", state.memory.len()));
+    }
+    if !RESOLUTIONS.contains(&(state.display_width, state.display_height)) {
+        return Err(format!(
+            "display of {}x{} is not a supported resolution",
+            state.display_width, state.display_height
+        ));
+    }
+    if state.display.len() != state.display_width * state.display_height {
+        return Err(format!(
+            "display of {} pixels does not match {}x{}",
+            state.display.len(),
+            state.display_width,
+            state.display_height
+        ));
+    }
+    chip8.memory = state.memory.clone();
+    if let Some(heatmap) = chip8.heatmap.as_mut() {
+        heatmap.resize(chip8.memory.len());
+    }
+    chip8.v = state.v;
+    chip8.pc = state.pc;
+    chip8.i = state.i & 0xFF_FFFF;
+    chip8.stack = state.stack.clone();
+    chip8.timer_delay = state.timer_delay;
+    chip8.timer_sound = state.timer_sound;
+    chip8.display = state.display.clone();
+    chip8.display_width = state.display_width;
+    chip8.display_height = state.display_height;
+    chip8.display_dirty = true;
+    chip8.rpl = state.rpl;
+    chip8.quirks = state.quirks;
+    chip8.vblank_wait = state.vblank_wait;
+    chip8.key_wait = state.key_wait;
+    chip8.rng = state.rng;
+    chip8.megachip = state.megachip;
+    for (entry, color) in chip8.palette.iter_mut().zip(&state.palette) {
+        *entry = *color;
+    }
+    chip8.sprite_width = state.sprite_width;
+    chip8.sprite_height = state.sprite_height;
+    chip8.fault = None;
+    chip8.stop = None;
+    chip8.breakpoint_hit = None;
+    chip8.step_over = None;
+    chip8.keypad.clear_edges();
+    update_sound(chip8);
+    Ok(())
+}
+
+/// Encodes a save state file: a binary header of the magic `C8ST`, the
+/// format version as a little-endian u16 and the ROM's SHA-1 as 40 hex
+/// digits, followed by the state as deflate-compressed JSON. The body is
+/// JSON rather than a binary encoding so older states keep loading as fields
+/// are added.
+pub fn encode(state: &State, rom_hash: &str) -> Vec<u8> {
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    let mut hash = [b'0'; ROM_HASH_LEN];
+    let len = rom_hash.len().min(ROM_HASH_LEN);
+    hash[..len].copy_from_slice(&rom_hash.as_bytes()[..len]);
+    bytes.extend_from_slice(&hash);
+    let mut encoder = DeflateEncoder::new(bytes, Compression::default());
+    // Serializing plain data into memory cannot fail.
+    encoder.write_all(&serde_json::to_vec(state).unwrap()).unwrap();
+    encoder.finish().unwrap()
+}
+
+/// Decodes a save state file, returning the SHA-1 of the ROM it was saved
+/// for along with the state.
+pub fn decode(bytes: &[u8]) -> Result<(String, State), String> {
+    let header_len = MAGIC.len() + 2 + ROM_HASH_LEN;
+    if bytes.len() < header_len || !bytes.starts_with(MAGIC) {
+        return Err("not a chip8 save state".to_string());
+    }
+    let version = u16::from_le_bytes([bytes[4], bytes[5]]);
+    if version > FORMAT_VERSION {
+        return Err(format!(
+            "saved by a newer version of chip8 (format {}, this version reads up to {})",
+            version, FORMAT_VERSION
+        ));
+    }
+    if version < OLDEST_FORMAT_VERSION {
+        return Err(format!(
+            "unknown save state format {} (this version reads formats {} to {})",
+            version, OLDEST_FORMAT_VERSION, FORMAT_VERSION
+        ));
+    }
+    let rom_hash = String::from_utf8_lossy(&bytes[6..header_len]).into_owned();
+    let mut json = Vec::new();
+    DeflateDecoder::new(&bytes[header_len..])
+        .read_to_end(&mut json)
+        .map_err(|error| format!("corrupt save state: {}", error))?;
+    let state = serde_json::from_slice(&json).map_err(|error| format!("corrupt save state: {}", error))?;
+    Ok((rom_hash, state))
+}
@@ -0,0 +1,48 @@
+//! An approximate COSMAC VIP timing model. On the VIP each instruction took
+//! a different number of machine cycles; drawing and clearing the screen
+//! were by far the slowest, so games tuned on it run too fast at a flat
+//! instructions-per-second rate.
+
+use std::time::Duration;
+
+/// The VIP's 1.7609MHz clock takes 8 clock cycles per machine cycle.
+pub const VIP_MACHINE_CYCLES_PER_SECOND: u64 = 1_760_900 / 8;
+
+/// Machine cycles the VIP interpreter spends on `opcode`, including the
+/// fetch and decode overhead. Skips and page crossings are not modelled.
+pub fn vip_cycles(opcode: u16) -> u64 {
+    let x = ((opcode & 0x0F00) >> 8) as u64;
+    let n = (opcode & 0x000F) as u64;
+    match opcode & 0xF000 {
+        0x0000 => match opcode {
+            0x00E0 => 24 + 3078,
+            0x00EE => 10,
+            _ => 40,
+        },
+        0x1000 => 12,
+        0x2000 => 26,
+        0x3000 | 0x4000 => 10,
+        0x5000 | 0x9000 => 14,
+        0x6000 => 6,
+        0x7000 => 10,
+        0x8000 => 20,
+        0xA000 => 12,
+        0xB000 => 22,
+        0xC000 => 36,
+        // Every row is shifted into place and XORed in two passes.
+        0xD000 => 68 + n * 46,
+        0xE000 => 14,
+        0xF000 => match opcode & 0x00FF {
+            0x33 => 80 + 3 * 16,
+            0x55 | 0x65 => 14 + (x + 1) * 14,
+            0x1E | 0x29 => 16,
+            _ => 10,
+        },
+        _ => 10,
+    }
+}
+
+/// How long `opcode` took on the VIP.
+pub fn vip_duration(opcode: u16) -> Duration {
+    Duration::from_nanos(vip_cycles(opcode) * 1_000_000_000 / VIP_MACHINE_CYCLES_PER_SECOND)
+}
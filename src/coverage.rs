@@ -0,0 +1,71 @@
+//! Which bytes of the ROM were ever fetched as an instruction, from the
+//! heatmap's execute counts, for `--coverage-out`.
+
+use std::ops::RangeInclusive;
+
+use chip8::chip8::Chip8;
+use chip8::symbols::Symbols;
+
+/// The runs of consecutive ROM addresses that were, or were not, executed.
+fn ranges(chip8: &Chip8, executed: bool) -> Vec<RangeInclusive<u16>> {
+    let Some(heatmap) = &chip8.heatmap else {
+        return Vec::new();
+    };
+    let start = chip8.start_address as usize;
+    let end = (start + chip8.rom.len()).min(heatmap.executes.len());
+    let mut ranges: Vec<RangeInclusive<u16>> = Vec::new();
+    for address in start..end {
+        if (heatmap.executes[address] > 0) != executed {
+            continue;
+        }
+        let address = address as u16;
+        match ranges.last_mut() {
+            Some(range) if *range.end() + 1 == address => *range = *range.start()..=address,
+            _ => ranges.push(address..=address),
+        }
+    }
+    ranges
+}
+
+fn ranges_json(ranges: &[RangeInclusive<u16>], symbols: &Symbols) -> Vec<serde_json::Value> {
+    ranges
+        .iter()
+        .map(|range| {
+            serde_json::json!({
+                "start": range.start(),
+                "end": range.end(),
+                "bytes": range.len(),
+                "label": symbols.name(*range.start()),
+            })
+        })
+        .collect()
+}
+
+/// The report as JSON: the ROM's size, how many of its bytes ran and what
+/// percentage that is, and the address ranges that ran and that never did.
+pub fn report(chip8: &Chip8, symbols: &Symbols) -> serde_json::Value {
+    let executed = ranges(chip8, true);
+    let executed_bytes: usize = executed.iter().map(|range| range.len()).sum();
+    let percentage = match chip8.rom.len() {
+        0 => 0.0,
+        len => executed_bytes as f64 * 100.0 / len as f64,
+    };
+    serde_json::json!({
+        "rom_bytes": chip8.rom.len(),
+        "executed_bytes": executed_bytes,
+        "percentage": percentage,
+        "executed": ranges_json(&executed, symbols),
+        "never_executed": ranges_json(&ranges(chip8, false), symbols),
+    })
+}
+
+/// Writes the report as pretty-printed JSON to `path`, or to stdout for `-`.
+pub fn write(path: &str, chip8: &Chip8, symbols: &Symbols) -> std::io::Result<()> {
+    let json = serde_json::to_string_pretty(&report(chip8, symbols)).map_err(std::io::Error::other)? + "\n";
+    if path == "-" {
+        print!("{}", json);
+        Ok(())
+    } else {
+        std::fs::write(path, json)
+    }
+}
@@ -0,0 +1,111 @@
+use std::fmt::Write;
+use std::path::PathBuf;
+
+use chip8::chip8::{call_chain, decode, read_opcode, Chip8};
+use chip8::disasm::instruction_text;
+use chip8::harness::display_snapshot;
+use chip8::symbols::Symbols;
+
+use crate::paths;
+
+/// Instructions the report disassembles before and after the faulting one.
+const DISASSEMBLY_CONTEXT: u16 = 8;
+
+/// An instruction as the report lists it, after a `name:` line when a label
+/// marks it.
+fn instruction_line(report: &mut String, chip8: &Chip8, address: u16, marker: &str, symbols: &Symbols) {
+    if let Some(name) = symbols.name(address) {
+        let _ = writeln!(report, "{}:", name);
+    }
+    let opcode = read_opcode(chip8, address);
+    let text = instruction_text(decode(opcode), symbols);
+    let _ = writeln!(report, "{:2} {:03X}  {:04X}  {}", marker, address, opcode, text);
+}
+
+/// The crash as text for people reading a bug report: the fault, the
+/// registers and call stack, the last instructions run, a disassembly
+/// around the faulting instruction and the display.
+pub fn report(chip8: &Chip8, rom_hash: Option<&str>, symbols: &Symbols) -> String {
+    let mut report = String::new();
+    let pc = chip8.fault.map_or(chip8.pc, |fault| fault.pc());
+    match chip8.fault {
+        Some(fault) => {
+            let _ = writeln!(report, "Fault: {} (opcode {:04X})", fault, read_opcode(chip8, pc));
+        }
+        None => report.push_str("Fault: none\n"),
+    }
+    let _ = writeln!(report, "ROM SHA-1: {}", rom_hash.unwrap_or("unknown"));
+
+    report.push_str("\nRegisters\n");
+    for (row, values) in chip8.v.chunks(4).enumerate() {
+        let line: Vec<String> = values
+            .iter()
+            .enumerate()
+            .map(|(column, value)| format!("V{:X}: {:02X}", row * 4 + column, value))
+            .collect();
+        let _ = writeln!(report, "{}", line.join("  "));
+    }
+    let _ = writeln!(
+        report,
+        "PC: {:03X}  I: {:03X}  DT: {:02X}  ST: {:02X}  SP: {}",
+        chip8.pc,
+        chip8.i,
+        chip8.timer_delay,
+        chip8.timer_sound,
+        chip8.stack.len()
+    );
+
+    report.push_str("\nCall stack, most recent first\n");
+    for call in call_chain(chip8) {
+        let _ = writeln!(report, "{}", crate::calls::call_text(&call, symbols));
+    }
+
+    let _ = writeln!(report, "\nLast {} instructions, oldest first", chip8.history.len());
+    for &(address, opcode) in &chip8.history {
+        let text = instruction_text(decode(opcode), symbols);
+        let label = symbols.name(address).map(|name| format!("{}: ", name)).unwrap_or_default();
+        let _ = writeln!(report, "{:03X}  {:04X}  {}{}", address, opcode, label, text);
+    }
+
+    report.push_str("\nDisassembly around the fault\n");
+    // Starting an even number of bytes before PC, so PC starts a line.
+    let start = pc - (pc.min(DISASSEMBLY_CONTEXT * 2) & !1);
+    for address in (start..=pc.saturating_add(DISASSEMBLY_CONTEXT * 2)).step_by(2) {
+        let marker = if address == pc { "->" } else { "" };
+        instruction_line(&mut report, chip8, address, marker, symbols);
+    }
+
+    let _ = writeln!(report, "\nDisplay, {}x{}", chip8.display_width, chip8.display_height);
+    report.push_str(&display_snapshot(chip8));
+    report
+}
+
+/// Writes a crash bundle for a faulted machine to a new directory under
+/// `<cache>/crashes` and returns its path. The bundle holds `report.txt`
+/// (see `report`), `state.json` (see `dump::state_json`), `trace.txt` with
+/// the instructions in the machine's history and `display.png`.
+pub fn write_bundle(chip8: &Chip8, rom_hash: Option<&str>, symbols: &Symbols) -> std::io::Result<PathBuf> {
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let dir = paths::cache_dir()
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "no cache directory"))?
+        .join("crashes")
+        .join(format!("{}-{}", timestamp, rom_hash.map_or("unknown", |hash| &hash[..8])));
+    std::fs::create_dir_all(&dir)?;
+
+    std::fs::write(dir.join("report.txt"), report(chip8, rom_hash, symbols))?;
+
+    crate::dump::write(&dir.join("state.json"), chip8, rom_hash)?;
+
+    let trace: String = chip8
+        .history
+        .iter()
+        .map(|(pc, opcode)| format!("{:#05X}  {:04X}\n", pc, opcode))
+        .collect();
+    std::fs::write(dir.join("trace.txt"), trace)?;
+
+    crate::image::write_display_png(&dir.join("display.png"), chip8, 8)?;
+    Ok(dir)
+}
@@ -0,0 +1,111 @@
+use chip8::chip8::state::{self, State};
+
+use crate::exit::ExitStatus;
+
+/// Differing pixels listed before the rest are only counted.
+const MAX_PIXELS_LISTED: usize = 32;
+
+/// Differing bytes shown per memory range before it is abbreviated.
+const MAX_BYTES_SHOWN: usize = 16;
+
+fn read_state(path: &str) -> (String, State) {
+    let bytes = std::fs::read(path).unwrap_or_else(|error| {
+        eprintln!("Unable to read {}: {}", path, error);
+        ExitStatus::Error.exit();
+    });
+    state::decode(&bytes).unwrap_or_else(|error| {
+        eprintln!("{}: {}", path, error);
+        ExitStatus::Error.exit();
+    })
+}
+
+fn hex(bytes: &[u8]) -> String {
+    let shown: Vec<String> = bytes.iter().take(MAX_BYTES_SHOWN).map(|byte| format!("{:02X}", byte)).collect();
+    let more = if bytes.len() > MAX_BYTES_SHOWN { " ..." } else { "" };
+    format!("{}{}", shown.join(" "), more)
+}
+
+/// Runs of addresses whose bytes differ, as half-open ranges. Memory present
+/// in only one state counts as differing.
+fn differing_ranges(a: &[u8], b: &[u8]) -> Vec<(usize, usize)> {
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for address in 0..a.len().max(b.len()) {
+        if a.get(address) == b.get(address) {
+            continue;
+        }
+        match ranges.last_mut() {
+            Some((_, end)) if *end == address => *end += 1,
+            _ => ranges.push((address, address + 1)),
+        }
+    }
+    ranges
+}
+
+/// Prints one line per difference between two values, returning whether
+/// there was one.
+fn field<T: PartialEq + std::fmt::Debug>(name: &str, a: &T, b: &T) -> bool {
+    if a != b {
+        println!("{}: {:X?} -> {:X?}", name, a, b);
+    }
+    a != b
+}
+
+/// Compares two save state files and prints where they differ: registers,
+/// memory ranges and display pixels. Exits with status 6 if they differ.
+pub fn run(first: &str, second: &str) {
+    let (hash_a, a) = read_state(first);
+    let (hash_b, b) = read_state(second);
+    let mut differ = false;
+
+    differ |= field("ROM", &hash_a, &hash_b);
+    differ |= field("PC", &a.pc, &b.pc);
+    differ |= field("I", &a.i, &b.i);
+    for (register, (va, vb)) in a.v.iter().zip(&b.v).enumerate() {
+        differ |= field(&format!("V{:X}", register), va, vb);
+    }
+    differ |= field("stack", &a.stack, &b.stack);
+    differ |= field("delay timer", &a.timer_delay, &b.timer_delay);
+    differ |= field("sound timer", &a.timer_sound, &b.timer_sound);
+    differ |= field("RPL flags", &a.rpl, &b.rpl);
+    differ |= field("quirks", &a.quirks, &b.quirks);
+    differ |= field("RNG", &a.rng, &b.rng);
+    differ |= field("MegaChip", &a.megachip, &b.megachip);
+
+    for (start, end) in differing_ranges(&a.memory, &b.memory) {
+        differ = true;
+        let slice = |memory: &[u8]| hex(&memory[start.min(memory.len())..end.min(memory.len())]);
+        println!(
+            "memory {:#05X}-{:#05X}: {} -> {}",
+            start,
+            end - 1,
+            slice(&a.memory),
+            slice(&b.memory)
+        );
+    }
+
+    let (width, height) = (a.display_width, a.display_height);
+    if (width, height) != (b.display_width, b.display_height) {
+        differ = true;
+        println!(
+            "display: {}x{} -> {}x{}",
+            width, height, b.display_width, b.display_height
+        );
+    } else {
+        let pixels: Vec<usize> = (0..a.display.len()).filter(|&index| a.display[index] != b.display[index]).collect();
+        if !pixels.is_empty() {
+            differ = true;
+            println!("display: {} pixels differ", pixels.len());
+            for &index in pixels.iter().take(MAX_PIXELS_LISTED) {
+                println!("  ({}, {}): {} -> {}", index % width, index / width, a.display[index], b.display[index]);
+            }
+            if pixels.len() > MAX_PIXELS_LISTED {
+                println!("  ...");
+            }
+        }
+    }
+
+    if differ {
+        ExitStatus::StatesDiffer.exit();
+    }
+    println!("The states are identical");
+}
@@ -0,0 +1,246 @@
+//! Disassembler producing the Octo syntax `asm` reads, so a disassembled ROM
+//! can be edited and assembled again. Jump, call and `i :=` targets inside
+//! the program get labels, each listing the addresses that refer to it.
+//! MegaChip and unknown opcodes come out as raw bytes. Addresses with a
+//! label in the ROM's symbols are given that label instead.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::chip8::{decode, Instruction};
+use crate::symbols::Symbols;
+
+/// Octo syntax for an instruction, with addresses as numbers.
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Instruction::Sys { nnn } => write!(f, "native {:#05X}", nnn),
+            Instruction::ClearScreen => write!(f, "clear"),
+            Instruction::Return => write!(f, "return"),
+            Instruction::ScrollDown { n } => write!(f, "scroll-down {}", n),
+            Instruction::ScrollRight => write!(f, "scroll-right"),
+            Instruction::ScrollLeft => write!(f, "scroll-left"),
+            Instruction::Exit => write!(f, "exit"),
+            Instruction::LowResolution => write!(f, "lores"),
+            Instruction::HighResolution => write!(f, "hires"),
+            Instruction::ScrollUp { n } => write!(f, "scroll-up {}", n),
+            Instruction::MegaChipOff => write!(f, "megaoff"),
+            Instruction::MegaChipOn => write!(f, "megaon"),
+            Instruction::LoadLongI { high } => write!(f, "ldhi {:#04X}", high),
+            Instruction::LoadPalette { count } => write!(f, "ldpal {}", count),
+            Instruction::SpriteWidth { width } => write!(f, "sprw {}", width),
+            Instruction::SpriteHeight { height } => write!(f, "sprh {}", height),
+            Instruction::Jump { nnn } => write!(f, "jump {:#05X}", nnn),
+            Instruction::Call { nnn } => write!(f, ":call {:#05X}", nnn),
+            // Octo's `if ... then` runs the next instruction when the
+            // condition holds, i.e. skips it when it does not.
+            Instruction::SkipIfEqual { x, kk } => write!(f, "if v{:x} != {:#04X} then", x, kk),
+            Instruction::SkipIfNotEqual { x, kk } => write!(f, "if v{:x} == {:#04X} then", x, kk),
+            Instruction::SkipIfRegistersEqual { x, y } => write!(f, "if v{:x} != v{:x} then", x, y),
+            Instruction::Load { x, kk } => write!(f, "v{:x} := {:#04X}", x, kk),
+            Instruction::Add { x, kk } => write!(f, "v{:x} += {:#04X}", x, kk),
+            Instruction::Move { x, y } => write!(f, "v{:x} := v{:x}", x, y),
+            Instruction::Or { x, y } => write!(f, "v{:x} |= v{:x}", x, y),
+            Instruction::And { x, y } => write!(f, "v{:x} &= v{:x}", x, y),
+            Instruction::Xor { x, y } => write!(f, "v{:x} ^= v{:x}", x, y),
+            Instruction::AddRegisters { x, y } => write!(f, "v{:x} += v{:x}", x, y),
+            Instruction::Subtract { x, y } => write!(f, "v{:x} -= v{:x}", x, y),
+            Instruction::ShiftRight { x, y } => write!(f, "v{:x} >>= v{:x}", x, y),
+            Instruction::SubtractReversed { x, y } => write!(f, "v{:x} =- v{:x}", x, y),
+            Instruction::ShiftLeft { x, y } => write!(f, "v{:x} <<= v{:x}", x, y),
+            Instruction::SkipIfRegistersNotEqual { x, y } => write!(f, "if v{:x} == v{:x} then", x, y),
+            Instruction::LoadI { nnn } => write!(f, "i := {:#05X}", nnn),
+            Instruction::JumpWithOffset { nnn, .. } => write!(f, "jump0 {:#05X}", nnn),
+            Instruction::Random { x, kk } => write!(f, "v{:x} := random {:#04X}", x, kk),
+            Instruction::Draw { x, y, n } => write!(f, "sprite v{:x} v{:x} {}", x, y, n),
+            Instruction::SkipIfKeyDown { x } => write!(f, "if v{:x} -key then", x),
+            Instruction::SkipIfKeyUp { x } => write!(f, "if v{:x} key then", x),
+            Instruction::LoadDelayTimer { x } => write!(f, "v{:x} := delay", x),
+            Instruction::WaitForKey { x } => write!(f, "v{:x} := key", x),
+            Instruction::SetDelayTimer { x } => write!(f, "delay := v{:x}", x),
+            Instruction::SetSoundTimer { x } => write!(f, "buzzer := v{:x}", x),
+            Instruction::AddToI { x } => write!(f, "i += v{:x}", x),
+            Instruction::LoadFont { x } => write!(f, "i := hex v{:x}", x),
+            Instruction::LoadBigFont { x } => write!(f, "i := bighex v{:x}", x),
+            Instruction::StoreBcd { x } => write!(f, "bcd v{:x}", x),
+            Instruction::StoreRegisters { x } => write!(f, "save v{:x}", x),
+            Instruction::LoadRegisters { x } => write!(f, "load v{:x}", x),
+            Instruction::StoreFlags { x } => write!(f, "saveflags v{:x}", x),
+            Instruction::LoadFlags { x } => write!(f, "loadflags v{:x}", x),
+            Instruction::Unknown { opcode } => write!(f, "{:#06X}", opcode),
+        }
+    }
+}
+
+/// Whether `asm` can assemble the instruction's `Display` text back into it.
+fn assembles(instruction: Instruction) -> bool {
+    !matches!(
+        instruction,
+        Instruction::ScrollUp { .. }
+            | Instruction::MegaChipOff
+            | Instruction::MegaChipOn
+            | Instruction::LoadLongI { .. }
+            | Instruction::LoadPalette { .. }
+            | Instruction::SpriteWidth { .. }
+            | Instruction::SpriteHeight { .. }
+            | Instruction::Unknown { .. }
+    )
+}
+
+/// Whether the opcode has bits `decode` ignores set, e.g. `5xy1`, so the
+/// mnemonic would assemble to a different opcode.
+fn loose(opcode: u16) -> bool {
+    matches!(opcode & 0xF000, 0x5000 | 0x9000) && opcode & 0x000F != 0
+}
+
+/// The address an instruction refers to, if it is one a label should mark.
+fn target(instruction: Instruction) -> Option<u16> {
+    match instruction {
+        Instruction::Jump { nnn }
+        | Instruction::Call { nnn }
+        | Instruction::JumpWithOffset { nnn, .. }
+        | Instruction::LoadI { nnn } => Some(nnn),
+        _ => None,
+    }
+}
+
+/// The label a disassembly gives `address` when the symbols have none.
+pub fn label(address: u32) -> String {
+    format!("label_{:03X}", address)
+}
+
+/// The instruction's `Display` text, with its target address replaced by the
+/// label there if the symbols have one.
+pub fn instruction_text(instruction: Instruction, symbols: &Symbols) -> String {
+    let text = instruction.to_string();
+    match target(instruction).and_then(|address| Some((address, symbols.name(address)?))) {
+        Some((address, name)) => text.replace(&format!("{:#05X}", address), name),
+        None => text,
+    }
+}
+
+/// One disassembled instruction, or a lone trailing byte.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Line {
+    pub address: u32,
+    pub bytes: Vec<u8>,
+    /// `None` for a trailing odd byte.
+    pub instruction: Option<Instruction>,
+    /// Addresses of the instructions that jump, call or point I here.
+    pub references: Vec<u32>,
+}
+
+impl Line {
+    /// Whether the line can be written as its mnemonic rather than bytes.
+    fn assembles(&self) -> bool {
+        let opcode = (self.bytes[0] as u16) << 8 | *self.bytes.get(1).unwrap_or(&0) as u16;
+        self.instruction.is_some_and(assembles) && !loose(opcode)
+    }
+
+    /// The line's Octo text, using the labels of targets that have lines of
+    /// their own in `labels`.
+    fn text(&self, labels: &BTreeMap<u32, String>) -> String {
+        let raw = || {
+            self.bytes
+                .iter()
+                .map(|byte| format!("{:#04X}", byte))
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        let Some(instruction) = self.instruction.filter(|_| self.assembles()) else {
+            return raw();
+        };
+        match target(instruction).and_then(|address| Some((address, labels.get(&(address as u32))?))) {
+            Some((address, name)) => instruction.to_string().replace(&format!("{:#05X}", address), name),
+            None => instruction.to_string(),
+        }
+    }
+}
+
+/// Disassembles `rom` as loaded at `origin`, two bytes at a time. The
+/// 4-byte MegaChip `01nn nnnn` is kept together.
+pub fn disassemble(rom: &[u8], origin: u16) -> Vec<Line> {
+    let mut lines = Vec::new();
+    let mut offset = 0;
+    while offset < rom.len() {
+        let address = origin as u32 + offset as u32;
+        if offset + 1 == rom.len() {
+            lines.push(Line {
+                address,
+                bytes: vec![rom[offset]],
+                instruction: None,
+                references: Vec::new(),
+            });
+            break;
+        }
+        let instruction = decode((rom[offset] as u16) << 8 | rom[offset + 1] as u16);
+        let length = match instruction {
+            Instruction::LoadLongI { .. } => 4.min(rom.len() - offset),
+            _ => 2,
+        };
+        lines.push(Line {
+            address,
+            bytes: rom[offset..offset + length].to_vec(),
+            instruction: Some(instruction),
+            references: Vec::new(),
+        });
+        offset += length;
+    }
+
+    let index: BTreeMap<u32, usize> = lines.iter().enumerate().map(|(i, line)| (line.address, i)).collect();
+    let references: Vec<(usize, u32)> = lines
+        .iter()
+        .filter_map(|line| {
+            let target = line.instruction.and_then(target)?;
+            index.get(&(target as u32)).map(|&i| (i, line.address))
+        })
+        .collect();
+    for (i, from) in references {
+        lines[i].references.push(from);
+    }
+    lines
+}
+
+/// Formats disassembled lines as an Octo program: labels on referenced
+/// lines and those the symbols name, with a comment listing where they are
+/// referenced from, and each instruction's address and opcode as a comment.
+pub fn to_octo(lines: &[Line], symbols: &Symbols) -> String {
+    let labels: BTreeMap<u32, String> = lines
+        .iter()
+        .filter_map(|line| {
+            let name = u16::try_from(line.address).ok().and_then(|address| symbols.name(address));
+            match name {
+                Some(name) => Some((line.address, name.to_string())),
+                None if !line.references.is_empty() => Some((line.address, label(line.address))),
+                None => None,
+            }
+        })
+        .collect();
+    let origin = lines.first().map_or(0x200, |line| line.address);
+
+    let mut out = String::new();
+    if origin != 0x200 {
+        // `:org` only pads forward from 0x200, so labels cannot line up.
+        out.push_str(&format!("# Loaded at {:#05X}; asm assembles for 0x200\n", origin));
+    }
+    for line in lines {
+        match (labels.get(&line.address), line.references.is_empty()) {
+            (Some(name), false) => {
+                let from: Vec<String> = line.references.iter().map(|from| format!("{:#05X}", from)).collect();
+                out.push_str(&format!(": {}  # from {}\n", name, from.join(", ")));
+            }
+            (Some(name), true) => out.push_str(&format!(": {}\n", name)),
+            (None, _) => {}
+        }
+        let hex: String = line.bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+        let comment = format!("{:#05X}  {}", line.address, hex);
+        match line.instruction {
+            // Say what raw bytes mean, e.g. for a MegaChip opcode.
+            Some(instruction) if !line.assembles() => {
+                out.push_str(&format!("  {:<28} # {:<16}  {}\n", line.text(&labels), comment, instruction))
+            }
+            _ => out.push_str(&format!("  {:<28} # {}\n", line.text(&labels), comment)),
+        }
+    }
+    out
+}
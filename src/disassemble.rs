@@ -0,0 +1,12 @@
+use chip8::disasm::{disassemble, to_octo};
+
+/// Prints a ROM (`-` for stdin), loaded at `start_address`, as Octo source,
+/// naming the labels in `symbols` or `<rom>.sym`.
+pub fn run(filename: &str, start_address: u16, symbols: Option<&str>) {
+    let rom = crate::rom::read_rom(filename).unwrap_or_else(|error| {
+        eprintln!("Unable to read {}: {}", filename, error);
+        crate::exit::ExitStatus::Error.exit();
+    });
+    let symbols = crate::load_symbols(symbols, filename);
+    print!("{}", to_octo(&disassemble(&rom, start_address), &symbols));
+}
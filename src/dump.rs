@@ -0,0 +1,63 @@
+use std::path::{Path, PathBuf};
+
+use chip8::chip8::Chip8;
+
+use crate::paths;
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// The whole machine as JSON for scripts and bug reports. Memory and the
+/// display are hex strings, the display one byte per pixel, row by row, and
+/// `history` lists the last instructions run as `[address, opcode]`, oldest
+/// first.
+pub fn state_json(chip8: &Chip8, rom_hash: Option<&str>) -> serde_json::Value {
+    serde_json::json!({
+        "fault": chip8.fault.map(|fault| fault.to_string()),
+        "rom_sha1": rom_hash,
+        "pc": chip8.pc,
+        "i": chip8.i,
+        "v": chip8.v,
+        "sp": chip8.stack.len(),
+        "stack": chip8.stack,
+        "history": chip8.history,
+        "delay_timer": chip8.timer_delay,
+        "sound_timer": chip8.timer_sound,
+        "keypad": (0..16).map(|key| chip8.keypad.is_down(key) as u8).collect::<Vec<_>>(),
+        "rpl": chip8.rpl,
+        "quirks": chip8.quirks,
+        "start_address": chip8.start_address,
+        "megachip": chip8.megachip,
+        "display_width": chip8.display_width,
+        "display_height": chip8.display_height,
+        "display": hex(&chip8.display),
+        "memory": hex(&chip8.memory),
+    })
+}
+
+/// Writes the state as pretty-printed JSON to `path`, or to stdout for `-`.
+pub fn write(path: &Path, chip8: &Chip8, rom_hash: Option<&str>) -> std::io::Result<()> {
+    let json = serde_json::to_string_pretty(&state_json(chip8, rom_hash)).map_err(std::io::Error::other)? + "\n";
+    if path == Path::new("-") {
+        print!("{}", json);
+        Ok(())
+    } else {
+        std::fs::write(path, json)
+    }
+}
+
+/// Writes the state to a new file under `<cache>/dumps` and returns its path.
+pub fn write_to_cache(chip8: &Chip8, rom_hash: Option<&str>) -> std::io::Result<PathBuf> {
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let dir = paths::cache_dir()
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "no cache directory"))?
+        .join("dumps");
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join(format!("{}-{}.json", timestamp, rom_hash.map_or("unknown", |hash| &hash[..8])));
+    write(&path, chip8, rom_hash)?;
+    Ok(path)
+}
@@ -0,0 +1,28 @@
+/// Process exit codes, so shell scripts and CI jobs can tell why a run ended.
+/// 2 is left to clap for command line errors.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExitStatus {
+    /// The window was closed, a run limit was reached or the ROM halted.
+    Success = 0,
+    /// The emulator itself failed, e.g. a file could not be read.
+    Error = 1,
+    /// The ROM hit an interpreter fault such as an unknown opcode.
+    Fault = 3,
+    /// `--timeout` expired before the ROM halted.
+    Timeout = 4,
+    /// A snapshot test did not match its golden file.
+    TestFailed = 5,
+    /// `diff-state` found differences between the two states.
+    StatesDiffer = 6,
+    /// `--playback` ended up in a different state than when it was recorded.
+    ReplayDiverged = 7,
+    /// A breakpoint, watchpoint or `--break-on-*` stop was hit in a run that
+    /// ends on its own, which would otherwise hang paused.
+    Stopped = 8,
+}
+
+impl ExitStatus {
+    pub fn exit(self) -> ! {
+        std::process::exit(self as i32)
+    }
+}
@@ -0,0 +1,443 @@
+//! A small expression language over the machine's state, for watch
+//! expressions and conditional breakpoints: `V[3] + V[4]`, `mem[I]`,
+//! `stack[sp - 1]`, `V0 == 5 && DT > 0`.
+//!
+//! Values are signed 64-bit integers. Numbers are decimal, or hex with a
+//! `0x` prefix. `V0`-`VF` and `V[n]` are the registers, `I`, `PC`, `SP`,
+//! `DT` and `ST` the others, `mem[a]` the byte at `a` and `stack[n]` the
+//! `n`th return address, `stack[0]` being the oldest. Names are not case
+//! sensitive, and any other name is a label from the ROM's symbols. The
+//! operators are C's, with their precedence: `* / %`, `+ -`, `<< >>`,
+//! `< <= > >=`, `== !=`, `&`, `^`, `|`, `&&`, `||`, and the unary `-`, `!`
+//! and `~`. Comparisons and logic give 1 for true and 0 for false.
+
+use std::fmt;
+
+use crate::chip8::Chip8;
+use crate::symbols::Symbols;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UnaryOp {
+    Negate,
+    Not,
+    Complement,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BinaryOp {
+    Multiply,
+    Divide,
+    Remainder,
+    Add,
+    Subtract,
+    ShiftLeft,
+    ShiftRight,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Equal,
+    NotEqual,
+    And,
+    Xor,
+    Or,
+    LogicalAnd,
+    LogicalOr,
+}
+
+/// Binary operators from the loosest binding to the tightest.
+const PRECEDENCE: [&[(&str, BinaryOp)]; 10] = [
+    &[("||", BinaryOp::LogicalOr)],
+    &[("&&", BinaryOp::LogicalAnd)],
+    &[("|", BinaryOp::Or)],
+    &[("^", BinaryOp::Xor)],
+    &[("&", BinaryOp::And)],
+    &[("==", BinaryOp::Equal), ("!=", BinaryOp::NotEqual)],
+    &[
+        ("<=", BinaryOp::LessEqual),
+        (">=", BinaryOp::GreaterEqual),
+        ("<", BinaryOp::Less),
+        (">", BinaryOp::Greater),
+    ],
+    &[("<<", BinaryOp::ShiftLeft), (">>", BinaryOp::ShiftRight)],
+    &[("+", BinaryOp::Add), ("-", BinaryOp::Subtract)],
+    &[("*", BinaryOp::Multiply), ("/", BinaryOp::Divide), ("%", BinaryOp::Remainder)],
+];
+
+/// A parsed expression, with labels already replaced by their addresses.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Expr {
+    Number(i64),
+    V(Box<Expr>),
+    I,
+    Pc,
+    Sp,
+    Dt,
+    St,
+    Memory(Box<Expr>),
+    Stack(Box<Expr>),
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Token {
+    Number(i64),
+    Name(String),
+    Symbol(&'static str),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Number(value) => write!(f, "{}", value),
+            Token::Name(name) => write!(f, "{}", name),
+            Token::Symbol(symbol) => write!(f, "{}", symbol),
+        }
+    }
+}
+
+/// Operators and brackets, two-character ones first so `<=` is not read as
+/// `<` and `=`.
+const SYMBOLS: [&str; 24] = [
+    "<<", ">>", "<=", ">=", "==", "!=", "&&", "||", "(", ")", "[", "]", "+", "-", "*", "/", "%", "<", ">", "&", "^", "|",
+    "!", "~",
+];
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = text.trim_start();
+    while !rest.is_empty() {
+        let first = rest.chars().next().unwrap_or_default();
+        let length = if first.is_ascii_alphanumeric() || first == '_' {
+            let length = rest.find(|c: char| !c.is_ascii_alphanumeric() && c != '_').unwrap_or(rest.len());
+            let word = &rest[..length];
+            if first.is_ascii_digit() {
+                let value = match word.strip_prefix("0x").or_else(|| word.strip_prefix("0X")) {
+                    Some(digits) => i64::from_str_radix(digits, 16),
+                    None => word.parse(),
+                };
+                tokens.push(Token::Number(value.map_err(|_| format!("{} is not a number", word))?));
+            } else {
+                tokens.push(Token::Name(word.to_string()));
+            }
+            length
+        } else {
+            let symbol = SYMBOLS
+                .iter()
+                .find(|symbol| rest.starts_with(**symbol))
+                .ok_or_else(|| format!("unexpected {}", first))?;
+            tokens.push(Token::Symbol(symbol));
+            symbol.len()
+        };
+        rest = rest[length..].trim_start();
+    }
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    position: usize,
+    symbols: &'a Symbols,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Result<Token, String> {
+        let token = self.peek().cloned().ok_or("unexpected end of expression")?;
+        self.position += 1;
+        Ok(token)
+    }
+
+    /// Takes `symbol` if it comes next.
+    fn accept(&mut self, symbol: &str) -> bool {
+        let found = matches!(self.peek(), Some(Token::Symbol(next)) if *next == symbol);
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, symbol: &str) -> Result<(), String> {
+        match self.accept(symbol) {
+            true => Ok(()),
+            false => match self.peek() {
+                Some(token) => Err(format!("expected {}, found {}", symbol, token)),
+                None => Err(format!("expected {} at the end", symbol)),
+            },
+        }
+    }
+
+    /// A binary expression of the operators at `level` of `PRECEDENCE` and
+    /// tighter.
+    fn binary(&mut self, level: usize) -> Result<Expr, String> {
+        let Some(operators) = PRECEDENCE.get(level) else {
+            return self.unary();
+        };
+        let mut left = self.binary(level + 1)?;
+        'operators: loop {
+            for &(symbol, op) in *operators {
+                if self.accept(symbol) {
+                    let right = self.binary(level + 1)?;
+                    left = Expr::Binary(op, Box::new(left), Box::new(right));
+                    continue 'operators;
+                }
+            }
+            return Ok(left);
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        let op = if self.accept("-") {
+            UnaryOp::Negate
+        } else if self.accept("!") {
+            UnaryOp::Not
+        } else if self.accept("~") {
+            UnaryOp::Complement
+        } else {
+            return self.primary();
+        };
+        Ok(Expr::Unary(op, Box::new(self.unary()?)))
+    }
+
+    /// The expression in brackets after `mem`, `stack` or `V`.
+    fn index(&mut self) -> Result<Box<Expr>, String> {
+        self.expect("[")?;
+        let index = self.binary(0)?;
+        self.expect("]")?;
+        Ok(Box::new(index))
+    }
+
+    fn primary(&mut self) -> Result<Expr, String> {
+        match self.next()? {
+            Token::Number(value) => Ok(Expr::Number(value)),
+            Token::Symbol("(") => {
+                let expr = self.binary(0)?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+            Token::Name(name) => match name.to_ascii_lowercase().as_str() {
+                "v" => Ok(Expr::V(self.index()?)),
+                "mem" => Ok(Expr::Memory(self.index()?)),
+                "stack" => Ok(Expr::Stack(self.index()?)),
+                "i" => Ok(Expr::I),
+                "pc" => Ok(Expr::Pc),
+                "sp" => Ok(Expr::Sp),
+                "dt" => Ok(Expr::Dt),
+                "st" => Ok(Expr::St),
+                register if register.len() == 2 && register.starts_with('v') => {
+                    match u8::from_str_radix(&register[1..], 16) {
+                        Ok(x) => Ok(Expr::V(Box::new(Expr::Number(x as i64)))),
+                        Err(_) => self.label(&name),
+                    }
+                }
+                _ => self.label(&name),
+            },
+            token => Err(format!("unexpected {}", token)),
+        }
+    }
+
+    fn label(&self, name: &str) -> Result<Expr, String> {
+        match self.symbols.address(name) {
+            Some(address) => Ok(Expr::Number(address as i64)),
+            None => Err(format!("{} is not a register or label", name)),
+        }
+    }
+}
+
+impl Expr {
+    /// Parses `text`, looking names that are not registers up in `symbols`.
+    pub fn parse(text: &str, symbols: &Symbols) -> Result<Expr, String> {
+        let mut parser = Parser {
+            tokens: tokenize(text)?,
+            position: 0,
+            symbols,
+        };
+        if parser.tokens.is_empty() {
+            return Err("empty expression".to_string());
+        }
+        let expr = parser.binary(0)?;
+        match parser.peek() {
+            Some(token) => Err(format!("unexpected {}", token)),
+            None => Ok(expr),
+        }
+    }
+
+    /// The expression's value for the machine as it is now. Registers and
+    /// stack entries that do not exist and division by zero are errors;
+    /// memory addresses wrap around like the machine's do.
+    pub fn eval(&self, chip8: &Chip8) -> Result<i64, String> {
+        Ok(match self {
+            Expr::Number(value) => *value,
+            Expr::V(index) => {
+                let x = index.eval(chip8)?;
+                match usize::try_from(x).ok().and_then(|x| chip8.v.get(x)) {
+                    Some(&value) => value as i64,
+                    None => return Err(format!("V[{}] does not exist", x)),
+                }
+            }
+            Expr::I => chip8.i as i64,
+            Expr::Pc => chip8.pc as i64,
+            Expr::Sp => chip8.stack.len() as i64,
+            Expr::Dt => chip8.timer_delay as i64,
+            Expr::St => chip8.timer_sound as i64,
+            Expr::Memory(address) => {
+                let address = address.eval(chip8)?.rem_euclid(chip8.memory.len() as i64);
+                chip8.memory[address as usize] as i64
+            }
+            Expr::Stack(index) => {
+                let n = index.eval(chip8)?;
+                match usize::try_from(n).ok().and_then(|n| chip8.stack.get(n)) {
+                    Some(&address) => address as i64,
+                    None => return Err(format!("stack[{}] is empty", n)),
+                }
+            }
+            Expr::Unary(op, operand) => {
+                let value = operand.eval(chip8)?;
+                match op {
+                    UnaryOp::Negate => value.wrapping_neg(),
+                    UnaryOp::Not => (value == 0) as i64,
+                    UnaryOp::Complement => !value,
+                }
+            }
+            Expr::Binary(BinaryOp::LogicalAnd, left, right) => {
+                (left.eval(chip8)? != 0 && right.eval(chip8)? != 0) as i64
+            }
+            Expr::Binary(BinaryOp::LogicalOr, left, right) => {
+                (left.eval(chip8)? != 0 || right.eval(chip8)? != 0) as i64
+            }
+            Expr::Binary(op, left, right) => {
+                let (left, right) = (left.eval(chip8)?, right.eval(chip8)?);
+                match op {
+                    BinaryOp::Multiply => left.wrapping_mul(right),
+                    BinaryOp::Divide | BinaryOp::Remainder if right == 0 => {
+                        return Err("division by zero".to_string())
+                    }
+                    BinaryOp::Divide => left.wrapping_div(right),
+                    BinaryOp::Remainder => left.wrapping_rem(right),
+                    BinaryOp::Add => left.wrapping_add(right),
+                    BinaryOp::Subtract => left.wrapping_sub(right),
+                    BinaryOp::ShiftLeft => left.wrapping_shl(right as u32),
+                    BinaryOp::ShiftRight => left.wrapping_shr(right as u32),
+                    BinaryOp::Less => (left < right) as i64,
+                    BinaryOp::LessEqual => (left <= right) as i64,
+                    BinaryOp::Greater => (left > right) as i64,
+                    BinaryOp::GreaterEqual => (left >= right) as i64,
+                    BinaryOp::Equal => (left == right) as i64,
+                    BinaryOp::NotEqual => (left != right) as i64,
+                    BinaryOp::And => left & right,
+                    BinaryOp::Xor => left ^ right,
+                    BinaryOp::Or => left | right,
+                    BinaryOp::LogicalAnd | BinaryOp::LogicalOr => unreachable!("evaluated above"),
+                }
+            }
+        })
+    }
+}
+
+/// A watch expression as typed, shown with its current value.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Watch {
+    pub text: String,
+    /// The parsed expression, or why it did not parse.
+    pub expr: Result<Expr, String>,
+}
+
+impl Watch {
+    pub fn new(text: &str, symbols: &Symbols) -> Watch {
+        Watch {
+            text: text.to_string(),
+            expr: Expr::parse(text, symbols),
+        }
+    }
+
+    /// The watch as the debuggers show it, e.g. `V[3] + V[4] = 12 (0xC)`.
+    pub fn show(&self, chip8: &Chip8) -> String {
+        let value = match &self.expr {
+            Ok(expr) => expr.eval(chip8),
+            Err(error) => Err(error.clone()),
+        };
+        match value {
+            Ok(value) if value < 0 => format!("{} = {}", self.text, value),
+            Ok(value) => format!("{} = {} ({:#X})", self.text, value, value),
+            Err(error) => format!("{}: {}", self.text, error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chip8::initialize;
+
+    fn eval(text: &str, symbols: &Symbols, chip8: &Chip8) -> Result<i64, String> {
+        Expr::parse(text, symbols)?.eval(chip8)
+    }
+
+    #[test]
+    fn multiplication_binds_tighter_than_addition() {
+        let chip8 = initialize();
+        assert_eq!(eval("1+2*3", &Symbols::default(), &chip8), Ok(7));
+        assert_eq!(eval("(1+2)*3", &Symbols::default(), &chip8), Ok(9));
+    }
+
+    #[test]
+    fn logical_and_binds_tighter_than_or() {
+        let mut symbols = Symbols::default();
+        symbols.insert("a", 1);
+        symbols.insert("b", 1);
+        symbols.insert("c", 0);
+        let number = |value| Box::new(Expr::Number(value));
+        assert_eq!(
+            Expr::parse("a||b&&c", &symbols),
+            Ok(Expr::Binary(
+                BinaryOp::LogicalOr,
+                number(1),
+                Box::new(Expr::Binary(BinaryOp::LogicalAnd, number(1), number(0))),
+            ))
+        );
+        assert_eq!(eval("a||b&&c", &symbols, &initialize()), Ok(1));
+    }
+
+    #[test]
+    fn registers_memory_and_stack() {
+        let mut chip8 = initialize();
+        chip8.v[3] = 0x12;
+        chip8.v[0xA] = 3;
+        chip8.i = 0x300;
+        chip8.memory[0x300] = 0xAB;
+        chip8.stack.push(0x202);
+        chip8.stack.push(0x2F4);
+        let symbols = Symbols::default();
+        assert_eq!(eval("V[3]", &symbols, &chip8), Ok(0x12));
+        assert_eq!(eval("V[VA]", &symbols, &chip8), Ok(0x12));
+        assert_eq!(eval("v3 == 0x12", &symbols, &chip8), Ok(1));
+        assert_eq!(eval("mem[I]", &symbols, &chip8), Ok(0xAB));
+        assert_eq!(eval("stack[sp-1]", &symbols, &chip8), Ok(0x2F4));
+        assert_eq!(eval("stack[0]", &symbols, &chip8), Ok(0x202));
+        assert!(eval("V[16]", &symbols, &chip8).is_err());
+        assert!(eval("stack[sp]", &symbols, &chip8).is_err());
+    }
+
+    #[test]
+    fn division_by_zero_is_an_error() {
+        let chip8 = initialize();
+        assert_eq!(eval("1/0", &Symbols::default(), &chip8), Err("division by zero".to_string()));
+        assert_eq!(eval("1%V0", &Symbols::default(), &chip8), Err("division by zero".to_string()));
+    }
+
+    #[test]
+    fn unknown_names_are_errors() {
+        assert_eq!(
+            Expr::parse("player_x + 1", &Symbols::default()),
+            Err("player_x is not a register or label".to_string())
+        );
+        assert_eq!(
+            Expr::parse("VG", &Symbols::default()),
+            Err("VG is not a register or label".to_string())
+        );
+    }
+}
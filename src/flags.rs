@@ -0,0 +1,29 @@
+use std::path::PathBuf;
+
+use chip8::chip8::Chip8;
+
+use crate::paths;
+
+fn flags_file(rom_hash: &str) -> Option<PathBuf> {
+    paths::data_dir().map(|dir| dir.join("flags").join(format!("{}.bin", rom_hash)))
+}
+
+/// Restores the SCHIP RPL user flags saved for the ROM, if any.
+pub fn load(chip8: &mut Chip8, rom_hash: &str) {
+    let Some(bytes) = flags_file(rom_hash).and_then(|path| std::fs::read(path).ok()) else {
+        return;
+    };
+    let count = bytes.len().min(chip8.rpl.len());
+    chip8.rpl[..count].copy_from_slice(&bytes[..count]);
+}
+
+/// Saves the SCHIP RPL user flags so the ROM finds them next time.
+pub fn save(chip8: &Chip8, rom_hash: &str) {
+    let Some(path) = flags_file(rom_hash) else {
+        return;
+    };
+    let result = std::fs::create_dir_all(path.parent().unwrap()).and_then(|_| std::fs::write(&path, chip8.rpl));
+    if let Err(error) = result {
+        tracing::warn!(target: "frontend", "Unable to save flags to {}: {}", path.display(), error);
+    }
+}
@@ -0,0 +1,367 @@
+//! A minimal GDB remote serial protocol server, so gdb or an IDE can attach
+//! to the running machine over TCP: registers, memory, breakpoints, single
+//! steps and continuing.
+//!
+//! The window's run loop polls the server once a frame. gdb expects the
+//! machine halted while it is attached and not running, so the server asks
+//! the frontend to pause on connecting and to resume on `c`, and is told
+//! about each stop so it can answer the `c`.
+
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+use chip8::chip8::*;
+
+/// gdb's signal numbers for stop replies.
+pub const SIGINT: u8 = 2;
+pub const SIGILL: u8 = 4;
+pub const SIGTRAP: u8 = 5;
+
+/// Registers in `g` packet order: V0-VF, then I, PC, SP, DT and ST, multi-byte
+/// ones little-endian.
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.chip8.core">
+    <reg name="v0" bitsize="8" type="uint8" regnum="0"/>
+    <reg name="v1" bitsize="8" type="uint8"/>
+    <reg name="v2" bitsize="8" type="uint8"/>
+    <reg name="v3" bitsize="8" type="uint8"/>
+    <reg name="v4" bitsize="8" type="uint8"/>
+    <reg name="v5" bitsize="8" type="uint8"/>
+    <reg name="v6" bitsize="8" type="uint8"/>
+    <reg name="v7" bitsize="8" type="uint8"/>
+    <reg name="v8" bitsize="8" type="uint8"/>
+    <reg name="v9" bitsize="8" type="uint8"/>
+    <reg name="va" bitsize="8" type="uint8"/>
+    <reg name="vb" bitsize="8" type="uint8"/>
+    <reg name="vc" bitsize="8" type="uint8"/>
+    <reg name="vd" bitsize="8" type="uint8"/>
+    <reg name="ve" bitsize="8" type="uint8"/>
+    <reg name="vf" bitsize="8" type="uint8"/>
+    <reg name="i" bitsize="32" type="uint32"/>
+    <reg name="pc" bitsize="16" type="uint16"/>
+    <reg name="sp" bitsize="8" type="uint8"/>
+    <reg name="dt" bitsize="8" type="uint8"/>
+    <reg name="st" bitsize="8" type="uint8"/>
+  </feature>
+</target>
+"#;
+
+/// Register sizes in bytes, by register number.
+const REGISTER_SIZES: [usize; 21] = [1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 4, 2, 1, 1, 1];
+
+/// What the frontend should do after a poll.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Control {
+    Pause,
+    Resume,
+}
+
+struct Client {
+    stream: TcpStream,
+    /// Bytes received but not yet parsed into packets.
+    received: Vec<u8>,
+    /// gdb asked for no `+` acknowledgements with QStartNoAckMode.
+    no_ack: bool,
+    /// gdb sent `c` and is waiting for a stop reply.
+    running: bool,
+}
+
+pub struct GdbServer {
+    listener: TcpListener,
+    client: Option<Client>,
+}
+
+impl GdbServer {
+    /// Listens for gdb on `port`, on the loopback interface only since the
+    /// protocol has no authentication.
+    pub fn bind(port: u16) -> std::io::Result<GdbServer> {
+        let listener = TcpListener::bind(("127.0.0.1", port))?;
+        listener.set_nonblocking(true)?;
+        tracing::info!(target: "frontend", "Waiting for gdb on 127.0.0.1:{}", port);
+        Ok(GdbServer { listener, client: None })
+    }
+
+    /// Accepts a connection and answers the packets that have arrived,
+    /// without blocking.
+    pub fn poll(&mut self, chip8: &mut Chip8) -> Option<Control> {
+        let mut control = None;
+        if self.client.is_none() {
+            if let Ok((stream, address)) = self.listener.accept() {
+                tracing::info!(target: "frontend", "gdb connected from {}", address);
+                if stream.set_nonblocking(true).is_ok() {
+                    self.client = Some(Client {
+                        stream,
+                        received: Vec::new(),
+                        no_ack: false,
+                        running: false,
+                    });
+                    control = Some(Control::Pause);
+                }
+            }
+        }
+        let Some(client) = self.client.as_mut() else {
+            return control;
+        };
+        let mut buffer = [0; 4096];
+        loop {
+            match client.stream.read(&mut buffer) {
+                Ok(0) => {
+                    tracing::info!(target: "frontend", "gdb disconnected");
+                    self.client = None;
+                    return Some(Control::Resume);
+                }
+                Ok(read) => client.received.extend_from_slice(&buffer[..read]),
+                Err(error) if error.kind() == ErrorKind::WouldBlock => break,
+                Err(error) => {
+                    tracing::warn!(target: "frontend", "gdb connection failed: {}", error);
+                    self.client = None;
+                    return Some(Control::Resume);
+                }
+            }
+        }
+        while let Some(packet) = client.next_packet() {
+            let result = match packet {
+                Packet::Interrupt => {
+                    client.running = false;
+                    control = Some(Control::Pause);
+                    client.send(&format!("S{:02x}", SIGINT))
+                }
+                Packet::Command(command) => {
+                    let (reply, next) = client.handle(&command, chip8);
+                    control = next.or(control);
+                    match reply {
+                        Some(reply) => client.send(&reply),
+                        None => Ok(()),
+                    }
+                }
+            };
+            if let Err(error) = result {
+                tracing::warn!(target: "frontend", "gdb connection failed: {}", error);
+                self.client = None;
+                return Some(Control::Resume);
+            }
+        }
+        control
+    }
+
+    /// Tells gdb the machine stopped with `signal`, if it is waiting for that
+    /// after a `c`.
+    pub fn stopped(&mut self, signal: u8) {
+        if let Some(client) = self.client.as_mut().filter(|client| client.running) {
+            client.running = false;
+            if let Err(error) = client.send(&format!("S{:02x}", signal)) {
+                tracing::warn!(target: "frontend", "gdb connection failed: {}", error);
+                self.client = None;
+            }
+        }
+    }
+}
+
+enum Packet {
+    Interrupt,
+    Command(String),
+}
+
+impl Client {
+    /// Takes the next complete packet off the received bytes, acknowledging
+    /// it. Acknowledgements from gdb and damaged packets are dropped.
+    fn next_packet(&mut self) -> Option<Packet> {
+        loop {
+            let start = self.received.iter().position(|&byte| byte == b'$' || byte == 0x03)?;
+            if self.received[start] == 0x03 {
+                self.received.drain(..=start);
+                return Some(Packet::Interrupt);
+            }
+            let end = self.received[start..].iter().position(|&byte| byte == b'#')? + start;
+            if self.received.len() < end + 3 {
+                return None;
+            }
+            let data = self.received[start + 1..end].to_vec();
+            let checksum = std::str::from_utf8(&self.received[end + 1..end + 3])
+                .ok()
+                .and_then(|digits| u8::from_str_radix(digits, 16).ok());
+            self.received.drain(..end + 3);
+            let valid = checksum == Some(data.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte)));
+            if !self.no_ack {
+                let _ = self.stream.write_all(if valid { b"+" } else { b"-" });
+            }
+            if valid {
+                return Some(Packet::Command(String::from_utf8_lossy(&data).into_owned()));
+            }
+        }
+    }
+
+    fn send(&mut self, data: &str) -> std::io::Result<()> {
+        let checksum = data.bytes().fold(0u8, |sum, byte| sum.wrapping_add(byte));
+        // Replies are small, so block until they are out rather than queue them.
+        self.stream.set_nonblocking(false)?;
+        let result = write!(self.stream, "${}#{:02x}", data, checksum);
+        self.stream.set_nonblocking(true)?;
+        result
+    }
+
+    /// Answers a packet, returning the reply (`None` for none at all) and
+    /// what the frontend should do.
+    fn handle(&mut self, command: &str, chip8: &mut Chip8) -> (Option<String>, Option<Control>) {
+        let reply = |text: &str| (Some(text.to_string()), None);
+        let first = command.chars().next().unwrap_or(' ');
+        let arguments = command.get(1..).unwrap_or("");
+        match first {
+            '?' => reply(&format!("S{:02x}", SIGTRAP)),
+            'g' => reply(&(0..REGISTER_SIZES.len()).map(|n| read_register(chip8, n)).collect::<String>()),
+            'G' => {
+                let mut offset = 0;
+                for (n, size) in REGISTER_SIZES.iter().enumerate() {
+                    let Some(hex) = arguments.get(offset..offset + size * 2) else {
+                        return reply("E01");
+                    };
+                    if write_register(chip8, n, hex).is_none() {
+                        return reply("E01");
+                    }
+                    offset += size * 2;
+                }
+                reply("OK")
+            }
+            'p' => match usize::from_str_radix(arguments, 16) {
+                Ok(n) if n < REGISTER_SIZES.len() => reply(&read_register(chip8, n)),
+                _ => reply("E01"),
+            },
+            'P' => {
+                let written = arguments.split_once('=').and_then(|(n, hex)| {
+                    let n = usize::from_str_radix(n, 16).ok().filter(|&n| n < REGISTER_SIZES.len())?;
+                    write_register(chip8, n, hex)
+                });
+                reply(if written.is_some() { "OK" } else { "E01" })
+            }
+            'm' => match parse_range(arguments).and_then(|(address, length)| {
+                chip8.memory.get(address..address.checked_add(length)?)
+            }) {
+                Some(bytes) => reply(&to_hex(bytes)),
+                None => reply("E01"),
+            },
+            'M' => {
+                let written = arguments.split_once(':').and_then(|(range, hex)| {
+                    let (address, length) = parse_range(range)?;
+                    let bytes = from_hex(hex).filter(|bytes| bytes.len() == length)?;
+                    let memory = chip8.memory.get_mut(address..address.checked_add(length)?)?;
+                    memory.copy_from_slice(&bytes);
+                    Some(())
+                });
+                reply(if written.is_some() { "OK" } else { "E01" })
+            }
+            'Z' | 'z' => {
+                // Software (0) and hardware (1) breakpoints are the same here.
+                let mut parts = arguments.split(',');
+                let (Some("0" | "1"), Some(address)) = (parts.next(), parts.next()) else {
+                    return reply("");
+                };
+                let Ok(address) = u16::from_str_radix(address, 16) else {
+                    return reply("E01");
+                };
+                if first == 'Z' {
+                    add_breakpoint(chip8, address);
+                } else {
+                    remove_breakpoint(chip8, address);
+                }
+                reply("OK")
+            }
+            's' => {
+                let faulted = chip8.fault.is_some();
+                step_into(chip8);
+                take_stop(chip8);
+                let signal = if !faulted && chip8.fault.is_some() { SIGILL } else { SIGTRAP };
+                reply(&format!("S{:02x}", signal))
+            }
+            'c' => {
+                self.running = true;
+                // Continuing from a breakpoint runs the instruction there.
+                chip8.breakpoint_hit = Some(chip8.pc);
+                (None, Some(Control::Resume))
+            }
+            'D' => (Some("OK".to_string()), Some(Control::Resume)),
+            'k' => (None, Some(Control::Resume)),
+            'H' => reply("OK"),
+            'q' if command.starts_with("qSupported") => {
+                reply("PacketSize=4000;qXfer:features:read+;QStartNoAckMode+")
+            }
+            'q' if command == "qAttached" => reply("1"),
+            'q' if command == "qC" => reply("QC1"),
+            'q' if command == "qfThreadInfo" => reply("m1"),
+            'q' if command == "qsThreadInfo" => reply("l"),
+            'q' if command.starts_with("qXfer:features:read:target.xml:") => {
+                let range = &command["qXfer:features:read:target.xml:".len()..];
+                match parse_range(range) {
+                    Some((offset, length)) => {
+                        let rest = TARGET_XML.get(offset.min(TARGET_XML.len())..).unwrap_or("");
+                        let chunk = &rest[..length.min(rest.len())];
+                        reply(&format!("{}{}", if chunk.len() < rest.len() { 'm' } else { 'l' }, chunk))
+                    }
+                    None => reply("E01"),
+                }
+            }
+            'Q' if command == "QStartNoAckMode" => {
+                self.no_ack = true;
+                reply("OK")
+            }
+            'T' => reply("OK"),
+            _ => reply(""),
+        }
+    }
+}
+
+/// A register as little-endian hex.
+fn read_register(chip8: &Chip8, n: usize) -> String {
+    let value = match n {
+        0..=15 => chip8.v[n] as u32,
+        16 => chip8.i,
+        17 => chip8.pc as u32,
+        18 => chip8.stack.len() as u32,
+        19 => chip8.timer_delay as u32,
+        _ => chip8.timer_sound as u32,
+    };
+    to_hex(&value.to_le_bytes()[..REGISTER_SIZES[n]])
+}
+
+/// Sets a register from little-endian hex. Setting SP drops return addresses
+/// or pushes zeros to get the stack to that depth. I past 24 bits, or SP past
+/// the stack's depth, is refused.
+fn write_register(chip8: &mut Chip8, n: usize, hex: &str) -> Option<()> {
+    let bytes = from_hex(hex).filter(|bytes| bytes.len() == REGISTER_SIZES[n])?;
+    let value = bytes.iter().rev().fold(0u32, |value, &byte| value << 8 | byte as u32);
+    match n {
+        0..=15 => chip8.v[n] = value as u8,
+        16 if value > Register::I.max() => return None,
+        16 => Register::I.set(chip8, value),
+        18 if value as usize > chip8.stack_depth.unwrap_or(u16::MAX as usize) => return None,
+        17 => chip8.pc = value as u16,
+        18 => chip8.stack.resize(value as usize, 0),
+        19 => chip8.timer_delay = value as u8,
+        _ => chip8.timer_sound = value as u8,
+    }
+    Some(())
+}
+
+/// Parses `address,length` in hex.
+fn parse_range(text: &str) -> Option<(usize, usize)> {
+    let (address, length) = text.split_once(',')?;
+    Some((
+        usize::from_str_radix(address, 16).ok()?,
+        usize::from_str_radix(length, 16).ok()?,
+    ))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|at| u8::from_str_radix(hex.get(at..at + 2)?, 16).ok())
+        .collect()
+}
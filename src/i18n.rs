@@ -0,0 +1,44 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::sync::OnceLock;
+
+const ENGLISH: &str = include_str!("../assets/i18n/en.toml");
+const LANGUAGES: &[(&str, &str)] = &[
+    ("en", ENGLISH),
+    ("pl", include_str!("../assets/i18n/pl.toml")),
+];
+
+static STRINGS: OnceLock<HashMap<String, String>> = OnceLock::new();
+
+fn parse(text: &str) -> HashMap<String, String> {
+    toml::from_str(text).expect("built-in language file is valid")
+}
+
+/// Selects the UI language; strings missing from a translation fall back to
+/// English. Only the first call has an effect.
+pub fn init(language: &str) {
+    let mut strings = parse(ENGLISH);
+    match LANGUAGES.iter().find(|(code, _)| *code == language) {
+        Some((_, text)) => strings.extend(parse(text)),
+        None => tracing::warn!(target: "frontend", "Unknown language {:?}, using English", language),
+    }
+    let _ = STRINGS.set(strings);
+}
+
+/// Looks up a UI string, returning the key itself if it is unknown.
+pub fn tr(key: &str) -> &str {
+    STRINGS
+        .get_or_init(|| parse(ENGLISH))
+        .get(key)
+        .map(String::as_str)
+        .unwrap_or(key)
+}
+
+/// Looks up a UI string and replaces its `{0}`, `{1}`, ... placeholders.
+pub fn trf(key: &str, args: &[&dyn Display]) -> String {
+    let mut text = tr(key).to_owned();
+    for (index, arg) in args.iter().enumerate() {
+        text = text.replace(&format!("{{{}}}", index), &arg.to_string());
+    }
+    text
+}
@@ -0,0 +1,191 @@
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+use chip8::chip8::heatmap::Heatmap;
+use chip8::chip8::Chip8;
+
+/// The display resampled to `width` by `height`, one palette index per
+/// pixel: 0 or 1, or the MegaChip palette entry.
+pub fn scaled_pixels(chip8: &Chip8, width: usize, height: usize) -> Vec<u8> {
+    let mut pixels = Vec::with_capacity(width * height);
+    for y in 0..height {
+        let row = y * chip8.display_height / height * chip8.display_width;
+        for x in 0..width {
+            let pixel = chip8.display[row + x * chip8.display_width / width];
+            pixels.push(if chip8.megachip { pixel } else { pixel.min(1) });
+        }
+    }
+    pixels
+}
+
+/// The RGB colors of the indexes in `scaled_pixels`: black and `color`, or
+/// the MegaChip palette with entry 0 black.
+pub fn palette_rgb(chip8: &Chip8, color: [u8; 3]) -> Vec<u8> {
+    if !chip8.megachip {
+        return [[0, 0, 0], color].concat();
+    }
+    let mut palette = Vec::with_capacity(256 * 3);
+    palette.extend([0, 0, 0]);
+    for argb in &chip8.palette[1..] {
+        let [_, r, g, b] = argb.to_be_bytes();
+        palette.extend([r, g, b]);
+    }
+    palette
+}
+
+/// Writes the display as a PNG in the colors of `palette_rgb`, `scale`
+/// pixels per 64x32 pixel like the window.
+pub fn write_screenshot(path: &Path, chip8: &Chip8, scale: usize, color: [u8; 3]) -> std::io::Result<()> {
+    let scale = (scale * 64 / chip8.display_width).max(1);
+    let (width, height) = (chip8.display_width * scale, chip8.display_height * scale);
+    let pixels = scaled_pixels(chip8, width, height);
+
+    let file = std::fs::File::create(path)?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width as u32, height as u32);
+    encoder.set_color(png::ColorType::Indexed);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_palette(palette_rgb(chip8, color));
+    let mut writer = encoder.write_header().map_err(std::io::Error::other)?;
+    writer.write_image_data(&pixels).map_err(std::io::Error::other)?;
+    Ok(())
+}
+
+/// A new file for a screenshot of `rom`, next to it and named after it and
+/// the time, e.g. `pong-20240501-181502.png`.
+pub fn screenshot_path(rom: &str) -> PathBuf {
+    let rom = Path::new(rom);
+    let dir = match rom.parent() {
+        Some(dir) if rom != Path::new("-") => dir,
+        _ => Path::new(""),
+    };
+    let stem = rom
+        .file_stem()
+        .filter(|_| rom != Path::new("-"))
+        .map_or("chip8".into(), |stem| stem.to_string_lossy());
+    let seconds = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let name = format!("{}-{}", stem, utc_timestamp(seconds));
+    let mut path = dir.join(format!("{}.png", name));
+    let mut counter = 2;
+    while path.exists() {
+        path = dir.join(format!("{}-{}.png", name, counter));
+        counter += 1;
+    }
+    path
+}
+
+/// `seconds` since the Unix epoch as `YYYYMMDD-HHMMSS` in UTC.
+fn utc_timestamp(seconds: u64) -> String {
+    let (days, time) = (seconds / 86400, seconds % 86400);
+    // Days to a civil date, from Howard Hinnant's `civil_from_days`.
+    let z = days + 719468;
+    let era = z / 146097;
+    let day_of_era = z % 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    format!(
+        "{:04}{:02}{:02}-{:02}{:02}{:02}",
+        year,
+        month,
+        day,
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    )
+}
+
+/// Writes the display as a black and white PNG, each pixel `scale` pixels
+/// wide.
+pub fn write_display_png(path: &Path, chip8: &Chip8, scale: usize) -> std::io::Result<()> {
+    let (width, height) = (chip8.display_width * scale, chip8.display_height * scale);
+    let pixels: Vec<u8> = scaled_pixels(chip8, width, height)
+        .into_iter()
+        .map(|pixel| if pixel != 0 { 0xFF } else { 0x00 })
+        .collect();
+
+    let file = std::fs::File::create(path)?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width as u32, height as u32);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(std::io::Error::other)?;
+    writer.write_image_data(&pixels).map_err(std::io::Error::other)?;
+    Ok(())
+}
+
+/// The most accesses of each kind any address of a heatmap got, to scale
+/// its colors by.
+pub struct HeatmapScale {
+    reads: u32,
+    writes: u32,
+    executes: u32,
+}
+
+impl HeatmapScale {
+    pub fn new(heatmap: &Heatmap) -> HeatmapScale {
+        let most = |counts: &[u32]| counts.iter().copied().max().unwrap_or(0);
+        HeatmapScale {
+            reads: most(&heatmap.reads),
+            writes: most(&heatmap.writes),
+            executes: most(&heatmap.executes),
+        }
+    }
+
+    /// The color of the addresses in `range`: executed in green, read in
+    /// blue and written in red, brighter the more they were accessed on a
+    /// log scale, and dark gray when never touched.
+    pub fn color(&self, heatmap: &Heatmap, range: std::ops::Range<usize>) -> [u8; 3] {
+        let level = |counts: &[u32], most: u32| {
+            let count = counts[range.clone()].iter().copied().max().unwrap_or(0);
+            match count {
+                0 => 0,
+                _ => 80 + (175.0 * (count as f64).ln_1p() / (most as f64).ln_1p()) as u8,
+            }
+        };
+        let color = [
+            level(&heatmap.writes, self.writes),
+            level(&heatmap.executes, self.executes),
+            level(&heatmap.reads, self.reads),
+        ];
+        match color {
+            [0, 0, 0] => [0x20, 0x20, 0x20],
+            color => color,
+        }
+    }
+}
+
+/// Writes a heatmap as a PNG, one cell per address in rows of a power of
+/// two, 64 for the 4K of a CHIP-8 and 256 for the 64K of an XO-CHIP, in the
+/// colors of `HeatmapScale::color` and about 512 pixels wide.
+pub fn write_heatmap_png(path: &Path, heatmap: &Heatmap) -> std::io::Result<()> {
+    let len = heatmap.executes.len().max(1);
+    let columns = 1usize << len.ilog2().div_ceil(2);
+    let rows = len.div_ceil(columns);
+    let scale = (512 / columns).max(1);
+    let (width, height) = (columns * scale, rows * scale);
+    let colors = HeatmapScale::new(heatmap);
+
+    let mut pixels = Vec::with_capacity(width * height * 3);
+    for y in 0..height {
+        for x in 0..width {
+            let address = y / scale * columns + x / scale;
+            match address < heatmap.executes.len() {
+                true => pixels.extend(colors.color(heatmap, address..address + 1)),
+                false => pixels.extend([0, 0, 0]),
+            }
+        }
+    }
+
+    let file = std::fs::File::create(path)?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(std::io::Error::other)?;
+    writer.write_image_data(&pixels).map_err(std::io::Error::other)?;
+    Ok(())
+}
@@ -0,0 +1,59 @@
+use chip8::romdb::{rom_hash, RomDatabase};
+
+use crate::paths;
+
+/// Loads the built-in database plus the user's overlay file, if present.
+pub fn load_database() -> RomDatabase {
+    let mut database = RomDatabase::builtin();
+    if let Some(path) = paths::config_dir().map(|dir| dir.join("romdb.toml")) {
+        if let Ok(text) = std::fs::read_to_string(&path) {
+            if let Err(error) = database.add_overlay(&text) {
+                tracing::warn!(target: "frontend", "Ignoring {}: {}", path.display(), error);
+            }
+        }
+    }
+    database
+}
+
+pub fn run(filename: &str) {
+    let rom = crate::rom::read_rom(filename).unwrap_or_else(|error| {
+        eprintln!("Unable to read {}: {}", filename, error);
+        crate::exit::ExitStatus::Error.exit();
+    });
+    let hash = rom_hash(&rom);
+
+    println!("File: {}", filename);
+    println!("Size: {} bytes", rom.len());
+    println!("SHA-1: {}", hash);
+
+    let database = load_database();
+    let Some(info) = database.lookup(&hash) else {
+        println!("Not in the ROM database");
+        return;
+    };
+
+    println!("Title: {}", info.title);
+    if let Some(author) = &info.author {
+        println!("Author: {}", author);
+    }
+    if let Some(platform) = &info.platform {
+        println!("Platform: {}", platform);
+    }
+    if !info.quirks.is_empty() {
+        println!("Quirks: {}", info.quirks.join(", "));
+    }
+    if let Some(tickrate) = info.tickrate {
+        println!("Speed: {} instructions/frame", tickrate);
+    }
+    if !info.keymap.is_empty() {
+        let keys: Vec<String> = info
+            .keymap
+            .iter()
+            .map(|(key, action)| format!("{}={}", key, action))
+            .collect();
+        println!("Keys: {}", keys.join(" "));
+    }
+    if let Some(description) = &info.description {
+        println!("\n{}", description);
+    }
+}
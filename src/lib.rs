@@ -0,0 +1,8 @@
+pub mod asm;
+pub mod chip8;
+pub mod disasm;
+pub mod expr;
+pub mod harness;
+pub mod romdb;
+pub mod source_map;
+pub mod symbols;
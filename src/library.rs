@@ -0,0 +1,243 @@
+use std::collections::{HashMap, VecDeque};
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver};
+
+use chip8::chip8::*;
+use chip8::romdb::{rom_hash, RomDatabase};
+use raylib::prelude::*;
+
+use crate::i18n;
+
+/// Frames each ROM is run for, headlessly, to produce its thumbnail.
+const THUMBNAIL_FRAMES: u32 = 300;
+const THUMBNAIL_CYCLES_PER_FRAME: u64 = 16;
+const ROW_HEIGHT: i32 = 72;
+const HEADER_HEIGHT: i32 = 40;
+
+pub struct LibraryEntry {
+    pub path: String,
+    pub title: String,
+    pub sha1: String,
+    pub recent: bool,
+}
+
+/// A ROM's display after a short headless run, and its width.
+struct Thumbnail {
+    display: Vec<u8>,
+    width: usize,
+}
+
+#[derive(Default)]
+pub struct Library {
+    pub entries: Vec<LibraryEntry>,
+    pub selected: usize,
+    pub playlist: VecDeque<String>,
+    /// Thumbnails rendered so far, by ROM SHA-1.
+    thumbnails: HashMap<String, Thumbnail>,
+    /// Thumbnails still being rendered on a background thread.
+    pending: Option<Receiver<(String, Thumbnail)>>,
+}
+
+fn is_rom(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(|extension| extension.to_str()),
+        Some("ch8") | Some("c8") | Some("mc8")
+    )
+}
+
+fn collect_roms(dir: &Path, roms: &mut Vec<String>) {
+    let Ok(read_dir) = std::fs::read_dir(dir) else {
+        tracing::warn!(target: "frontend", "Unable to read library directory {}", dir.display());
+        return;
+    };
+    for entry in read_dir.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_roms(&path, roms);
+        } else if is_rom(&path) {
+            roms.push(path.to_string_lossy().into_owned());
+        }
+    }
+}
+
+/// Runs the ROM headlessly for a few seconds. The seed is fixed so a ROM
+/// that draws random things always gets the same thumbnail.
+fn thumbnail(rom: &[u8]) -> Thumbnail {
+    let mut chip8 = with_seed(0);
+    load_rom(&mut chip8, rom);
+    for _ in 0..THUMBNAIL_FRAMES {
+        run(&mut chip8, THUMBNAIL_CYCLES_PER_FRAME);
+        update_timers(&mut chip8);
+    }
+    Thumbnail {
+        display: chip8.display,
+        width: chip8.display_width,
+    }
+}
+
+/// Titles a ROM from the ROM database where possible, falling back to the
+/// file name, and queues the ROM for a thumbnail unless one with the same
+/// hash already is.
+fn entry(
+    path: String,
+    database: &RomDatabase,
+    recent: bool,
+    roms: &mut HashMap<String, Vec<u8>>,
+) -> Option<LibraryEntry> {
+    let rom = crate::rom::read_rom(&path).ok()?;
+    let sha1 = rom_hash(&rom);
+    let title = match database.lookup_rom(&rom) {
+        Some(info) => info.title.clone(),
+        None => Path::new(&path)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_else(|| path.clone()),
+    };
+    roms.entry(sha1.clone()).or_insert(rom);
+    Some(LibraryEntry {
+        path,
+        title,
+        sha1,
+        recent,
+    })
+}
+
+/// Lists the `recent` ROMs followed by the .ch8/.c8/.mc8 files found by
+/// recursively scanning `dirs`. Their thumbnails are rendered on a background
+/// thread and show up as they are ready.
+pub fn scan(dirs: &[String], recent: &[String], database: &RomDatabase) -> Library {
+    let mut paths = Vec::new();
+    for dir in dirs {
+        collect_roms(Path::new(dir), &mut paths);
+    }
+
+    let mut roms = HashMap::new();
+    let mut scanned: Vec<LibraryEntry> = paths
+        .into_iter()
+        .filter_map(|path| entry(path, database, false, &mut roms))
+        .collect();
+    scanned.sort_by_key(|entry| entry.title.to_lowercase());
+
+    let mut entries: Vec<LibraryEntry> = recent
+        .iter()
+        .filter_map(|path| entry(path.clone(), database, true, &mut roms))
+        .collect();
+    entries.extend(scanned);
+
+    let pending = (!roms.is_empty()).then(|| {
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            for (sha1, rom) in roms {
+                // The library is gone once nobody is receiving.
+                if sender.send((sha1, thumbnail(&rom))).is_err() {
+                    break;
+                }
+            }
+        });
+        receiver
+    });
+
+    Library {
+        entries,
+        pending,
+        ..Default::default()
+    }
+}
+
+/// Picks up the thumbnails the background thread finished since last time.
+fn receive_thumbnails(library: &mut Library) {
+    let Some(pending) = &library.pending else {
+        return;
+    };
+    loop {
+        match pending.try_recv() {
+            Ok((sha1, thumbnail)) => {
+                library.thumbnails.insert(sha1, thumbnail);
+            }
+            Err(mpsc::TryRecvError::Empty) => return,
+            Err(mpsc::TryRecvError::Disconnected) => {
+                library.pending = None;
+                return;
+            }
+        }
+    }
+}
+
+/// Handles navigation keys while the library is shown, returning the ROM to
+/// start when one is chosen.
+pub fn handle_input(library: &mut Library, rl: &RaylibHandle) -> Option<String> {
+    receive_thumbnails(library);
+    if library.entries.is_empty() {
+        return None;
+    }
+
+    if rl.is_key_pressed(KeyboardKey::KEY_DOWN) {
+        library.selected = (library.selected + 1) % library.entries.len();
+    }
+    if rl.is_key_pressed(KeyboardKey::KEY_UP) {
+        library.selected = library
+            .selected
+            .checked_sub(1)
+            .unwrap_or(library.entries.len() - 1);
+    }
+    if rl.is_key_pressed(KeyboardKey::KEY_Q) {
+        let path = library.entries[library.selected].path.clone();
+        library.playlist.push_back(path);
+    }
+    if rl.is_key_pressed(KeyboardKey::KEY_ENTER) {
+        return Some(library.entries[library.selected].path.clone());
+    }
+    None
+}
+
+pub fn draw(d: &mut RaylibDrawHandle, library: &Library, screen_height: i32, pixel_color: Color) {
+    d.draw_text(
+        &i18n::trf("library_header", &[&library.playlist.len()]),
+        10,
+        10,
+        20,
+        Color::WHITE,
+    );
+
+    if library.entries.is_empty() {
+        d.draw_text(i18n::tr("library_empty"), 10, HEADER_HEIGHT, 20, Color::GRAY);
+        return;
+    }
+
+    let visible_rows = ((screen_height - HEADER_HEIGHT) / ROW_HEIGHT).max(1) as usize;
+    let first = library.selected.saturating_sub(visible_rows - 1);
+    for (row, entry) in library.entries.iter().enumerate().skip(first).take(visible_rows) {
+        let top = HEADER_HEIGHT + ((row - first) as i32 * ROW_HEIGHT);
+        if row == library.selected {
+            d.draw_rectangle_lines(5, top - 2, 700, ROW_HEIGHT - 4, Color::WHITE);
+        }
+
+        // Thumbnails fit in 128x64 screen pixels whatever the ROM's
+        // resolution; MegaChip displays are sampled every third pixel. Rows
+        // whose thumbnail is still rendering are left blank.
+        if let Some(thumbnail) = library.thumbnails.get(&entry.sha1) {
+            let width = thumbnail.width;
+            let height = thumbnail.display.len() / width;
+            let scale = (128 / width).max(1) as i32;
+            let step = (width.div_ceil(128)).max(height.div_ceil(64));
+            for (index, pixel) in thumbnail.display.iter().enumerate() {
+                let (x, y) = (index % width, index / width);
+                if *pixel != 0 && x % step == 0 && y % step == 0 {
+                    let (x, y) = ((x / step) as i32, (y / step) as i32);
+                    d.draw_rectangle(10 + x * scale, top + 2 + y * scale, scale, scale, pixel_color);
+                }
+            }
+        }
+
+        let mut label = entry.title.clone();
+        if entry.recent {
+            label.push(' ');
+            label.push_str(i18n::tr("library_recent"));
+        }
+        if library.playlist.contains(&entry.path) {
+            label.push(' ');
+            label.push_str(i18n::tr("library_queued"));
+        }
+        d.draw_text(&label, 150, top + 24, 20, Color::WHITE);
+    }
+}
@@ -0,0 +1,35 @@
+use std::sync::Mutex;
+
+use tracing_subscriber::filter::Targets;
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+use tracing_subscriber::prelude::*;
+
+/// Installs the global logger. `filter` is either a level (`warn`) or a list
+/// of per-target levels (`info,core=trace,input=debug`); the targets in use
+/// are `core`, `frontend`, `audio`, `input`, `script` and `rom`. Logs go to
+/// stderr unless a file is given, so stdout stays clean for pipelines.
+pub fn init(filter: &str, file: Option<&str>) {
+    let targets: Targets = match filter.parse() {
+        Ok(targets) => targets,
+        Err(error) => {
+            eprintln!("Invalid log level {:?}: {}", filter, error);
+            std::process::exit(2);
+        }
+    };
+
+    let (writer, ansi) = match file {
+        Some(path) => match std::fs::File::create(path) {
+            Ok(file) => (BoxMakeWriter::new(Mutex::new(file)), false),
+            Err(error) => {
+                eprintln!("Unable to create log file {}: {}", path, error);
+                std::process::exit(2);
+            }
+        },
+        None => (BoxMakeWriter::new(std::io::stderr), true),
+    };
+
+    tracing_subscriber::registry()
+        .with(tracing_subscriber::fmt::layer().with_writer(writer).with_ansi(ansi))
+        .with(targets)
+        .init();
+}
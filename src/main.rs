@@ -1,14 +1,369 @@
-use chip8::*;
-use clap::Parser;
+use chip8::chip8::*;
+use chip8::expr::{Expr, Watch};
+use chip8::romdb::{RomDatabase, RomInfo};
+use chip8::source_map::SourceMap;
+use chip8::symbols::Symbols;
+use clap::{Parser, Subcommand};
 use raylib::prelude::*;
 
-pub mod chip8;
+mod assemble;
+mod bench;
+mod calls;
+mod capture;
+mod coverage;
+mod crash;
+mod diff_state;
+mod disassemble;
+mod dump;
+mod exit;
+mod flags;
+mod gdb;
+mod i18n;
+mod image;
+mod info;
+mod library;
+mod logging;
+mod pacing;
+mod paths;
+mod profile;
+mod recent;
+mod remote;
+mod replay;
+mod rom;
+mod romdb_import;
+mod script;
+mod settings;
+mod snapshot_test;
+mod states;
+mod stats;
+mod threaded;
+mod trace;
+mod tui;
+mod watch;
 
-fn load_rom_from_file(chip8: &mut Chip8, filename: &str) {
-    let buffer = std::fs::read(filename).expect("Unable to read file");
-    for i in 0..buffer.len() {
-        chip8.memory[i + 0x200] = buffer[i];
+/// Loads a ROM into `chip8` and returns its bytes.
+fn load_rom_from_file(chip8: &mut Chip8, filename: &str) -> std::io::Result<Vec<u8>> {
+    let buffer = rom::read_rom(filename)?;
+    load_rom(chip8, &buffer);
+    Ok(buffer)
+}
+
+/// A fresh machine set up for the platform chosen on the command line.
+fn new_chip8(args: &ChipCliArgs, quirks: Quirks) -> Chip8 {
+    let mut chip8 = match args.seed {
+        Some(seed) => with_seed(seed),
+        None => initialize(),
+    };
+    chip8.quirks = quirks;
+    if args.eti660 {
+        set_eti660(&mut chip8);
+    }
+    if let Some(address) = args.start_address {
+        set_start_address(&mut chip8, address);
+    }
+    if let Some(size) = args.memory_size {
+        set_memory_size(&mut chip8, size);
+    }
+    if let Some(font) = args.font {
+        set_font(&mut chip8, font);
+    }
+    if let Some(policy) = args.out_of_bounds {
+        chip8.out_of_bounds = policy;
+    }
+    if args.strict {
+        chip8.strict = true;
+        chip8.out_of_bounds = OutOfBounds::Fault;
+    }
+    if let Some(depth) = args.stack_depth {
+        chip8.stack_depth = (depth > 0).then_some(depth);
+    }
+    if args.debug_port {
+        debug_port::enable(&mut chip8);
+    }
+    chip8.latch_input = args.latch_input;
+    for addresses in &args.watch_memory {
+        add_watchpoint(&mut chip8, addresses.clone());
+    }
+    for &register in &args.watch_register {
+        watch_register(&mut chip8, register);
+    }
+    chip8.break_on_unknown = args.break_on_unknown;
+    chip8.break_on_draw = args.break_on_draw;
+    chip8.break_on_clear = args.break_on_clear;
+    if args.heatmap_out.is_some() || args.coverage_out.is_some() {
+        heatmap::enable(&mut chip8);
+    }
+    chip8
+}
+
+/// Parses a font name, or reads an 80-byte font file.
+fn parse_font(value: &str) -> Result<[u8; 80], String> {
+    if let Some(font) = fonts::font_by_name(value) {
+        return Ok(font);
+    }
+    let bytes = std::fs::read(value).map_err(|error| format!("{}: {}", value, error))?;
+    bytes
+        .try_into()
+        .map_err(|bytes: Vec<u8>| format!("{} is {} bytes, a font is 80", value, bytes.len()))
+}
+
+/// Parses a memory size in bytes, or in kilobytes with a `k` suffix.
+fn parse_memory_size(value: &str) -> Result<usize, String> {
+    let (digits, unit) = match value.strip_suffix(['k', 'K']) {
+        Some(digits) => (digits, 1024),
+        None => (value, 1),
+    };
+    let size = digits.parse::<usize>().map_err(|error| error.to_string())? * unit;
+    if !size.is_power_of_two() || !(4096..=MEGACHIP_MEMORY_SIZE).contains(&size) {
+        return Err("memory size must be a power of two from 4k to 16384k".to_string());
+    }
+    Ok(size)
+}
+
+/// The quirks to run a ROM with. The `--quirks`/`--chip48` preset wins over
+/// the ROM database's, and the individual quirk flags apply on top of either.
+fn quirks_for(args: &ChipCliArgs, known: Option<&RomInfo>) -> Quirks {
+    let mut quirks = match (args.quirks_preset, known.and_then(RomInfo::quirks)) {
+        (Some(preset), _) => preset,
+        (None, _) if args.chip48 => Quirks::chip48(),
+        (None, Some(known)) => known,
+        (None, None) => Quirks::default(),
+    };
+    quirks.vf_reset |= args.vf_reset;
+    quirks.display_wait |= args.display_wait;
+    quirks.jump_with_vx |= args.jump_with_vx;
+    quirks.index_overflow |= args.index_overflow;
+    quirks.key_release |= args.key_release;
+    quirks.collision_row_count |= args.collision_row_count;
+    if let Some(memory_increment) = args.memory_increment {
+        quirks.memory_increment_i = memory_increment;
+    }
+    quirks
+}
+
+/// The ROM database entry used to configure `rom`, unless `--no-rom-db`.
+fn known_rom<'a>(args: &ChipCliArgs, database: &'a RomDatabase, rom: &[u8]) -> Option<&'a RomInfo> {
+    if args.no_rom_db {
+        return None;
+    }
+    let known = database.lookup_rom(rom)?;
+    tracing::info!(target: "frontend", "Configuring {} from the ROM database", known.title);
+    Some(known)
+}
+
+/// Where the emulator runs: in a window, or in the terminal as a debugger.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum Backend {
+    #[default]
+    Raylib,
+    TuiDebug,
+}
+
+impl std::str::FromStr for Backend {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "raylib" => Ok(Backend::Raylib),
+            "tui-debug" => Ok(Backend::TuiDebug),
+            _ => Err(format!("expected raylib or tui-debug, got {}", value)),
+        }
+    }
+}
+
+/// Parses an address given in hex, with or without a 0x prefix. Whether it
+/// is inside memory depends on the machine, see `check_address`.
+fn parse_address(value: &str) -> Result<u16, String> {
+    let digits = value.trim_start_matches("0x").trim_start_matches("0X");
+    u16::from_str_radix(digits, 16).map_err(|error| error.to_string())
+}
+
+/// Checks that `address` is inside `chip8`'s memory, whose size
+/// `--memory-size` and large ROMs change.
+fn check_address(chip8: &Chip8, address: u16) -> Result<u16, String> {
+    match (address as usize) < chip8.memory.len() {
+        true => Ok(address),
+        false => Err(format!("{:#05X} is past the end of memory at {:#05X}", address, chip8.memory.len() - 1)),
+    }
+}
+
+/// Parses a hex address or an inclusive `start-end` range anywhere in the
+/// MegaChip address space.
+fn parse_address_range(value: &str) -> Result<std::ops::RangeInclusive<u32>, String> {
+    let parse = |value: &str| {
+        let digits = value.trim().trim_start_matches("0x").trim_start_matches("0X");
+        match u32::from_str_radix(digits, 16) {
+            Ok(address) if (address as usize) < MEGACHIP_MEMORY_SIZE => Ok(address),
+            Ok(_) => Err("address must be below 0x1000000".to_string()),
+            Err(error) => Err(error.to_string()),
+        }
+    };
+    let (start, end) = match value.split_once('-') {
+        Some((start, end)) => (parse(start)?, parse(end)?),
+        None => (parse(value)?, parse(value)?),
+    };
+    if end < start {
+        return Err("the range ends before it starts".to_string());
+    }
+    Ok(start..=end)
+}
+
+/// Reads the cheats from `path`, or looks for `<rom>.cht` next to the ROM and
+/// then `<config>/cheats/<sha1>.cht`.
+fn load_cheats(path: Option<&str>, filename: &str, rom: &[u8]) -> Vec<cheats::Cheat> {
+    let candidates = match path {
+        Some(path) => vec![std::path::PathBuf::from(path)],
+        None => {
+            let mut candidates = vec![std::path::Path::new(filename).with_extension("cht")];
+            if let Some(dir) = paths::config_dir() {
+                candidates.push(dir.join("cheats").join(format!("{}.cht", chip8::romdb::rom_hash(rom))));
+            }
+            candidates
+        }
+    };
+
+    let Some((candidate, text)) = candidates
+        .into_iter()
+        .find_map(|candidate| std::fs::read_to_string(&candidate).ok().map(|text| (candidate, text)))
+    else {
+        if let Some(path) = path {
+            tracing::warn!(target: "frontend", "Unable to read cheats from {}", path);
+        }
+        return Vec::new();
+    };
+
+    match cheats::parse_cheats(&text) {
+        Ok(cheats) => {
+            tracing::info!(target: "frontend", "Loaded {} cheats from {}", cheats.len(), candidate.display());
+            cheats
+        }
+        Err(error) => {
+            tracing::warn!(target: "frontend", "Ignoring {}: {}", candidate.display(), error);
+            Vec::new()
+        }
+    }
+}
+
+/// Reads the ROM's labels from `path`, or from `<rom>.sym` next to the ROM,
+/// or for an .8o source from the source itself.
+fn load_symbols(path: Option<&str>, filename: &str) -> Symbols {
+    let candidate = match path {
+        Some(path) => std::path::PathBuf::from(path),
+        None => match rom::source_symbols(filename) {
+            Some(symbols) => return symbols,
+            None => std::path::Path::new(filename).with_extension("sym"),
+        },
+    };
+    let Ok(text) = std::fs::read_to_string(&candidate) else {
+        if let Some(path) = path {
+            tracing::warn!(target: "frontend", "Unable to read symbols from {}", path);
+        }
+        return Symbols::default();
+    };
+    match Symbols::parse(&text) {
+        Ok(symbols) => {
+            tracing::info!(target: "frontend", "Loaded {} labels from {}", symbols.len(), candidate.display());
+            symbols
+        }
+        Err(error) => {
+            tracing::warn!(target: "frontend", "Ignoring {}: {}", candidate.display(), error);
+            Symbols::default()
+        }
+    }
+}
+
+/// The source map of the ROM, assembling `path`, the ROM itself for an .8o
+/// source, or `<rom>.8o` next to it. A source that does not assemble into
+/// the ROM's bytes is ignored.
+fn load_source_map(path: Option<&str>, filename: &str, rom: &[u8]) -> Option<SourceMap> {
+    let candidate = match path {
+        Some(path) => path.to_string(),
+        None if rom::is_source(filename) => filename.to_string(),
+        None => std::path::Path::new(filename).with_extension("8o").to_string_lossy().into_owned(),
+    };
+    let Ok(source) = std::fs::read_to_string(&candidate) else {
+        if let Some(path) = path {
+            tracing::warn!(target: "frontend", "Unable to read the source {}", path);
+        }
+        return None;
+    };
+    match chip8::asm::assemble(&source) {
+        Ok(program) if program.bytes == rom => Some(SourceMap::new(&candidate, &source, &program)),
+        Ok(_) => {
+            tracing::warn!(target: "frontend", "Ignoring {}: it does not assemble into the ROM", candidate);
+            None
+        }
+        Err(error) => {
+            tracing::warn!(target: "frontend", "Ignoring {}: {}", candidate, error);
+            None
+        }
+    }
+}
+
+/// What the debugger knows about the ROM beyond its bytes.
+#[derive(Clone, Debug, Default)]
+struct DebugInfo {
+    symbols: Symbols,
+    source_map: Option<SourceMap>,
+    watches: Vec<Watch>,
+}
+
+/// The ROM's labels and source map, from the `--symbols` and `--source`
+/// files or the ones found next to it, and the `--watch-expr` expressions
+/// with those labels.
+fn load_debug_info(args: &ChipCliArgs, filename: &str, rom: &[u8]) -> DebugInfo {
+    let symbols = load_symbols(args.symbols.as_deref(), filename);
+    DebugInfo {
+        watches: args.watch_exprs.iter().map(|text| Watch::new(text, &symbols)).collect(),
+        symbols,
+        source_map: load_source_map(args.source.as_deref(), filename, rom),
+    }
+}
+
+/// Parses a label the symbols know, a `file.8o:line` source line, or a hex
+/// address.
+fn resolve_address(value: &str, debug_info: &DebugInfo) -> Result<u16, String> {
+    if let Some(address) = debug_info.symbols.address(value) {
+        return Ok(address);
+    }
+    if let Some((file, line)) = value.rsplit_once(':') {
+        let Some(source) = debug_info.source_map.as_ref().filter(|source| source.is_file(file)) else {
+            return Err(format!("{} is not the ROM's source", file));
+        };
+        let line = line.parse().map_err(|_| format!("expected a line number, got {}", line))?;
+        return source
+            .address(line)
+            .ok_or_else(|| format!("no code at or after line {} of {}", line, file));
     }
+    parse_address(value).map_err(|error| format!("{} is not a label or address: {}", value, error))
+}
+
+/// Sets a breakpoint given as an address, label or source line, followed by
+/// `if` and a condition for a conditional one, e.g. `loop if V3 == 5`.
+/// Returns its address.
+fn set_breakpoint(chip8: &mut Chip8, value: &str, debug_info: &DebugInfo) -> Result<u16, String> {
+    let (address, condition) = match value.split_once(" if ") {
+        Some((address, condition)) => (address.trim(), Some(condition)),
+        None => (value.trim(), None),
+    };
+    let address = check_address(chip8, resolve_address(address, debug_info)?)?;
+    match condition {
+        Some(condition) => {
+            let condition = Expr::parse(condition, &debug_info.symbols)
+                .map_err(|error| format!("bad condition {}: {}", condition, error))?;
+            add_conditional_breakpoint(chip8, address, condition);
+        }
+        None => add_breakpoint(chip8, address),
+    }
+    Ok(address)
+}
+
+/// Sets the `--break` breakpoints, looking labels and source lines up.
+fn add_breakpoints(chip8: &mut Chip8, breakpoints: &[String], debug_info: &DebugInfo) -> Result<(), String> {
+    for breakpoint in breakpoints {
+        set_breakpoint(chip8, breakpoint, debug_info)?;
+    }
+    Ok(())
 }
 
 #[derive(Parser, Debug)]
@@ -18,17 +373,407 @@ fn load_rom_from_file(chip8: &mut Chip8, filename: &str) {
     author = "Gabriel Kaszewski"
 )]
 struct ChipCliArgs {
-    #[clap(short, long, help = "Path to the ROM file")]
-    rom: String,
+    #[clap(subcommand)]
+    command: Option<ChipCommand>,
+    #[clap(
+        short,
+        long,
+        help = "Path to the ROM file or .8o source; opens the ROM picker when omitted"
+    )]
+    rom: Option<String>,
+    #[clap(
+        long,
+        value_name = "DIR",
+        help = "Directory to scan for ROMs shown in the library view (Tab); can be repeated"
+    )]
+    library: Vec<String>,
     #[clap(
         short = 'c',
         long = "tcps",
-        default_value = "1000",
-        help = "Target cycles per second"
+        help = "Target cycles per second [default: last used speed, or 1000]"
     )]
-    target_cycles_per_second: u32,
+    target_cycles_per_second: Option<u32>,
     #[clap(short, long, default_value = "20", help = "Pixel size")]
     pixel_size: usize,
+    #[clap(
+        long,
+        help = "Emulate the CHIP-48 interpreter's quirks (Bxnn jumps, Fx55/Fx65 increment I by x)"
+    )]
+    chip48: bool,
+    #[clap(
+        long = "quirks",
+        value_name = "chip8|schip|xochip|chip48",
+        conflicts_with = "chip48",
+        help = "Start from the quirks of an interpreter: COSMAC VIP, SUPER-CHIP 1.1, XO-CHIP or CHIP-48"
+    )]
+    quirks_preset: Option<Quirks>,
+    #[clap(long, help = "Clear VF after 8xy1/8xy2/8xy3, like the COSMAC VIP")]
+    vf_reset: bool,
+    #[clap(long, help = "Make Dxyn wait for the next 60Hz tick, like the COSMAC VIP")]
+    display_wait: bool,
+    #[clap(long, help = "Interpret Bnnn as Bxnn, jumping to xnn + Vx like SCHIP")]
+    jump_with_vx: bool,
+    #[clap(long, help = "Make Fx1E set VF when I goes past 0xFFF, like the Amiga interpreter")]
+    index_overflow: bool,
+    #[clap(long, help = "Make Fx0A take a key when it is released, like the COSMAC VIP")]
+    key_release: bool,
+    #[clap(
+        long,
+        help = "Make hires Dxyn set VF to the number of rows that collided or were clipped, like SCHIP 1.1"
+    )]
+    collision_row_count: bool,
+    #[clap(
+        long,
+        value_name = "none|x|x-plus-one",
+        help = "What Fx55/Fx65 add to I: nothing, x (CHIP-48) or x + 1 (COSMAC VIP)"
+    )]
+    memory_increment: Option<MemoryIncrement>,
+    #[clap(
+        long,
+        help = "Don't take quirks and speed for known ROMs from the ROM database"
+    )]
+    no_rom_db: bool,
+    #[clap(long, help = "Emulate the ETI-660: ROMs start at 0x600 on a 64x48 display")]
+    eti660: bool,
+    #[clap(
+        long,
+        value_name = "ADDRESS",
+        value_parser = parse_address,
+        help = "Load and start ROMs at this hex address instead of 0x200"
+    )]
+    start_address: Option<u16>,
+    #[clap(
+        long,
+        value_name = "SIZE",
+        value_parser = parse_memory_size,
+        help = "Memory size in bytes or with a k suffix, e.g. 64k for XO-CHIP [default: 4k]"
+    )]
+    memory_size: Option<usize>,
+    #[clap(
+        long,
+        value_name = "DEPTH",
+        help = "Return addresses the stack holds, e.g. 12 like the COSMAC VIP, or 0 for no limit [default: 16]"
+    )]
+    stack_depth: Option<usize>,
+    #[clap(
+        long,
+        value_name = "wrap|clamp|fault",
+        help = "What Dxyn, Fx33, Fx55 and Fx65 do past the end of memory [default: wrap]"
+    )]
+    out_of_bounds: Option<OutOfBounds>,
+    #[clap(
+        long,
+        conflicts_with = "threaded",
+        help = "Stop at the first unknown opcode, odd jump, write below the program or access past the end of memory, with a diagnostic"
+    )]
+    strict: bool,
+    #[clap(
+        long,
+        value_name = "NAME|PATH",
+        value_parser = parse_font,
+        help = "Hex digit font: vip, dream6800, eti660, fishnchips or an 80-byte font file"
+    )]
+    font: Option<[u8; 80]>,
+    #[clap(
+        long,
+        global = true,
+        help = "Reset and reload the ROM whenever the file changes, re-assembling .8o sources"
+    )]
+    watch: bool,
+    #[clap(
+        long,
+        help = "Run the CPU on its own thread, independent of rendering; only the core hotkeys are available"
+    )]
+    threaded: bool,
+    #[clap(
+        long,
+        value_name = "raylib|tui-debug",
+        default_value = "raylib",
+        help = "Run in a window, or in the terminal with a debugger UI (display, registers, disassembly, memory and a command prompt)"
+    )]
+    backend: Backend,
+    #[clap(long, help = "Start with emulation paused (press P or Space to resume)")]
+    start_paused: bool,
+    #[clap(long, help = "Exit after rendering this many frames")]
+    max_frames: Option<u64>,
+    #[clap(long, help = "Exit after executing this many instructions")]
+    max_cycles: Option<u64>,
+    #[clap(long, value_name = "SECONDS", help = "Exit with status 4 if the ROM has not halted after this long")]
+    timeout: Option<u64>,
+    #[clap(long, help = "Exit with status 0 once the ROM halts (jumps to itself)")]
+    exit_on_halt: bool,
+    #[clap(long, help = "Exit with status 3 as soon as the ROM hits an interpreter fault")]
+    exit_on_fault: bool,
+    #[clap(long, help = "Print the machine state to stdout when the run stops")]
+    dump_state: bool,
+    #[clap(
+        long,
+        value_name = "PATH",
+        help = "Write the full machine state as JSON to PATH (- for stdout) when the emulator exits"
+    )]
+    dump_state_on_exit: Option<std::path::PathBuf>,
+    #[clap(
+        long,
+        value_name = "FRAMES",
+        help = "Print a hash of the display every N frames and at exit"
+    )]
+    print_display_hash_every: Option<u64>,
+    #[clap(
+        long,
+        value_name = "PATH",
+        help = "Cheat file to use instead of <rom>.cht or <config>/cheats/<sha1>.cht"
+    )]
+    cheats: Option<String>,
+    #[clap(
+        long,
+        value_name = "PATH",
+        help = "Octo symbol file with the ROM's labels, instead of <rom>.sym or the labels of an .8o source"
+    )]
+    symbols: Option<String>,
+    #[clap(
+        long,
+        value_name = "PATH",
+        help = ".8o source the ROM was assembled from, for source-level debugging, instead of <rom>.8o"
+    )]
+    source: Option<String>,
+    #[clap(long, value_name = "PATH", help = "Lua script to run alongside the ROM")]
+    script: Option<String>,
+    #[clap(
+        long,
+        value_name = "PATH",
+        help = "Write run statistics (instruction counts, draw calls, faults, ...) as JSON at exit"
+    )]
+    stats: Option<String>,
+    #[clap(
+        long,
+        value_name = "PATH",
+        help = "Write a profile of the instructions run per opcode, address and loop as JSON at exit"
+    )]
+    profile_out: Option<String>,
+    #[clap(
+        long,
+        value_name = "PATH",
+        help = "Write a PNG heatmap of the memory read, written and executed at exit"
+    )]
+    heatmap_out: Option<String>,
+    #[clap(
+        long,
+        value_name = "PATH",
+        help = "Write which ROM addresses were executed, as ranges and a percentage, as JSON at exit (- for stdout)"
+    )]
+    coverage_out: Option<String>,
+    #[clap(
+        long,
+        help = "Keep the SCHIP RPL user flags (Fx75/Fx85) of each ROM between runs"
+    )]
+    save_flags: bool,
+    #[clap(
+        long,
+        help = "Give each instruction roughly the time it took on the COSMAC VIP instead of a flat rate; -c is ignored"
+    )]
+    vip_timing: bool,
+    #[clap(
+        long,
+        value_name = "PATH",
+        help = "Record the keys held and instructions run every frame to a replay file, with the seed and quirks"
+    )]
+    record: Option<String>,
+    #[clap(
+        long,
+        value_name = "PATH",
+        help = "Play a replay file back instead of reading the keyboard, stopping at the first frame that differs"
+    )]
+    playback: Option<String>,
+    #[clap(
+        long,
+        value_name = "PATH",
+        help = "Capture the display every frame to an animated GIF, or to a video through ffmpeg for other extensions"
+    )]
+    capture: Option<String>,
+    #[clap(long, default_value = "8", help = "Size of a 64x32 pixel in captured frames and F12 screenshots")]
+    capture_scale: usize,
+    #[clap(long, help = "Resume the ROM where it was left when the emulator last closed")]
+    resume: bool,
+    #[clap(
+        long,
+        value_name = "PATH",
+        help = "Write a line with the registers for every instruction executed to PATH (- for stdout)"
+    )]
+    trace: Option<String>,
+    #[clap(
+        long,
+        value_name = "native|octo",
+        default_value = "native",
+        help = "Layout of --trace lines; octo matches Octo's register names for diffing traces"
+    )]
+    trace_format: trace::TraceFormat,
+    #[clap(long, help = "Seed the random number generator so Cxkk gives the same numbers every run")]
+    seed: Option<u64>,
+    #[clap(
+        long,
+        help = "Keep a key press visible until Ex9E, ExA1 or Fx0A has seen it, so quick taps aren't missed at low speeds"
+    )]
+    latch_input: bool,
+    #[clap(
+        long = "break",
+        value_name = "ADDRESS|LABEL|FILE:LINE[ if EXPR]",
+        conflicts_with = "threaded",
+        help = "Pause before the instruction at this hex address, label or .8o source line runs, only while EXPR \
+                holds when given, e.g. \"loop if V3 == 5\"; can be repeated"
+    )]
+    breakpoints: Vec<String>,
+    #[clap(
+        long = "watch-expr",
+        value_name = "EXPR",
+        help = "Show the value of an expression, e.g. \"V[3] + V[4]\" or \"mem[I]\", with the registers (F2); can be repeated"
+    )]
+    watch_exprs: Vec<String>,
+    #[clap(
+        long,
+        value_name = "ADDRESS[-END]",
+        value_parser = parse_address_range,
+        conflicts_with = "threaded",
+        help = "Pause after any instruction that writes to this hex address or range; can be repeated"
+    )]
+    watch_memory: Vec<std::ops::RangeInclusive<u32>>,
+    #[clap(
+        long,
+        value_name = "V0-VF|I",
+        conflicts_with = "threaded",
+        help = "Pause after any instruction that changes this register; can be repeated"
+    )]
+    watch_register: Vec<Register>,
+    #[clap(
+        long,
+        conflicts_with = "threaded",
+        help = "Pause before an unknown opcode runs and show the registers and disassembly, instead of skipping it"
+    )]
+    break_on_unknown: bool,
+    #[clap(long, conflicts_with = "threaded", help = "Pause after every Dxyn, to see what each draw changes")]
+    break_on_draw: bool,
+    #[clap(long, conflicts_with = "threaded", help = "Pause after every 00E0 clears the display")]
+    break_on_clear: bool,
+    #[clap(
+        long,
+        value_name = "PORT",
+        conflicts_with = "threaded",
+        help = "Let gdb attach over the GDB remote protocol on this port of 127.0.0.1"
+    )]
+    gdb: Option<u16>,
+    #[clap(
+        long,
+        value_name = "PORT",
+        conflicts_with = "threaded",
+        help = "Serve an HTTP control API and a WebSocket display stream on this port of 127.0.0.1"
+    )]
+    remote: Option<u16>,
+    #[clap(long, help = "Log Vx on FxFF and the x + 1 bytes at I on FxFE, for debugging homebrew ROMs")]
+    debug_port: bool,
+    #[clap(
+        long,
+        global = true,
+        default_value = "warn",
+        help = "Log level, or per-target levels such as `info,core=trace` (targets: core, frontend, audio, input, script, rom)"
+    )]
+    log_level: String,
+    #[clap(long, global = true, value_name = "PATH", help = "Write logs to a file instead of stderr")]
+    log_file: Option<String>,
+}
+
+#[derive(Subcommand, Debug)]
+enum ChipCommand {
+    #[clap(about = "Assemble an Octo (.8o) source file into a ROM")]
+    Asm {
+        #[clap(help = "Path to the source file, or - for stdin")]
+        source: String,
+        #[clap(short, long, help = "Where to write the ROM; defaults to stdout")]
+        output: Option<String>,
+    },
+    #[clap(about = "Run a ROM headlessly as fast as possible and report performance")]
+    Bench {
+        #[clap(help = "Path to the ROM file")]
+        rom: String,
+        #[clap(short, long, default_value = "5", help = "How long to run the benchmark for, in seconds")]
+        seconds: u64,
+        #[clap(
+            short = 'c',
+            long = "tcps",
+            default_value = "1000",
+            help = "Target cycles per second, used to split the run into 60Hz frames"
+        )]
+        target_cycles_per_second: u32,
+    },
+    #[clap(about = "Print the differences between two save state files")]
+    DiffState {
+        #[clap(help = "Path to the first save state")]
+        first: String,
+        #[clap(help = "Path to the second save state")]
+        second: String,
+    },
+    #[clap(about = "Disassemble a ROM into Octo source that asm assembles back into it")]
+    Disasm {
+        #[clap(help = "Path to the ROM file, or - for stdin")]
+        rom: String,
+        #[clap(
+            long,
+            value_name = "ADDRESS",
+            value_parser = parse_address,
+            default_value = "200",
+            help = "Hex address the ROM is loaded at, for jump targets"
+        )]
+        start_address: u16,
+        #[clap(
+            long,
+            value_name = "PATH",
+            help = "Octo symbol file naming the labels, instead of <rom>.sym"
+        )]
+        symbols: Option<String>,
+    },
+    #[clap(about = "Show a ROM's hash and its entry in the ROM database")]
+    Info {
+        #[clap(help = "Path to the ROM file")]
+        rom: String,
+    },
+    #[clap(about = "Convert the CHIP-8 database's programs.json into ROM database entries")]
+    RomdbImport {
+        #[clap(help = "Path to programs.json from github.com/chip-8/chip-8-database")]
+        programs: String,
+    },
+    #[clap(about = "Run a ROM or .8o source, the same as --rom; e.g. chip8 run --watch game.8o")]
+    Run {
+        #[clap(help = "Path to the ROM file or .8o source")]
+        rom: String,
+    },
+    #[clap(about = "Run a ROM for a number of cycles and compare the display to a golden snapshot")]
+    Test {
+        #[clap(help = "Path to the ROM file")]
+        rom: String,
+        #[clap(long, help = "Number of instructions to execute")]
+        cycles: u64,
+        #[clap(long, default_value = "16", help = "Instructions per 60Hz timer tick")]
+        cycles_per_frame: u64,
+        #[clap(long, help = "Input script with `<cycle> <down|up> <key>` lines")]
+        input: Option<String>,
+        #[clap(long, help = "Path to the golden snapshot file")]
+        expect: String,
+        #[clap(long, help = "Write the current display to the golden file instead of comparing")]
+        update: bool,
+    },
+}
+
+fn print_state(chip8: &Chip8) {
+    println!(
+        "PC: {:#06X}  I: {:#06X}  DT: {}  ST: {}",
+        chip8.pc, chip8.i, chip8.timer_delay, chip8.timer_sound
+    );
+    for (i, v) in chip8.v.iter().enumerate() {
+        print!("V{:X}: {:#04X}{}", i, v, if i % 8 == 7 { "\n" } else { "  " });
+    }
+    if let Some(fault) = chip8.fault {
+        println!("Fault: {} (opcode {:04X})", fault, read_opcode(chip8, fault.pc()));
+    }
+    println!("Stack: {:04X?}", chip8.stack);
+    print!("{}", chip8::harness::display_snapshot(chip8));
 }
 
 fn handle_draw_debug(
@@ -42,14 +787,14 @@ fn handle_draw_debug(
 ) {
     if draw_cycles_info {
         d.draw_text(
-            &format!("Cycles per second: {}", cycles_per_second),
+            &i18n::trf("cycles_per_second", &[&cycles_per_second]),
             10,
             10,
             20,
             Color::WHITE,
         );
         d.draw_text(
-            &format!("Total cycles: {}", total_cycles),
+            &i18n::trf("total_cycles", &[&total_cycles]),
             10,
             30,
             20,
@@ -67,19 +812,335 @@ fn handle_draw_debug(
                 Color::WHITE,
             );
         }
+        let others = [
+            format!("PC: {:03X}", chip8.pc),
+            format!("I: {:03X}", chip8.i),
+            format!("DT: {}", chip8.timer_delay),
+            format!("ST: {}", chip8.timer_sound),
+        ];
+        for (row, text) in others.iter().enumerate() {
+            d.draw_text(
+                text,
+                screen_width - 80,
+                (10 + ((16 + row) * 20)).try_into().unwrap(),
+                20,
+                Color::WHITE,
+            );
+        }
+        // The instruction at PC, wider than the register column.
+        let opcode = peek_opcode(chip8);
+        d.draw_text(
+            &format!("{:04X} {}", opcode, decode(opcode)),
+            screen_width - 300,
+            10 + 20 * 20,
+            20,
+            Color::YELLOW,
+        );
+        // Breakpoints under the registers, the one execution is stopped at
+        // highlighted.
+        for (row, &address) in chip8.breakpoints.iter().enumerate() {
+            let color = if chip8.breakpoint_hit == Some(address) { Color::YELLOW } else { Color::RED };
+            let conditional = if chip8.breakpoint_conditions.contains_key(&address) { "?" } else { "" };
+            d.draw_text(
+                &format!("* {:03X}{}", address, conditional),
+                screen_width - 80,
+                (10 + ((22 + row) * 20)).try_into().unwrap(),
+                20,
+                color,
+            );
+        }
+        // The stack left of the registers, most recent return address first.
+        d.draw_text(&format!("SP: {}", chip8.stack.len()), screen_width - 160, 10, 20, Color::WHITE);
+        for (row, address) in chip8.stack.iter().rev().take(STACK_ROWS).enumerate() {
+            d.draw_text(
+                &format!("{:03X}", address),
+                screen_width - 160,
+                (30 + row * 20).try_into().unwrap(),
+                20,
+                Color::LIGHTGRAY,
+            );
+        }
+        if chip8.stack.len() > STACK_ROWS {
+            let y = (30 + STACK_ROWS * 20).try_into().unwrap();
+            d.draw_text("...", screen_width - 160, y, 20, Color::LIGHTGRAY);
+        }
+    }
+}
+
+/// Characters of the source line at PC the registers overlay shows.
+const SOURCE_COLUMNS: usize = 20;
+
+/// The .8o source line the instruction at PC was assembled from, under the
+/// instruction in the registers overlay.
+fn handle_draw_source_line(d: &mut RaylibDrawHandle, chip8: &Chip8, source_map: &SourceMap, screen_width: i32) {
+    let Some(line) = source_map.line(chip8.pc) else {
+        return;
+    };
+    let file = std::path::Path::new(&source_map.file).file_name().unwrap_or_default().to_string_lossy();
+    let text: String = source_map.text(line).unwrap_or_default().trim().chars().take(SOURCE_COLUMNS).collect();
+    d.draw_text(
+        &format!("{}:{} {}", file, line, text),
+        screen_width - 300,
+        10 + 21 * 20,
+        20,
+        Color::YELLOW,
+    );
+}
+
+/// Watch expressions and their values under the disassembly, left of the
+/// breakpoints, in the registers overlay.
+fn handle_draw_watches(d: &mut RaylibDrawHandle, chip8: &Chip8, watches: &[Watch], screen_width: i32) {
+    for (row, watch) in watches.iter().enumerate() {
+        let y = (10 + (23 + row) * 20).try_into().unwrap();
+        d.draw_text(&watch.show(chip8), screen_width - 460, y, 20, Color::SKYBLUE);
+    }
+}
+
+/// Return addresses the stack panel shows before eliding the oldest.
+const STACK_ROWS: usize = 16;
+
+/// Instructions the disassembly overlay shows before and after PC.
+const DISASSEMBLY_CONTEXT: usize = 7;
+
+/// Disassembly around PC, left of the registers and stack: the instruction at PC in
+/// yellow, breakpoints marked with `*` in red.
+fn handle_draw_disassembly(d: &mut RaylibDrawHandle, chip8: &Chip8, symbols: &Symbols, screen_width: i32) {
+    let pc = chip8.pc as usize;
+    let start = pc.saturating_sub(DISASSEMBLY_CONTEXT * 2);
+    let end = (pc + (DISASSEMBLY_CONTEXT + 1) * 2).min(chip8.memory.len());
+    // Disassembled in two parts so the instruction at PC always starts a
+    // line, even when the bytes before it are data.
+    let mut lines = chip8::disasm::disassemble(&chip8.memory[start..pc], start as u16);
+    lines.extend(chip8::disasm::disassemble(&chip8.memory[pc..end], chip8.pc));
+    for (row, line) in lines.iter().enumerate() {
+        let breakpoint = chip8.breakpoints.contains(&(line.address as u16));
+        let color = if line.address as usize == pc {
+            Color::YELLOW
+        } else if breakpoint {
+            Color::RED
+        } else {
+            Color::WHITE
+        };
+        let text = match line.instruction {
+            Some(instruction) => chip8::disasm::instruction_text(instruction, symbols),
+            None => format!("{:#04X}", line.bytes[0]),
+        };
+        let label = symbols.name(line.address as u16).map_or(String::new(), |name| format!("{}: ", name));
+        d.draw_text(
+            &format!("{} {:03X}  {}{}", if breakpoint { "*" } else { " " }, line.address, label, text),
+            screen_width - 460,
+            (10 + row * 20).try_into().unwrap(),
+            20,
+            color,
+        );
+    }
+}
+
+/// Addresses the profiler overlay lists.
+const HOT_SPOTS: usize = 10;
+
+/// The addresses that ran most on the left, with their share of all the
+/// instructions run and the instruction there.
+fn handle_draw_profile(d: &mut RaylibDrawHandle, chip8: &Chip8, profiler: &profile::Profiler, symbols: &Symbols) {
+    d.draw_text(i18n::tr("hot_spots"), 10, 60, 20, Color::WHITE);
+    for (row, spot) in profiler.hot_spots(HOT_SPOTS).iter().enumerate() {
+        let text = format!(
+            "{:03X} {:5.1}%  {}",
+            spot.address,
+            profiler.share(spot.count),
+            profile::Profiler::instruction(chip8, spot.address, symbols)
+        );
+        d.draw_text(&text, 10, (80 + row * 20).try_into().unwrap(), 20, Color::LIGHTGRAY);
+    }
+}
+
+/// Changes of each register the register history overlay shows.
+const REGISTER_HISTORY_ROWS: usize = 2;
+
+/// The latest changes of each V register on the left, under the profiler,
+/// the newest first: the address of the instruction that made it, the old
+/// and new value, and the instruction count when it happened.
+fn handle_draw_register_history(d: &mut RaylibDrawHandle, chip8: &Chip8) {
+    d.draw_text(i18n::tr("register_history"), 10, 300, 20, Color::WHITE);
+    let history = &chip8.register_history;
+    for x in 0..16 {
+        let changes: Vec<String> = history
+            .writes(x)
+            .rev()
+            .take(REGISTER_HISTORY_ROWS)
+            .map(ToString::to_string)
+            .collect();
+        let y = (320 + x * 20).try_into().unwrap();
+        d.draw_text(&format!("V{:X}  {}", x, changes.join("  ")), 10, y, 20, Color::LIGHTGRAY);
+    }
+}
+
+/// Calls the call chain under the call timeline lists before eliding the
+/// oldest.
+const CALL_ROWS: usize = 8;
+
+/// The call timeline at the top in the middle, a bar per frame from the
+/// shallowest to the deepest the stack got, scaled to the deepest frame
+/// shown, and under it the live call chain, the most recent call first.
+fn handle_draw_calls(
+    d: &mut RaylibDrawHandle,
+    chip8: &Chip8,
+    timeline: &calls::CallTimeline,
+    symbols: &Symbols,
+    screen_width: i32,
+) {
+    const BAR: i32 = 2;
+    const HEIGHT: i32 = 80;
+    let left = (screen_width - calls::TIMELINE_FRAMES as i32 * BAR) / 2;
+    let deepest = timeline.deepest();
+    d.draw_text(&i18n::trf("call_timeline", &[&deepest]), left, 10, 20, Color::WHITE);
+    let bottom = 35 + HEIGHT;
+    let scale = deepest.max(4) as i32;
+    d.draw_line(left, bottom, left + calls::TIMELINE_FRAMES as i32 * BAR, bottom, Color::DARKGRAY);
+    for (x, (low, high)) in timeline.frames().enumerate() {
+        let top = bottom - high as i32 * HEIGHT / scale;
+        let height = ((high - low) as i32 * HEIGHT / scale).max(BAR);
+        d.draw_rectangle(left + x as i32 * BAR, top, BAR, height, Color::SKYBLUE);
+    }
+    let chain = call_chain(chip8);
+    for (row, call) in chain.iter().take(CALL_ROWS).enumerate() {
+        let y = bottom + 10 + row as i32 * 20;
+        d.draw_text(&calls::call_text(call, symbols), left, y, 20, Color::LIGHTGRAY);
+    }
+    if chain.len() > CALL_ROWS {
+        d.draw_text("...", left, bottom + 10 + CALL_ROWS as i32 * 20, 20, Color::LIGHTGRAY);
+    }
+}
+
+/// Cells per side of the heatmap overlay, each covering 1/4096 of memory.
+const HEATMAP_CELLS: usize = 64;
+
+/// The memory heatmap at the bottom in the middle, one byte per cell for
+/// 4K of memory and the busiest of each 16 bytes for 64K, in the colors of
+/// the `--heatmap-out` PNG.
+fn handle_draw_heatmap(d: &mut RaylibDrawHandle, heatmap: &heatmap::Heatmap, screen_width: i32, screen_height: i32) {
+    const CELL: i32 = 4;
+    let size = HEATMAP_CELLS as i32 * CELL;
+    let left = (screen_width - size) / 2;
+    let top = screen_height - size - 10;
+    d.draw_text(i18n::tr("heatmap"), left, top - 25, 20, Color::WHITE);
+    let colors = image::HeatmapScale::new(heatmap);
+    let bytes = (heatmap.executes.len() / (HEATMAP_CELLS * HEATMAP_CELLS)).max(1);
+    for cell in 0..HEATMAP_CELLS * HEATMAP_CELLS {
+        let start = (cell * bytes).min(heatmap.executes.len());
+        let end = (start + bytes).min(heatmap.executes.len());
+        let [r, g, b] = colors.color(heatmap, start..end);
+        let (x, y) = ((cell % HEATMAP_CELLS) as i32, (cell / HEATMAP_CELLS) as i32);
+        d.draw_rectangle(left + x * CELL, top + y * CELL, CELL, CELL, Color::new(r, g, b, 255));
+    }
+}
+
+/// Keypad keys in the COSMAC VIP's 4x4 layout, row by row.
+const KEYPAD_LAYOUT: [usize; 16] = [0x1, 0x2, 0x3, 0xC, 0x4, 0x5, 0x6, 0xD, 0x7, 0x8, 0x9, 0xE, 0xA, 0x0, 0xB, 0xF];
+
+/// The keypad in the bottom right corner, with the keys the machine sees as
+/// held filled in `color` and each labelled with its host key.
+fn handle_draw_keypad(d: &mut RaylibDrawHandle, chip8: &Chip8, screen_width: i32, screen_height: i32, color: Color) {
+    const CELL: i32 = 40;
+    let left = screen_width - 4 * CELL - 10;
+    let top = screen_height - 4 * CELL - 10;
+    for (index, &key) in KEYPAD_LAYOUT.iter().enumerate() {
+        let x = left + (index % 4) as i32 * CELL;
+        let y = top + (index / 4) as i32 * CELL;
+        if chip8.keypad.is_down(key) {
+            d.draw_rectangle(x, y, CELL, CELL, color);
+        }
+        d.draw_rectangle_lines(x, y, CELL, CELL, Color::GRAY);
+        d.draw_text(&format!("{:X}", key), x + 6, y + 4, 20, Color::WHITE);
+        // Letter and digit keys are their ASCII codes in raylib.
+        let host = (host_key(key) as u8 as char).to_string();
+        d.draw_text(&host, x + CELL - 14, y + CELL - 16, 10, Color::LIGHTGRAY);
+    }
+}
+
+/// Whether P or Space, which pause and resume emulation, was pressed.
+fn pause_pressed(rl: &RaylibHandle) -> bool {
+    rl.is_key_pressed(KeyboardKey::KEY_P) || rl.is_key_pressed(KeyboardKey::KEY_SPACE)
+}
+
+/// Pixel colors cycled with `[` and `]`.
+const COLORS: [Color; 19] = [
+    Color::RED,
+    Color::BLUE,
+    Color::GREEN,
+    Color::YELLOW,
+    Color::ORANGE,
+    Color::PURPLE,
+    Color::PINK,
+    Color::GOLD,
+    Color::LIME,
+    Color::MAROON,
+    Color::DARKBLUE,
+    Color::DARKGREEN,
+    Color::DARKPURPLE,
+    Color::DARKGRAY,
+    Color::GRAY,
+    Color::BLACK,
+    Color::WHITE,
+    Color::RAYWHITE,
+    Color::MAGENTA,
+];
+
+const TIMER_PERIOD: std::time::Duration = std::time::Duration::from_nanos(1_000_000_000 / 60);
+const FRAME_PERIOD: std::time::Duration = std::time::Duration::from_nanos(1_000_000_000 / 60);
+const MAX_CATCH_UP: std::time::Duration = std::time::Duration::from_millis(250);
+
+fn instruction_period_for(cycles_per_second: u32) -> std::time::Duration {
+    std::time::Duration::from_secs_f64(1.0 / cycles_per_second as f64)
+}
+
+/// Keys that toggle the first nine cheats while paused.
+const CHEAT_KEYS: [KeyboardKey; 9] = [
+    KeyboardKey::KEY_ONE,
+    KeyboardKey::KEY_TWO,
+    KeyboardKey::KEY_THREE,
+    KeyboardKey::KEY_FOUR,
+    KeyboardKey::KEY_FIVE,
+    KeyboardKey::KEY_SIX,
+    KeyboardKey::KEY_SEVEN,
+    KeyboardKey::KEY_EIGHT,
+    KeyboardKey::KEY_NINE,
+];
+
+fn draw_cheats(d: &mut RaylibDrawHandle, cheats: &[cheats::Cheat], screen_height: i32) {
+    let top = screen_height - 80 - cheats.len().min(CHEAT_KEYS.len()) as i32 * 20;
+    for (index, cheat) in cheats.iter().take(CHEAT_KEYS.len()).enumerate() {
+        d.draw_text(
+            &format!("{}. [{}] {}", index + 1, if cheat.enabled { "x" } else { " " }, cheat.name),
+            10,
+            top + index as i32 * 20,
+            20,
+            Color::WHITE,
+        );
     }
 }
 
 fn handle_draw_emulator(d: &mut RaylibDrawHandle, chip8: &Chip8, pixel_size: usize, pixel_color: Color) {
-    for y in 0..32 {
-        for x in 0..64 {
-            if chip8.display[(y * 64) + x] == 1 {
+    // `pixel_size` is for the 64x32 display; hires pixels are half as big so
+    // the picture keeps its size.
+    let width = chip8.display_width;
+    let pixel_size = (pixel_size * 64 / width).max(1);
+    for y in 0..chip8.display_height {
+        for x in 0..width {
+            let pixel = chip8.display[(y * width) + x];
+            if pixel != 0 {
+                let color = if chip8.megachip {
+                    let [_, r, g, b] = chip8.palette[pixel as usize].to_be_bytes();
+                    Color::new(r, g, b, 255)
+                } else {
+                    pixel_color
+                };
                 d.draw_rectangle(
                     (x * pixel_size).try_into().unwrap(),
                     (y * pixel_size).try_into().unwrap(),
                     pixel_size as i32,
                     pixel_size as i32,
-                    pixel_color,
+                    color,
                 );
             }
         }
@@ -87,62 +1148,267 @@ fn handle_draw_emulator(d: &mut RaylibDrawHandle, chip8: &Chip8, pixel_size: usi
 }
 
 fn main() {
-    let args: ChipCliArgs = ChipCliArgs::parse();
+    let mut args: ChipCliArgs = ChipCliArgs::parse();
+    let log_level = if args.debug_port { format!("{},rom=info", args.log_level) } else { args.log_level.clone() };
+    logging::init(&log_level, args.log_file.as_deref());
+
+    if let Some(ChipCommand::Run { rom }) = &args.command {
+        args.rom = Some(rom.clone());
+    } else if let Some(command) = args.command.take() {
+        match command {
+            ChipCommand::Run { .. } => unreachable!("handled above"),
+            ChipCommand::Asm { source, output } => assemble::run(&source, output.as_deref()),
+            ChipCommand::Bench {
+                rom,
+                seconds,
+                target_cycles_per_second,
+            } => bench::run(&rom, seconds, target_cycles_per_second),
+            ChipCommand::DiffState { first, second } => diff_state::run(&first, &second),
+            ChipCommand::Disasm {
+                rom,
+                start_address,
+                symbols,
+            } => disassemble::run(&rom, start_address, symbols.as_deref()),
+            ChipCommand::Info { rom } => info::run(&rom),
+            ChipCommand::RomdbImport { programs } => romdb_import::run(&programs),
+            ChipCommand::Test {
+                rom,
+                cycles,
+                cycles_per_frame,
+                input,
+                expect,
+                update,
+            } => snapshot_test::run(
+                &rom,
+                cycles,
+                cycles_per_frame,
+                input.as_deref(),
+                &expect,
+                update,
+            ),
+        }
+        return;
+    }
 
     // Initialize the Chip8
-    let mut chip8 = initialize();
+    let database = info::load_database();
+    let mut chip8 = new_chip8(&args, quirks_for(&args, None));
+    let mut cheats = Vec::new();
+    let mut debug_info = DebugInfo::default();
+    let mut rom_hash = None;
+    // The file the running ROM came from; screenshots are saved next to it.
+    let mut rom_path = args.rom.clone();
+    let mut rom_cycles_per_second = None;
+    // Extra host keys from the ROM's keymap in the database.
+    let mut key_bindings = Vec::new();
+    // Why the ROM given on the command line did not load, when watch mode
+    // keeps running to pick up the fixed file.
+    let mut load_error = None;
     // Load ROM into memory
-    let filename = args.rom;
-    load_rom_from_file(&mut chip8, &filename);
+    if let Some(filename) = &args.rom {
+        tracing::info!(target: "frontend", "Loading {}", filename);
+        match load_rom_from_file(&mut chip8, filename) {
+            Ok(rom) => {
+                let known = known_rom(&args, &database, &rom);
+                chip8.quirks = quirks_for(&args, known);
+                rom_cycles_per_second = known.and_then(RomInfo::cycles_per_second);
+                key_bindings = known.map(RomInfo::key_bindings).unwrap_or_default();
+                recent::record(filename);
+                cheats = load_cheats(args.cheats.as_deref(), filename, &rom);
+                debug_info = load_debug_info(&args, filename, &rom);
+                rom_hash = Some(chip8::romdb::rom_hash(&rom));
+            }
+            Err(error) => {
+                eprintln!("Unable to load {}: {}", filename, error);
+                if !args.watch {
+                    exit::ExitStatus::Error.exit();
+                }
+                load_error = Some(error.to_string());
+            }
+        }
+    }
+    if let Err(error) = add_breakpoints(&mut chip8, &args.breakpoints, &debug_info) {
+        eprintln!("{}", error);
+        exit::ExitStatus::Error.exit();
+    }
+    if let (true, Some(hash)) = (args.save_flags, &rom_hash) {
+        flags::load(&mut chip8, hash);
+    }
+
+    let mut script = args.script.as_deref().map(|path| {
+        script::Script::load(path, &mut chip8).unwrap_or_else(|error| {
+            eprintln!("Unable to load script {}: {}", path, error);
+            exit::ExitStatus::Error.exit();
+        })
+    });
+
+    let mut watcher = args
+        .rom
+        .as_deref()
+        .filter(|filename| args.watch && *filename != "-")
+        .map(watch::RomWatcher::new);
+
+    let mut library = library::scan(&args.library, &recent::load(), &database);
+    let mut show_library = args.rom.is_none();
+
+    let mut settings = settings::load();
+    i18n::init(&settings.language);
+    let target_cycles_per_second = args
+        .target_cycles_per_second
+        .or(rom_cycles_per_second)
+        .unwrap_or(settings.cycles_per_second);
+
+    if args.backend == Backend::TuiDebug {
+        tui::run(chip8, debug_info, cheats, target_cycles_per_second, args.start_paused);
+        return;
+    }
 
     let (mut rl, thread) = raylib::init()
-        .size(1280, 720)
+        .size(settings.window_width, settings.window_height)
+        .resizable()
         .title("Chip8")
         //.vsync()
         .build();
 
+    if args.threaded {
+        threaded::run(
+            chip8,
+            debug_info,
+            key_bindings,
+            target_cycles_per_second,
+            args.start_paused,
+            args.pixel_size,
+            rl,
+            thread,
+        );
+        return;
+    }
+
     let pixel_size = args.pixel_size;
     let mut cycles = 0;
     let mut total_cycles = 0;
+    let mut frames_rendered: u64 = 0;
+    let mut cycles_executed: u64 = 0;
+    let mut run_stats = stats::RunStats::new();
+    let mut profiler = profile::Profiler::new();
+    let mut call_timeline = calls::CallTimeline::new();
+    let started = std::time::Instant::now();
+    let mut crash_reported = false;
+    let mut exit_status = exit::ExitStatus::Success;
     let mut cycles_per_second = 0;
     let mut last_time = std::time::Instant::now();
 
-    let mut draw_debug_cycles_info = false;
-    let mut draw_debug_registers_info = true;
-    let mut draw_emulator = true;
-
-    let original_target_cycles_per_second = args.target_cycles_per_second;
-    let mut target_cycles_per_second = args.target_cycles_per_second;
-    let mut sleep_duration =
-        std::time::Duration::from_millis(1000 / target_cycles_per_second as u64);
+    let mut draw_debug_cycles_info = settings.show_cycles_info;
+    let mut draw_debug_registers_info = settings.show_registers;
+    let mut draw_emulator = settings.show_display;
+    let mut draw_disassembly = settings.show_disassembly;
+    let mut draw_keypad = settings.show_keypad;
+    let mut draw_profile = settings.show_profile;
+    let mut draw_heatmap = settings.show_heatmap;
+    let mut draw_calls = settings.show_calls;
+    let mut draw_register_history = settings.show_register_history;
+    let mut paused = args.start_paused;
+    // Runs that end on their own, e.g. in CI.
+    let unattended = args.exit_on_fault
+        || args.strict
+        || args.exit_on_halt
+        || args.max_frames.is_some()
+        || args.max_cycles.is_some()
+        || args.timeout.is_some()
+        || args.playback.is_some();
+    // Short status text shown at the bottom of the screen, e.g. assembly errors.
+    let mut message: Option<(String, std::time::Instant)> = match (&args.rom, load_error) {
+        (Some(filename), Some(error)) => {
+            Some((i18n::trf("rom_load_failed", &[filename, &error]), std::time::Instant::now()))
+        }
+        _ => None,
+    };
+    // Input recording covers the ROM given on the command line from its first
+    // instruction; it stops when another ROM is loaded.
+    let mut recorder = match (&args.record, &rom_hash) {
+        (Some(path), Some(hash)) => {
+            let seed = args.seed.unwrap_or_else(rand::random);
+            set_seed(&mut chip8, seed);
+            Some(replay::Recorder::new(path, hash, seed, chip8.quirks))
+        }
+        (Some(_), None) => {
+            eprintln!("--record needs a ROM given with --rom");
+            exit::ExitStatus::Error.exit();
+        }
+        _ => None,
+    };
+    let mut player = args.playback.as_deref().map(|path| {
+        let player = replay::Player::load(path).unwrap_or_else(|error| {
+            eprintln!("Unable to load replay {}", error);
+            exit::ExitStatus::Error.exit();
+        });
+        if rom_hash.as_deref() != Some(player.replay.rom_sha1.as_str()) {
+            eprintln!("{} was recorded with another ROM (SHA-1 {})", path, player.replay.rom_sha1);
+            exit::ExitStatus::Error.exit();
+        }
+        set_seed(&mut chip8, player.replay.seed);
+        chip8.quirks = player.replay.quirks;
+        player
+    });
+    let mut capture = args.capture.as_deref().map(|path| {
+        capture::Capture::start(path, &chip8, args.capture_scale).unwrap_or_else(|error| {
+            eprintln!("Unable to capture to {}: {}", path, error);
+            exit::ExitStatus::Error.exit();
+        })
+    });
+    let mut tracer = args.trace.as_deref().map(|path| {
+        trace::Tracer::create(path, args.trace_format).unwrap_or_else(|error| {
+            eprintln!("Unable to write trace to {}: {}", path, error);
+            exit::ExitStatus::Error.exit();
+        })
+    });
+    let mut gdb = args.gdb.map(|port| {
+        gdb::GdbServer::bind(port).unwrap_or_else(|error| {
+            eprintln!("Unable to listen for gdb on port {}: {}", port, error);
+            exit::ExitStatus::Error.exit();
+        })
+    });
+    let mut remote = args.remote.map(|port| {
+        remote::RemoteServer::bind(port).unwrap_or_else(|error| {
+            eprintln!("Unable to serve the remote API on port {}: {}", port, error);
+            exit::ExitStatus::Error.exit();
+        })
+    });
+    // Set once the replay has been played to the end or diverged.
+    let mut replay_finished = false;
+    // Whether F6 resumes the ROM from where it was left last time.
+    let mut resume_offered = false;
+    if let Some(hash) = rom_hash.as_deref().filter(|hash| states::can_resume(hash)) {
+        if args.resume {
+            if let Err(error) = states::resume(&mut chip8, hash) {
+                tracing::error!(target: "frontend", "Unable to resume: {}", error);
+            }
+        } else {
+            resume_offered = true;
+            message = Some((i18n::tr("resume_offer").to_string(), std::time::Instant::now()));
+        }
+    }
 
-    let screen_width = rl.get_screen_width();
+    let mut original_target_cycles_per_second = target_cycles_per_second;
+    let mut target_cycles_per_second = target_cycles_per_second;
+    let mut instruction_period = instruction_period_for(target_cycles_per_second);
+    // Real time owed to the CPU and the 60Hz timers but not yet emulated.
+    let mut instruction_accumulator = std::time::Duration::ZERO;
+    let mut timer_accumulator = std::time::Duration::ZERO;
+    let mut last_tick = std::time::Instant::now();
 
-    let colors = [
-        Color::RED,
-        Color::BLUE,
-        Color::GREEN,
-        Color::YELLOW,
-        Color::ORANGE,
-        Color::PURPLE,
-        Color::PINK,
-        Color::GOLD,
-        Color::LIME,
-        Color::MAROON,
-        Color::DARKBLUE,
-        Color::DARKGREEN,
-        Color::DARKPURPLE,
-        Color::DARKGRAY,
-        Color::GRAY,
-        Color::BLACK,
-        Color::WHITE,
-        Color::RAYWHITE,
-        Color::MAGENTA,
-    ];
-    let mut current_color_index = 0;
+    let mut current_color_index = (settings.color % COLORS.len()) as i32;
 
     // loop
     while !rl.window_should_close() {
+        let now = std::time::Instant::now();
+        // After a stall (window drag, breakpoint) skip ahead instead of running
+        // a huge batch to catch up.
+        let delta = (now - last_tick).min(MAX_CATCH_UP);
+        last_tick = now;
+        let screen_width = rl.get_screen_width();
+        let screen_height = rl.get_screen_height();
+
         // calculate cycles per second and fps
         let elapsed = last_time.elapsed();
         if elapsed.as_secs() >= 1 {
@@ -164,73 +1430,668 @@ fn main() {
             draw_emulator = !draw_emulator;
         }
 
+        if rl.is_key_pressed(KeyboardKey::KEY_F7) {
+            draw_disassembly = !draw_disassembly;
+        }
+
+        if rl.is_key_pressed(KeyboardKey::KEY_K) && !show_library {
+            draw_keypad = !draw_keypad;
+        }
+
+        if rl.is_key_pressed(KeyboardKey::KEY_H) && !show_library {
+            draw_profile = !draw_profile;
+        }
+
+        if rl.is_key_pressed(KeyboardKey::KEY_M) && !show_library {
+            draw_heatmap = !draw_heatmap;
+        }
+        if rl.is_key_pressed(KeyboardKey::KEY_T) && !show_library {
+            draw_calls = !draw_calls;
+        }
+
+        if rl.is_key_pressed(KeyboardKey::KEY_R) && !show_library {
+            draw_register_history = !draw_register_history;
+        }
+
+        if draw_heatmap && chip8.heatmap.is_none() {
+            heatmap::enable(&mut chip8);
+        }
+
+        if rl.is_key_pressed(KeyboardKey::KEY_J) && !show_library {
+            let text = match dump::write_to_cache(&chip8, rom_hash.as_deref()) {
+                Ok(path) => i18n::trf("state_dumped", &[&path.display()]),
+                Err(error) => {
+                    tracing::error!(target: "frontend", "Unable to dump state: {}", error);
+                    i18n::trf("state_dump_failed", &[&error])
+                }
+            };
+            message = Some((text, std::time::Instant::now()));
+        }
+
+        if rl.is_key_pressed(KeyboardKey::KEY_F12) && !show_library {
+            let path = image::screenshot_path(rom_path.as_deref().unwrap_or("-"));
+            let Color { r, g, b, .. } = COLORS[current_color_index as usize];
+            let text = match image::write_screenshot(&path, &chip8, args.capture_scale, [r, g, b]) {
+                Ok(()) => i18n::trf("screenshot_saved", &[&path.display()]),
+                Err(error) => {
+                    tracing::error!(target: "frontend", "Unable to save screenshot {}: {}", path.display(), error);
+                    i18n::trf("screenshot_failed", &[&error])
+                }
+            };
+            message = Some((text, std::time::Instant::now()));
+        }
+
+        if rl.is_key_pressed(KeyboardKey::KEY_F4) && !show_library {
+            reset(&mut chip8);
+            crash_reported = false;
+            message = Some((i18n::tr("rom_reset").to_string(), std::time::Instant::now()));
+        }
+
+        if let (true, Some(hash)) = (rl.is_key_pressed(KeyboardKey::KEY_F5), &rom_hash) {
+            let text = match states::save(&chip8, hash) {
+                Ok(_) => i18n::tr("state_saved").to_string(),
+                Err(error) => {
+                    tracing::error!(target: "frontend", "Unable to save state: {}", error);
+                    i18n::trf("state_save_failed", &[&error])
+                }
+            };
+            message = Some((text, std::time::Instant::now()));
+        }
+
+        if let (true, Some(hash)) = (rl.is_key_pressed(KeyboardKey::KEY_F9), &rom_hash) {
+            let text = match states::load(&mut chip8, hash) {
+                Ok(()) => {
+                    crash_reported = false;
+                    i18n::tr("state_loaded").to_string()
+                }
+                Err(error) => {
+                    tracing::error!(target: "frontend", "Unable to load state: {}", error);
+                    i18n::trf("state_load_failed", &[&error])
+                }
+            };
+            message = Some((text, std::time::Instant::now()));
+        }
+
+        if let (true, true, Some(hash)) = (resume_offered, rl.is_key_pressed(KeyboardKey::KEY_F6), &rom_hash) {
+            resume_offered = false;
+            let text = match states::resume(&mut chip8, hash) {
+                Ok(()) => i18n::tr("state_loaded").to_string(),
+                Err(error) => {
+                    tracing::error!(target: "frontend", "Unable to resume: {}", error);
+                    i18n::trf("state_load_failed", &[&error])
+                }
+            };
+            message = Some((text, std::time::Instant::now()));
+        }
+
+        if rl.is_key_pressed(KeyboardKey::KEY_TAB) && !library.entries.is_empty() {
+            show_library = !show_library;
+        }
+
+        let mut next_rom = None;
+        if show_library {
+            next_rom = library::handle_input(&mut library, &rl);
+        }
+        if rl.is_key_pressed(KeyboardKey::KEY_F8) {
+            next_rom = library.playlist.pop_front();
+        }
+        if rl.is_file_dropped() {
+            next_rom = rl.get_dropped_files().into_iter().next();
+            rl.clear_dropped_files();
+        }
+        if let Some(watcher) = watcher.as_mut() {
+            if watcher.changed() {
+                next_rom = Some(watcher.path.clone());
+            }
+        }
+        if let Some(filename) = next_rom {
+            let mut next_chip8 = new_chip8(&args, quirks_for(&args, None));
+            match load_rom_from_file(&mut next_chip8, &filename) {
+                Ok(rom) => {
+                    let known = known_rom(&args, &database, &rom);
+                    next_chip8.quirks = quirks_for(&args, known);
+                    if let (None, Some(speed)) =
+                        (args.target_cycles_per_second, known.and_then(RomInfo::cycles_per_second))
+                    {
+                        original_target_cycles_per_second = speed;
+                        target_cycles_per_second = speed;
+                        instruction_period = instruction_period_for(speed);
+                    }
+                    key_bindings = known.map(RomInfo::key_bindings).unwrap_or_default();
+                    if let (true, Some(hash)) = (args.save_flags, &rom_hash) {
+                        flags::save(&chip8, hash);
+                    }
+                    // Breakpoints and watches set while running, e.g. over gdb
+                    // or the remote API, carry over to the new machine.
+                    next_chip8.breakpoints = std::mem::take(&mut chip8.breakpoints);
+                    next_chip8.breakpoint_conditions = std::mem::take(&mut chip8.breakpoint_conditions);
+                    next_chip8.watchpoints = std::mem::take(&mut chip8.watchpoints);
+                    next_chip8.watched_registers = std::mem::take(&mut chip8.watched_registers);
+                    chip8 = next_chip8;
+                    cheats = load_cheats(args.cheats.as_deref(), &filename, &rom);
+                    debug_info = load_debug_info(&args, &filename, &rom);
+                    profiler = profile::Profiler::new();
+                    call_timeline = calls::CallTimeline::new();
+                    if let Err(error) = add_breakpoints(&mut chip8, &args.breakpoints, &debug_info) {
+                        tracing::warn!(target: "frontend", "{}", error);
+                    }
+                    rom_hash = Some(chip8::romdb::rom_hash(&rom));
+                    if let (true, Some(hash)) = (args.save_flags, &rom_hash) {
+                        flags::load(&mut chip8, hash);
+                    }
+                    rom_path = Some(filename.clone());
+                    crash_reported = false;
+                    resume_offered = false;
+                    if let Some(recorder) = recorder.take() {
+                        recorder.finish();
+                    }
+                    message = Some((i18n::trf("rom_loaded", &[&filename]), std::time::Instant::now()));
+                    recent::record(&filename);
+                    show_library = false;
+                    if args.watch && watcher.as_ref().is_none_or(|watcher| watcher.path != filename) {
+                        watcher = Some(watch::RomWatcher::new(&filename));
+                    }
+                }
+                Err(error) => {
+                    tracing::error!(target: "frontend", "Unable to load {}: {}", filename, error);
+                    message = Some((
+                        i18n::trf("rom_load_failed", &[&filename, &error]),
+                        std::time::Instant::now(),
+                    ));
+                }
+            }
+        }
+
+        if pause_pressed(&rl) {
+            paused = !paused;
+        }
+
+        if let Some(server) = gdb.as_mut() {
+            match server.poll(&mut chip8) {
+                Some(gdb::Control::Pause) => paused = true,
+                Some(gdb::Control::Resume) => paused = false,
+                None => {}
+            }
+        }
+        if let Some(server) = remote.as_mut() {
+            server.poll(&mut chip8, &mut paused, rom_hash.as_deref());
+        }
+
+        // While paused F11 runs a single instruction, F10 steps over calls,
+        // resuming until they return, and N runs one 60Hz frame.
+        let mut stepping = false;
+        let mut advancing = false;
+        if paused && !show_library && player.is_none() {
+            advancing = rl.is_key_pressed(KeyboardKey::KEY_N);
+            if rl.is_key_pressed(KeyboardKey::KEY_F10) && step_over(&mut chip8) {
+                paused = false;
+            } else if rl.is_key_pressed(KeyboardKey::KEY_F10) || rl.is_key_pressed(KeyboardKey::KEY_F11) {
+                stepping = true;
+            }
+        }
+
+        if paused {
+            for (cheat, key) in cheats.iter_mut().zip(CHEAT_KEYS) {
+                if rl.is_key_pressed(key) {
+                    cheat.toggle();
+                }
+            }
+        }
+
         if rl.is_key_pressed(KeyboardKey::KEY_COMMA) {
             target_cycles_per_second -= 100;
             if target_cycles_per_second < 100 {
                 target_cycles_per_second = 100;
             }
-            sleep_duration =
-                std::time::Duration::from_millis(1000 / target_cycles_per_second as u64);
+            instruction_period = instruction_period_for(target_cycles_per_second);
         }
 
         if rl.is_key_pressed(KeyboardKey::KEY_PERIOD) {
             target_cycles_per_second = original_target_cycles_per_second;
-            sleep_duration =
-                std::time::Duration::from_millis(1000 / target_cycles_per_second as u64);
+            instruction_period = instruction_period_for(target_cycles_per_second);
         }
 
         if rl.is_key_pressed(KeyboardKey::KEY_SLASH) {
             target_cycles_per_second += 100;
-            sleep_duration =
-                std::time::Duration::from_millis(1000 / target_cycles_per_second as u64);
+            instruction_period = instruction_period_for(target_cycles_per_second);
         }
 
         if rl.is_key_pressed(KeyboardKey::KEY_LEFT_BRACKET) {
             current_color_index -= 1;
             if current_color_index < 0 {
-                current_color_index = colors.len() as i32 - 1;
+                current_color_index = COLORS.len() as i32 - 1;
             }
         }
 
         if rl.is_key_pressed(KeyboardKey::KEY_RIGHT_BRACKET) {
             current_color_index += 1;
-            if current_color_index >= colors.len() as i32 {
+            if current_color_index >= COLORS.len() as i32 {
                 current_color_index = 0;
             }
         }
 
-        // emulate cycle
-        handle_keypads(&mut chip8, &rl);
-        let opcode = fetch_opcode(&mut chip8);
-        execute_opcode(opcode, &mut chip8);
+        // emulate the instructions and timer ticks that fell due since the last iteration
+        let mut cycle_limit_reached = false;
+        let mut stopped_unattended = false;
+        if (!paused || stepping || advancing) && !show_library {
+            if advancing {
+                instruction_accumulator += TIMER_PERIOD;
+                timer_accumulator += TIMER_PERIOD;
+            } else if !stepping {
+                instruction_accumulator += delta;
+                timer_accumulator += delta;
+            }
+
+            let mut keys = read_keypad(&rl) | read_bound_keys(&rl, &key_bindings);
+            cheats::apply_cheats(&mut chip8, &mut cheats);
+            if let Some(script) = script.as_mut() {
+                script.frame(&mut chip8);
+                keys |= script.held_keys();
+            }
+            // A replay decides the keys and how far each frame runs instead of
+            // the keyboard and the clock.
+            let playback = player.as_mut().and_then(replay::Player::next_frame);
+            if let Some((_, recorded)) = playback {
+                keys = recorded.keys;
+                instruction_accumulator = std::time::Duration::ZERO;
+                timer_accumulator = std::time::Duration::ZERO;
+            }
+            set_keypad(&mut chip8, keys);
+
+            let mut frame = replay::Frame {
+                keys,
+                instructions: 0,
+                timer_ticks: 0,
+                checksum: 0,
+            };
+            while !cycle_limit_reached {
+                if let Some((_, recorded)) = playback {
+                    if frame.instructions == recorded.instructions || chip8.vblank_wait {
+                        break;
+                    }
+                } else if stepping {
+                    if frame.instructions == 1 {
+                        break;
+                    }
+                    // A step waiting for the display runs the timer tick that
+                    // ends the wait, and steps straight off a breakpoint.
+                    if chip8.vblank_wait {
+                        update_timers(&mut chip8);
+                        frame.timer_ticks += 1;
+                    }
+                    chip8.breakpoint_hit = Some(chip8.pc);
+                } else {
+                    let cost = if args.vip_timing {
+                        timing::vip_duration(peek_opcode(&chip8))
+                    } else {
+                        instruction_period
+                    };
+                    if instruction_accumulator < cost {
+                        break;
+                    }
+                    instruction_accumulator -= cost;
+                    if chip8.vblank_wait {
+                        continue;
+                    }
+                }
+                if check_breakpoint(&mut chip8) {
+                    break;
+                }
+                let pc = chip8.pc;
+                let opcode = fetch_opcode(&mut chip8);
+                if let Some(script) = script.as_mut() {
+                    script.instruction(&mut chip8, pc, opcode);
+                }
+                run_stats.record(opcode);
+                profiler.record(pc, opcode);
+                if let Some(writer) = tracer.as_mut() {
+                    if let Err(error) = writer.record(&chip8, pc, opcode, &debug_info.symbols) {
+                        tracing::error!(target: "frontend", "Stopped tracing: {}", error);
+                        tracer = None;
+                    }
+                }
+                execute_opcode(opcode, &mut chip8);
+                cheats::apply_cheats(&mut chip8, &mut cheats);
+                call_timeline.record(chip8.stack.len());
+                frame.instructions += 1;
+                if args.strict && chip8.fault.is_some() || chip8.stop.is_some() {
+                    break;
+                }
+
+                cycles += 1;
+                cycles_executed += 1;
+                cycle_limit_reached = args.max_cycles.is_some_and(|max| cycles_executed >= max);
+            }
+            call_timeline.end_frame();
+
+            if let Some((_, recorded)) = playback {
+                for _ in 0..recorded.timer_ticks {
+                    update_timers(&mut chip8);
+                }
+                frame.timer_ticks = recorded.timer_ticks;
+            }
+            while timer_accumulator >= TIMER_PERIOD {
+                timer_accumulator -= TIMER_PERIOD;
+                update_timers(&mut chip8);
+                frame.timer_ticks += 1;
+            }
+            frame.checksum = state_hash(&chip8);
+            if let Some(recorder) = recorder.as_mut() {
+                recorder.record(frame);
+            }
+            if let Some((number, recorded)) = playback {
+                if frame != recorded {
+                    println!(
+                        "Replay diverged at frame {}: recorded {:?}, got {:?}",
+                        number, recorded, frame
+                    );
+                    exit_status = exit::ExitStatus::ReplayDiverged;
+                    replay_finished = true;
+                }
+            } else if player.take().is_some() {
+                println!("Replay finished, all frames matched");
+                replay_finished = true;
+            }
+            for event in take_events(&mut chip8) {
+                tracing::debug!(target: "audio", ?event, "sound");
+            }
+            if let Some(stop) = take_stop(&mut chip8) {
+                tracing::info!(target: "frontend", "{}", stop);
+                // An unattended run would never finish while paused, so it
+                // ends with its own status instead.
+                if unattended {
+                    println!("{}", stop);
+                    exit_status = exit::ExitStatus::Stopped;
+                    stopped_unattended = true;
+                } else {
+                    paused = true;
+                }
+                if let Stop::UnknownOpcode { .. } = stop {
+                    // Usually a jump into data, which the disassembly shows.
+                    draw_debug_registers_info = true;
+                    draw_disassembly = true;
+                }
+                if let Some(server) = gdb.as_mut() {
+                    server.stopped(gdb::SIGTRAP);
+                }
+                message = Some((i18n::trf("stopped", &[&stop]), std::time::Instant::now()));
+            }
+
+            if let (Some(fault), false) = (chip8.fault, crash_reported) {
+                crash_reported = true;
+                // Stop on the fault so its state can be inspected, unless the
+                // run is unattended and would never finish while paused.
+                paused = !unattended;
+                if let Some(server) = gdb.as_mut() {
+                    server.stopped(gdb::SIGILL);
+                }
+                match crash::write_bundle(&chip8, rom_hash.as_deref(), &debug_info.symbols) {
+                    Ok(dir) => {
+                        tracing::error!(target: "core", "{}; crash report saved to {}", fault, dir.display());
+                        message = Some((
+                            i18n::trf("crash_report", &[&fault, &dir.display()]),
+                            std::time::Instant::now(),
+                        ));
+                    }
+                    Err(error) => tracing::error!(target: "frontend", "Unable to write crash report: {}", error),
+                }
+            }
+        }
+        if let Some(server) = remote.as_mut() {
+            server.frame(&chip8);
+        }
         //render
         let mut d = rl.begin_drawing(&thread);
         d.clear_background(Color::BLACK);
-        if draw_emulator {
-            handle_draw_emulator(&mut d, &chip8, pixel_size, colors[current_color_index as usize]);
-        }
-
-        handle_draw_debug(
-            &mut d,
-            &chip8,
-            draw_debug_cycles_info,
-            draw_debug_registers_info,
-            screen_width,
-            cycles_per_second,
-            total_cycles,
-        );
+        if show_library {
+            library::draw(&mut d, &library, screen_height, COLORS[current_color_index as usize]);
+        } else {
+            if draw_emulator {
+                handle_draw_emulator(&mut d, &chip8, pixel_size, COLORS[current_color_index as usize]);
+            }
 
-        play_beep(&mut chip8);
+            handle_draw_debug(
+                &mut d,
+                &chip8,
+                draw_debug_cycles_info,
+                draw_debug_registers_info,
+                screen_width,
+                cycles_per_second,
+                total_cycles,
+            );
+            if draw_disassembly {
+                handle_draw_disassembly(&mut d, &chip8, &debug_info.symbols, screen_width);
+            }
+            if let (true, Some(source_map)) = (draw_debug_registers_info, &debug_info.source_map) {
+                handle_draw_source_line(&mut d, &chip8, source_map, screen_width);
+            }
+            if draw_debug_registers_info {
+                handle_draw_watches(&mut d, &chip8, &debug_info.watches, screen_width);
+            }
+            if draw_keypad {
+                let color = COLORS[current_color_index as usize];
+                handle_draw_keypad(&mut d, &chip8, screen_width, screen_height, color);
+            }
+            if draw_profile {
+                handle_draw_profile(&mut d, &chip8, &profiler, &debug_info.symbols);
+            }
+            if draw_register_history {
+                handle_draw_register_history(&mut d, &chip8);
+            }
+            if draw_calls {
+                handle_draw_calls(&mut d, &chip8, &call_timeline, &debug_info.symbols, screen_width);
+            }
+            if let (true, Some(heatmap)) = (draw_heatmap, &chip8.heatmap) {
+                handle_draw_heatmap(&mut d, heatmap, screen_width, screen_height);
+            }
 
-        if chip8.timer_delay > 0 {
-            chip8.timer_delay -= 1;
+            if let Some(script) = &script {
+                for (line, text) in script.hud().iter().enumerate() {
+                    d.draw_text(text, 10, 60 + line as i32 * 20, 20, Color::WHITE);
+                }
+            }
         }
 
-        if chip8.timer_sound > 0 {
-            chip8.timer_sound -= 1;
+        if let Some((text, shown_at)) = &message {
+            if shown_at.elapsed().as_secs() < 5 {
+                d.draw_text(text, 10, screen_height - 55, 20, Color::WHITE);
+            }
         }
 
-        cycles += 1;
-        std::thread::sleep(sleep_duration);
+        if show_library {
+            // emulation is suspended while browsing
+        } else if paused {
+            d.draw_text(i18n::tr("paused"), 10, screen_height - 30, 20, Color::WHITE);
+            if draw_debug_registers_info {
+                let opcode = peek_opcode(&chip8);
+                let pc = format!("{:#05X}", chip8.pc);
+                let instruction = format!("{:04X} {}", opcode, decode(opcode));
+                d.draw_text(
+                    &i18n::trf("next_instruction", &[&pc, &instruction]),
+                    10,
+                    screen_height - 80,
+                    20,
+                    Color::WHITE,
+                );
+            }
+            draw_cheats(&mut d, &cheats, screen_height);
+        } else {
+            frames_rendered += 1;
+
+            let display_changed = take_display_dirty(&mut chip8);
+            if let Some(recording) = capture.as_mut() {
+                let Color { r, g, b, .. } = COLORS[current_color_index as usize];
+                if let Err(error) = recording.frame(&chip8, [r, g, b], display_changed) {
+                    tracing::error!(target: "frontend", "Stopped capturing: {}", error);
+                    capture.take().unwrap().finish();
+                }
+            }
+
+            if let Some(every) = args.print_display_hash_every {
+                if every > 0 && frames_rendered.is_multiple_of(every) {
+                    println!("frame {} display hash {:016x}", frames_rendered, display_hash(&chip8));
+                }
+            }
+        }
+
+        // Run limits apply whether or not the machine is paused, e.g. when a
+        // stop paused it in the same frame the cycle limit was reached.
+        let frame_limit_reached = args.max_frames.is_some_and(|max| frames_rendered >= max);
+        let halted = args.exit_on_halt && is_halted(&chip8);
+        let faulted = (args.exit_on_fault || args.strict) && chip8.fault.is_some();
+        let timed_out = args.timeout.is_some_and(|seconds| started.elapsed().as_secs() >= seconds);
+        if timed_out && !halted && !stopped_unattended {
+            exit_status = exit::ExitStatus::Timeout;
+        }
+        if frame_limit_reached
+            || cycle_limit_reached
+            || halted
+            || faulted
+            || timed_out
+            || replay_finished
+            || stopped_unattended
+        {
+            if args.dump_state || (args.strict && faulted) {
+                print_state(&chip8);
+            }
+            break;
+        }
+
+        // A ROM that exits with 00FD goes back to the library, or closes the
+        // window when there is no library to go back to.
+        if !show_library && !paused && has_exited(&chip8) {
+            if library.entries.is_empty() {
+                break;
+            }
+            show_library = true;
+            message = Some((i18n::tr("rom_exited").to_string(), std::time::Instant::now()));
+        }
+
+        // Render at a fixed frame rate; each frame runs the batch of
+        // instructions that fell due since the previous one.
+        pacing::sleep(FRAME_PERIOD.saturating_sub(last_tick.elapsed()));
+    }
+
+    if args.print_display_hash_every.is_some() {
+        println!("exit display hash {:016x}", display_hash(&chip8));
+    }
+
+    if let Some(recorder) = recorder {
+        recorder.finish();
+    }
+
+    if let Some(capture) = capture {
+        capture.finish();
+    }
+
+    if let Some(Err(error)) = tracer.map(trace::Tracer::finish) {
+        tracing::error!(target: "frontend", "Unable to finish the trace: {}", error);
+    }
+
+    if let Some(path) = &args.dump_state_on_exit {
+        if let Err(error) = dump::write(path, &chip8, rom_hash.as_deref()) {
+            tracing::error!(target: "frontend", "Unable to write state to {}: {}", path.display(), error);
+        }
+    }
+
+    if let Some(path) = &args.stats {
+        if let Err(error) = run_stats.write(path, frames_rendered, &chip8) {
+            tracing::error!(target: "frontend", "Unable to write statistics to {}: {}", path, error);
+        }
+    }
+
+    if let Some(path) = &args.profile_out {
+        if let Err(error) = profiler.write(path, &chip8, &debug_info.symbols) {
+            tracing::error!(target: "frontend", "Unable to write the profile to {}: {}", path, error);
+        }
+    }
+
+    if let (Some(path), Some(heatmap)) = (&args.heatmap_out, &chip8.heatmap) {
+        if let Err(error) = image::write_heatmap_png(std::path::Path::new(path), heatmap) {
+            tracing::error!(target: "frontend", "Unable to write the heatmap to {}: {}", path, error);
+        }
+    }
+
+    if let Some(path) = &args.coverage_out {
+        if let Err(error) = coverage::write(path, &chip8, &debug_info.symbols) {
+            tracing::error!(target: "frontend", "Unable to write the coverage report to {}: {}", path, error);
+        }
+    }
+
+    if let (true, Some(hash)) = (args.save_flags, &rom_hash) {
+        flags::save(&chip8, hash);
+    }
+    // A ROM that crashed or finished has nothing worth resuming.
+    if let (false, Some(hash)) = (unattended || chip8.fault.is_some() || has_exited(&chip8), &rom_hash) {
+        states::save_resume(&chip8, hash);
+    }
+
+    settings.cycles_per_second = target_cycles_per_second;
+    settings.color = current_color_index as usize;
+    settings.show_cycles_info = draw_debug_cycles_info;
+    settings.show_registers = draw_debug_registers_info;
+    settings.show_display = draw_emulator;
+    settings.show_disassembly = draw_disassembly;
+    settings.show_keypad = draw_keypad;
+    settings.show_profile = draw_profile;
+    settings.show_heatmap = draw_heatmap;
+    settings.show_calls = draw_calls;
+    settings.show_register_history = draw_register_history;
+    settings.window_width = rl.get_screen_width();
+    settings.window_height = rl.get_screen_height();
+    settings::save(&settings);
+
+    if chip8.fault.is_some() {
+        exit_status = exit::ExitStatus::Fault;
+    }
+    if exit_status != exit::ExitStatus::Success {
+        exit_status.exit();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROM: &[u8] = &[0x00, 0xE0, 0x12, 0x00];
+
+    fn database() -> RomDatabase {
+        let mut database = RomDatabase::default();
+        let entry = format!(
+            "[[rom]]\nsha1 = \"{}\"\ntitle = \"Car\"\nplatform = \"schip\"\nquirks = [\"vf_reset\", \"memory_increment_none\"]\ntickrate = 30\n",
+            chip8::romdb::rom_hash(ROM)
+        );
+        database.add_overlay(&entry).unwrap();
+        database
+    }
+
+    #[test]
+    fn known_hash_selects_quirks_and_tickrate() {
+        let database = database();
+        let args = ChipCliArgs::parse_from(["chip8"]);
+        let known = known_rom(&args, &database, ROM).expect("the ROM is in the database");
+        let mut expected = Quirks::schip();
+        expected.vf_reset = true;
+        expected.memory_increment_i = MemoryIncrement::None;
+        assert_eq!(quirks_for(&args, Some(known)), expected);
+        assert_eq!(known.cycles_per_second(), Some(1800));
+    }
+
+    #[test]
+    fn unknown_hash_keeps_defaults() {
+        let database = database();
+        let args = ChipCliArgs::parse_from(["chip8"]);
+        assert!(known_rom(&args, &database, &[0x12, 0x00]).is_none());
+        assert_eq!(quirks_for(&args, None), Quirks::default());
+    }
+
+    #[test]
+    fn quirk_flags_and_no_rom_db_override_the_database() {
+        let database = database();
+        let args = ChipCliArgs::parse_from(["chip8", "--no-rom-db"]);
+        assert!(known_rom(&args, &database, ROM).is_none());
+        let args = ChipCliArgs::parse_from(["chip8", "--quirks", "xochip"]);
+        let known = known_rom(&args, &database, ROM);
+        assert_eq!(quirks_for(&args, known), Quirks::xochip());
     }
 }
@@ -0,0 +1,49 @@
+use std::time::{Duration, Instant};
+
+/// `thread::sleep` can wake a little late, so `sleep` only sleeps until this
+/// much time is left and spins for the rest. Windows' ~15ms default tick is
+/// lowered to 1ms first, see `raise_timer_resolution`.
+#[cfg(windows)]
+const SPIN_MARGIN: Duration = Duration::from_millis(2);
+#[cfg(not(windows))]
+const SPIN_MARGIN: Duration = Duration::from_millis(1);
+
+#[cfg(windows)]
+#[link(name = "winmm")]
+extern "system" {
+    fn timeBeginPeriod(period: u32) -> u32;
+}
+
+/// Asks Windows for 1ms timer resolution for the rest of the process, so
+/// sleeps end within about a millisecond instead of a whole scheduler tick.
+#[cfg(windows)]
+fn raise_timer_resolution() {
+    static RAISED: std::sync::Once = std::sync::Once::new();
+    // SAFETY: timeBeginPeriod takes a plain integer and has no preconditions.
+    RAISED.call_once(|| unsafe {
+        timeBeginPeriod(1);
+    });
+}
+
+#[cfg(not(windows))]
+fn raise_timer_resolution() {}
+
+/// Sleeps for `duration` with sub-millisecond accuracy: most of the interval
+/// is slept, the remainder is spun.
+pub fn sleep(duration: Duration) {
+    raise_timer_resolution();
+    let deadline = Instant::now() + duration;
+    if duration > SPIN_MARGIN {
+        std::thread::sleep(duration - SPIN_MARGIN);
+    }
+    while Instant::now() < deadline {
+        std::hint::spin_loop();
+    }
+}
+
+/// Sleeps for about `duration`, possibly a millisecond or so longer, without
+/// spinning; for loops that measure the time that actually passed.
+pub fn sleep_coarse(duration: Duration) {
+    raise_timer_resolution();
+    std::thread::sleep(duration);
+}
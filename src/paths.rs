@@ -0,0 +1,16 @@
+use std::path::PathBuf;
+
+/// Per-user directory for chip8 configuration, e.g. `~/.config/chip8`.
+pub fn config_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("chip8"))
+}
+
+/// Per-user directory for data worth keeping, e.g. `~/.local/share/chip8`.
+pub fn data_dir() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("chip8"))
+}
+
+/// Per-user directory for downloaded and generated files, e.g. `~/.cache/chip8`.
+pub fn cache_dir() -> Option<PathBuf> {
+    dirs::cache_dir().map(|dir| dir.join("chip8"))
+}
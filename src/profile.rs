@@ -0,0 +1,128 @@
+//! Where a ROM spends its cycles: executions per opcode type, per address
+//! and per loop, for `--profile-out` and the `H` overlay.
+
+use std::collections::{BTreeMap, HashMap};
+
+use chip8::chip8::{decode, read_opcode, Chip8, Instruction};
+use chip8::disasm::instruction_text;
+use chip8::symbols::Symbols;
+
+pub struct Profiler {
+    instructions: u64,
+    opcodes: BTreeMap<&'static str, u64>,
+    /// Executions of the instruction at each address.
+    addresses: Vec<u64>,
+    /// Backward `1nnn` jumps taken, by the loop's first and last address.
+    loops: HashMap<(u16, u16), u64>,
+}
+
+/// An address and how often the instruction there ran.
+pub struct HotSpot {
+    pub address: u16,
+    pub count: u64,
+}
+
+impl Profiler {
+    pub fn new() -> Profiler {
+        Profiler {
+            instructions: 0,
+            opcodes: BTreeMap::new(),
+            addresses: vec![0; 0x10000],
+            loops: HashMap::new(),
+        }
+    }
+
+    pub fn record(&mut self, pc: u16, opcode: u16) {
+        self.instructions += 1;
+        self.addresses[pc as usize] += 1;
+        let instruction = decode(opcode);
+        if let Some(pattern) = instruction.pattern() {
+            *self.opcodes.entry(pattern).or_insert(0) += 1;
+        }
+        if let Instruction::Jump { nnn } = instruction {
+            if nnn <= pc {
+                *self.loops.entry((nnn, pc)).or_insert(0) += 1;
+            }
+        }
+    }
+
+    /// The instruction at `address` as the overlay and report show it.
+    pub fn instruction(chip8: &Chip8, address: u16, symbols: &Symbols) -> String {
+        instruction_text(decode(read_opcode(chip8, address)), symbols)
+    }
+
+    /// The `count` addresses that ran most, most first.
+    pub fn hot_spots(&self, count: usize) -> Vec<HotSpot> {
+        let mut spots: Vec<HotSpot> = (0..self.addresses.len())
+            .filter(|&address| self.addresses[address] > 0)
+            .map(|address| HotSpot {
+                address: address as u16,
+                count: self.addresses[address],
+            })
+            .collect();
+        spots.sort_by(|a, b| b.count.cmp(&a.count).then(a.address.cmp(&b.address)));
+        spots.truncate(count);
+        spots
+    }
+
+    /// Percentage of all instructions run that `count` is.
+    pub fn share(&self, count: u64) -> f64 {
+        match self.instructions {
+            0 => 0.0,
+            total => count as f64 * 100.0 / total as f64,
+        }
+    }
+
+    /// The report as JSON: opcode type counts, every address run with its
+    /// instruction, and the loops closed by a backward jump with the
+    /// instructions run inside them, the hottest first.
+    pub fn report(&self, chip8: &Chip8, symbols: &Symbols) -> serde_json::Value {
+        let addresses: Vec<serde_json::Value> = self
+            .hot_spots(usize::MAX)
+            .iter()
+            .map(|spot| {
+                serde_json::json!({
+                    "address": spot.address,
+                    "label": symbols.name(spot.address),
+                    "instruction": Profiler::instruction(chip8, spot.address, symbols),
+                    "count": spot.count,
+                    "share": self.share(spot.count),
+                })
+            })
+            .collect();
+        let mut loops: Vec<(u16, u16, u64, u64)> = self
+            .loops
+            .iter()
+            .map(|(&(start, end), &iterations)| {
+                let inside = self.addresses[start as usize..=end as usize].iter().sum();
+                (start, end, iterations, inside)
+            })
+            .collect();
+        loops.sort_by(|a, b| b.3.cmp(&a.3).then(a.0.cmp(&b.0)));
+        let loops: Vec<serde_json::Value> = loops
+            .iter()
+            .map(|&(start, end, iterations, inside)| {
+                serde_json::json!({
+                    "start": start,
+                    "end": end,
+                    "label": symbols.name(start),
+                    "iterations": iterations,
+                    "instructions": inside,
+                    "share": self.share(inside),
+                })
+            })
+            .collect();
+        serde_json::json!({
+            "instructions": self.instructions,
+            "opcodes": self.opcodes,
+            "addresses": addresses,
+            "loops": loops,
+        })
+    }
+
+    /// Writes the report as pretty-printed JSON to `path`.
+    pub fn write(&self, path: &str, chip8: &Chip8, symbols: &Symbols) -> std::io::Result<()> {
+        let json = serde_json::to_string_pretty(&self.report(chip8, symbols)).map_err(std::io::Error::other)?;
+        std::fs::write(path, json + "\n")
+    }
+}
@@ -0,0 +1,44 @@
+use std::path::PathBuf;
+
+use crate::paths;
+
+const MAX_RECENT_ROMS: usize = 10;
+
+fn recent_file() -> Option<PathBuf> {
+    paths::config_dir().map(|dir| dir.join("recent.txt"))
+}
+
+/// Most recently opened ROMs, newest first.
+pub fn load() -> Vec<String> {
+    let Some(text) = recent_file().and_then(|path| std::fs::read_to_string(path).ok()) else {
+        return Vec::new();
+    };
+    text.lines()
+        .filter(|line| !line.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
+/// Moves `filename` to the front of the recent list and saves it.
+pub fn record(filename: &str) {
+    let Some(path) = recent_file() else {
+        return;
+    };
+    if filename == "-" {
+        return;
+    }
+    let filename = std::fs::canonicalize(filename)
+        .map(|path| path.to_string_lossy().into_owned())
+        .unwrap_or_else(|_| filename.to_owned());
+
+    let mut recent = load();
+    recent.retain(|entry| *entry != filename);
+    recent.insert(0, filename);
+    recent.truncate(MAX_RECENT_ROMS);
+
+    let result = std::fs::create_dir_all(path.parent().unwrap())
+        .and_then(|_| std::fs::write(&path, recent.join("\n") + "\n"));
+    if let Err(error) = result {
+        tracing::warn!(target: "frontend", "Unable to save recent ROMs to {}: {}", path.display(), error);
+    }
+}
@@ -0,0 +1,361 @@
+//! `--remote PORT`: an HTTP API for controlling the running machine from
+//! scripts, test harnesses or a web dashboard, and a WebSocket stream of the
+//! display.
+//!
+//! The HTTP server runs on its own thread and hands each request to the
+//! window's run loop, which answers them once a frame from `poll`, so
+//! requests never see the machine halfway through an instruction.
+//!
+//! - `POST /pause`, `/resume`, `/step` and `/reset` control the machine and
+//!   answer with the registers.
+//! - `GET /registers` answers with PC, I, V0-VF, the stack, the timers and
+//!   whether the machine is paused.
+//! - `GET /memory?address=200&length=64` answers with memory as hex, the
+//!   address in hex like `--break`, the length in bytes.
+//! - `POST /memory?address=2A0&data=0905` writes the hex bytes in `data`
+//!   there, and `POST /registers?V3=09&I=300` sets registers to hex values,
+//!   e.g. to try out what a ROM does with 9 lives.
+//! - `GET /state` answers with everything `--dump-state-on-exit` writes.
+//! - `GET /display` upgraded to a WebSocket receives a binary message for
+//!   every frame the display changes: the width and height as little-endian
+//!   u16s, then one byte per pixel, row by row.
+//!
+//! Listening on 127.0.0.1 keeps other machines out, but a web page in the
+//! user's browser can still send requests there, so requests must name
+//! localhost as their Host and, when they have one, their Origin.
+
+use std::net::Ipv4Addr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender, SyncSender, TryRecvError, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chip8::chip8::*;
+use tiny_http::{Header, Method, Request, Response, Server};
+use tungstenite::handshake::derive_accept_key;
+use tungstenite::protocol::Role;
+use tungstenite::{Message, WebSocket};
+
+/// How long the HTTP thread waits for the run loop, which only stops
+/// answering while the window is not being drawn.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Frames queued for a slow WebSocket client before newer ones are dropped.
+const FRAME_QUEUE: usize = 4;
+
+enum Command {
+    Pause,
+    Resume,
+    Step,
+    Reset,
+    Registers,
+    Memory { address: u16, length: u32 },
+    WriteMemory { address: u16, bytes: Vec<u8> },
+    WriteRegisters(Vec<(Register, u32)>),
+    State,
+}
+
+/// An HTTP status and a JSON body.
+type Reply = (u16, serde_json::Value);
+
+struct Subscribers {
+    clients: Mutex<Vec<SyncSender<Arc<Vec<u8>>>>>,
+    /// Set when a client connects, so it is sent the display straight away.
+    joined: AtomicBool,
+}
+
+pub struct RemoteServer {
+    requests: Receiver<(Command, Sender<Reply>)>,
+    subscribers: Arc<Subscribers>,
+    /// The last frame sent to WebSocket clients.
+    last_frame: Vec<u8>,
+}
+
+impl RemoteServer {
+    /// Listens on `port` of 127.0.0.1 only, as the API can rewrite memory.
+    pub fn bind(port: u16) -> std::io::Result<RemoteServer> {
+        let server = Server::http((Ipv4Addr::LOCALHOST, port)).map_err(std::io::Error::other)?;
+        let (sender, requests) = mpsc::channel();
+        let subscribers = Arc::new(Subscribers {
+            clients: Mutex::new(Vec::new()),
+            joined: AtomicBool::new(false),
+        });
+        let shared = subscribers.clone();
+        std::thread::spawn(move || serve(server, sender, shared));
+        Ok(RemoteServer {
+            requests,
+            subscribers,
+            last_frame: Vec::new(),
+        })
+    }
+
+    /// Answers the requests that came in since the last frame.
+    pub fn poll(&mut self, chip8: &mut Chip8, paused: &mut bool, rom_hash: Option<&str>) {
+        loop {
+            let (command, reply) = match self.requests.try_recv() {
+                Ok(request) => request,
+                Err(TryRecvError::Empty | TryRecvError::Disconnected) => return,
+            };
+            let answer = match command {
+                Command::Pause => {
+                    *paused = true;
+                    (200, registers(chip8, *paused))
+                }
+                Command::Resume => {
+                    *paused = false;
+                    (200, registers(chip8, *paused))
+                }
+                Command::Step => {
+                    *paused = true;
+                    step_into(chip8);
+                    (200, registers(chip8, *paused))
+                }
+                Command::Reset => {
+                    reset(chip8);
+                    (200, registers(chip8, *paused))
+                }
+                Command::Registers => (200, registers(chip8, *paused)),
+                Command::Memory { address, length } => match chip8.memory.get(address as usize..address as usize + length as usize) {
+                    Some(bytes) => (
+                        200,
+                        serde_json::json!({
+                            "address": address,
+                            "data": bytes.iter().map(|byte| format!("{:02x}", byte)).collect::<String>(),
+                        }),
+                    ),
+                    None => (400, error(&format!("{:#05X}+{} is outside memory", address, length))),
+                },
+                Command::WriteMemory { address, bytes } if address as usize + bytes.len() > chip8.memory.len() => {
+                    (400, error(&format!("{:#05X}+{} is outside memory", address, bytes.len())))
+                }
+                Command::WriteMemory { address, bytes } => {
+                    write_memory(chip8, address, &bytes);
+                    (
+                        200,
+                        serde_json::json!({
+                            "address": address,
+                            "data": bytes.iter().map(|byte| format!("{:02x}", byte)).collect::<String>(),
+                        }),
+                    )
+                }
+                Command::WriteRegisters(values) => {
+                    for (register, value) in values {
+                        register.set(chip8, value);
+                    }
+                    (200, registers(chip8, *paused))
+                }
+                Command::State => {
+                    let mut state = crate::dump::state_json(chip8, rom_hash);
+                    state["paused"] = (*paused).into();
+                    (200, state)
+                }
+            };
+            let _ = reply.send(answer);
+        }
+    }
+
+    /// Sends the display to WebSocket clients if it changed since the last
+    /// frame sent, or if a client just connected.
+    pub fn frame(&mut self, chip8: &Chip8) {
+        let joined = self.subscribers.joined.swap(false, Ordering::Relaxed);
+        let mut frame = Vec::with_capacity(4 + chip8.display.len());
+        frame.extend_from_slice(&(chip8.display_width as u16).to_le_bytes());
+        frame.extend_from_slice(&(chip8.display_height as u16).to_le_bytes());
+        frame.extend_from_slice(&chip8.display);
+        if !joined && frame == self.last_frame {
+            return;
+        }
+        let shared = Arc::new(frame);
+        let mut clients = self.subscribers.clients.lock().unwrap();
+        clients.retain(|client| !matches!(client.try_send(shared.clone()), Err(TrySendError::Disconnected(_))));
+        self.last_frame = Arc::unwrap_or_clone(shared);
+    }
+}
+
+fn registers(chip8: &Chip8, paused: bool) -> serde_json::Value {
+    serde_json::json!({
+        "paused": paused,
+        "fault": chip8.fault.map(|fault| fault.to_string()),
+        "pc": chip8.pc,
+        "i": chip8.i,
+        "v": chip8.v,
+        "sp": chip8.stack.len(),
+        "stack": chip8.stack,
+        "delay_timer": chip8.timer_delay,
+        "sound_timer": chip8.timer_sound,
+    })
+}
+
+fn error(message: &str) -> serde_json::Value {
+    serde_json::json!({ "error": message })
+}
+
+/// Reads `/memory`'s query string.
+fn memory_command(query: &str) -> Result<Command, String> {
+    let mut address = None;
+    let mut length = None;
+    for pair in query.split('&') {
+        match pair.split_once('=') {
+            Some(("address", value)) => address = Some(crate::parse_address(value)?),
+            Some(("length", value)) => {
+                length = Some(value.parse::<u32>().map_err(|_| format!("invalid length {}", value))?)
+            }
+            _ => {}
+        }
+    }
+    match (address, length) {
+        (Some(address), Some(length)) => Ok(Command::Memory { address, length }),
+        _ => Err("expected ?address=HEX&length=BYTES".to_string()),
+    }
+}
+
+/// Reads `POST /memory`'s query string.
+fn write_memory_command(query: &str) -> Result<Command, String> {
+    let mut address = None;
+    let mut bytes = None;
+    for pair in query.split('&') {
+        match pair.split_once('=') {
+            Some(("address", value)) => address = Some(crate::parse_address(value)?),
+            Some(("data", value)) => {
+                let parsed = (0..value.len())
+                    .step_by(2)
+                    .map(|start| value.get(start..start + 2).and_then(|byte| u8::from_str_radix(byte, 16).ok()))
+                    .collect::<Option<Vec<u8>>>();
+                bytes = Some(parsed.ok_or_else(|| format!("expected hex bytes, got {}", value))?);
+            }
+            _ => {}
+        }
+    }
+    match (address, bytes) {
+        (Some(address), Some(bytes)) => Ok(Command::WriteMemory { address, bytes }),
+        _ => Err("expected ?address=HEX&data=HEXBYTES".to_string()),
+    }
+}
+
+/// Reads `POST /registers`'s query string, e.g. `V3=09&I=300`.
+fn write_registers_command(query: &str) -> Result<Command, String> {
+    let mut values = Vec::new();
+    for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+        let (name, value) = pair.split_once('=').ok_or_else(|| format!("expected REGISTER=HEX, got {}", pair))?;
+        let register: Register = name.parse()?;
+        let value = u32::from_str_radix(value, 16).map_err(|_| format!("expected a hex value, got {}", value))?;
+        if value > register.max() {
+            return Err(format!("{} holds at most {:#X}, got {:#X}", register, register.max(), value));
+        }
+        values.push((register, value));
+    }
+    match values.is_empty() {
+        true => Err("expected ?V0=HEX&I=HEX...".to_string()),
+        false => Ok(Command::WriteRegisters(values)),
+    }
+}
+
+fn command(request: &Request) -> Result<Command, Reply> {
+    let (path, query) = request.url().split_once('?').unwrap_or((request.url(), ""));
+    match (request.method(), path) {
+        (Method::Post, "/pause") => Ok(Command::Pause),
+        (Method::Post, "/resume") => Ok(Command::Resume),
+        (Method::Post, "/step") => Ok(Command::Step),
+        (Method::Post, "/reset") => Ok(Command::Reset),
+        (Method::Get, "/registers") => Ok(Command::Registers),
+        (Method::Get, "/memory") => memory_command(query).map_err(|message| (400, error(&message))),
+        (Method::Post, "/memory") => write_memory_command(query).map_err(|message| (400, error(&message))),
+        (Method::Post, "/registers") => write_registers_command(query).map_err(|message| (400, error(&message))),
+        (Method::Get, "/state") => Ok(Command::State),
+        (_, "/pause" | "/resume" | "/step" | "/reset" | "/registers" | "/memory" | "/state") => {
+            Err((405, error("method not allowed")))
+        }
+        _ => Err((404, error("not found"))),
+    }
+}
+
+fn header(request: &Request, name: &'static str) -> Option<String> {
+    request
+        .headers()
+        .iter()
+        .find(|header| header.field.equiv(name))
+        .map(|header| header.value.to_string())
+}
+
+/// Whether `host`, a Host header or the host of an Origin, names this
+/// machine, with or without a port.
+fn is_local_host(host: &str) -> bool {
+    let name = match host.rsplit_once(':') {
+        Some((name, port)) if port.bytes().all(|byte| byte.is_ascii_digit()) => name,
+        _ => host,
+    };
+    matches!(name, "localhost" | "127.0.0.1" | "[::1]")
+}
+
+/// Rejects requests a page from another site could have made: the Host
+/// must be localhost, against DNS rebinding, and so must the Origin that
+/// browsers send with POSTs and WebSocket upgrades. Scripts that send no
+/// Origin are let through.
+fn check_origin(request: &Request) -> Result<(), Reply> {
+    let host = header(request, "Host").is_some_and(|host| is_local_host(&host));
+    let origin = match header(request, "Origin") {
+        Some(origin) => origin.strip_prefix("http://").is_some_and(is_local_host),
+        None => true,
+    };
+    match host && origin {
+        true => Ok(()),
+        false => Err((403, error("only localhost may use the remote API"))),
+    }
+}
+
+fn serve(server: Server, requests: Sender<(Command, Sender<Reply>)>, subscribers: Arc<Subscribers>) {
+    let json = Header::from_bytes("Content-Type", "application/json").unwrap();
+    for request in server.incoming_requests() {
+        let checked = check_origin(&request);
+        if checked.is_ok() && request.url() == "/display" {
+            subscribe(request, &subscribers);
+            continue;
+        }
+        let (status, body) = match checked.and_then(|()| command(&request)) {
+            Ok(command) => {
+                let (reply, answer) = mpsc::channel();
+                if requests.send((command, reply)).is_err() {
+                    return;
+                }
+                answer
+                    .recv_timeout(REPLY_TIMEOUT)
+                    .unwrap_or_else(|_| (503, error("the emulator is not answering")))
+            }
+            Err(reply) => reply,
+        };
+        let response = Response::from_string(body.to_string())
+            .with_status_code(status)
+            .with_header(json.clone());
+        if let Err(error) = request.respond(response) {
+            tracing::warn!(target: "frontend", "Unable to answer a remote request: {}", error);
+        }
+    }
+}
+
+/// Upgrades a `/display` request to a WebSocket and streams frames to it
+/// from a thread of its own.
+fn subscribe(request: Request, subscribers: &Subscribers) {
+    let Some(key) = header(&request, "Sec-WebSocket-Key") else {
+        let response = Response::from_string("expected a WebSocket upgrade").with_status_code(426);
+        let _ = request.respond(response);
+        return;
+    };
+    let response = Response::empty(101)
+        .with_header(Header::from_bytes("Upgrade", "websocket").unwrap())
+        .with_header(Header::from_bytes("Connection", "Upgrade").unwrap())
+        .with_header(Header::from_bytes("Sec-WebSocket-Accept", derive_accept_key(key.as_bytes())).unwrap());
+    let stream = request.upgrade("websocket", response);
+    let (sender, frames) = mpsc::sync_channel::<Arc<Vec<u8>>>(FRAME_QUEUE);
+    subscribers.clients.lock().unwrap().push(sender);
+    subscribers.joined.store(true, Ordering::Relaxed);
+    std::thread::spawn(move || {
+        let mut socket = WebSocket::from_raw_socket(stream, Role::Server, None);
+        // Ends when the client goes away, which drops `frames` and has the
+        // run loop forget the client on the next frame.
+        for frame in frames {
+            if socket.send(Message::Binary(frame.to_vec())).is_err() {
+                break;
+            }
+        }
+    });
+}
@@ -0,0 +1,80 @@
+use chip8::chip8::Quirks;
+use serde::{Deserialize, Serialize};
+
+/// What happened in one frame of the main loop: the keys held, how many
+/// instructions ran and how many times the 60Hz timers ticked, and the
+/// `state_hash` of the machine afterwards.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Frame {
+    pub keys: u16,
+    pub instructions: u32,
+    pub timer_ticks: u32,
+    pub checksum: u64,
+}
+
+/// Everything needed to re-run a session exactly: with the same ROM, seed
+/// and quirks, feeding the frames back reproduces every instruction.
+/// Cheats, scripts, resets and loading states are not recorded.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Replay {
+    pub rom_sha1: String,
+    pub seed: u64,
+    pub quirks: Quirks,
+    pub frames: Vec<Frame>,
+}
+
+/// Records frames to a replay file, written when the recording stops.
+pub struct Recorder {
+    path: String,
+    replay: Replay,
+}
+
+impl Recorder {
+    pub fn new(path: &str, rom_sha1: &str, seed: u64, quirks: Quirks) -> Recorder {
+        Recorder {
+            path: path.to_owned(),
+            replay: Replay {
+                rom_sha1: rom_sha1.to_owned(),
+                seed,
+                quirks,
+                frames: Vec::new(),
+            },
+        }
+    }
+
+    pub fn record(&mut self, frame: Frame) {
+        self.replay.frames.push(frame);
+    }
+
+    /// Writes the replay file.
+    pub fn finish(self) {
+        let result = serde_json::to_string(&self.replay)
+            .map_err(std::io::Error::other)
+            .and_then(|json| std::fs::write(&self.path, json + "\n"));
+        match result {
+            Ok(()) => tracing::info!(target: "frontend", "Recorded {} frames to {}", self.replay.frames.len(), self.path),
+            Err(error) => tracing::error!(target: "frontend", "Unable to write replay {}: {}", self.path, error),
+        }
+    }
+}
+
+/// Feeds a recorded replay back one frame at a time.
+pub struct Player {
+    pub replay: Replay,
+    next: usize,
+}
+
+impl Player {
+    pub fn load(path: &str) -> Result<Player, String> {
+        let json = std::fs::read(path).map_err(|error| format!("{}: {}", path, error))?;
+        let replay = serde_json::from_slice(&json).map_err(|error| format!("{}: {}", path, error))?;
+        Ok(Player { replay, next: 0 })
+    }
+
+    /// The next frame and its number, counting from 1, until the replay ends.
+    pub fn next_frame(&mut self) -> Option<(usize, Frame)> {
+        let frame = *self.replay.frames.get(self.next)?;
+        self.next += 1;
+        Some((self.next, frame))
+    }
+}
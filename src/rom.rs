@@ -0,0 +1,127 @@
+use std::io::{Cursor, Error, ErrorKind, Read, Seek};
+use std::path::{Path, PathBuf};
+
+use chip8::symbols::Symbols;
+
+use crate::paths;
+
+/// Large enough for any zipped ROM pack, small enough to not fill the disk
+/// when pointed at the wrong URL.
+const MAX_DOWNLOAD_SIZE: u64 = 16 * 1024 * 1024;
+
+fn has_extension(name: &str, extensions: &[&str]) -> bool {
+    Path::new(name)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            extensions
+                .iter()
+                .any(|candidate| extension.eq_ignore_ascii_case(candidate))
+        })
+}
+
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+
+/// Reads the first .ch8/.c8 entry of a zip archive.
+fn read_zipped_rom(reader: impl Read + Seek) -> std::io::Result<Vec<u8>> {
+    let mut archive = zip::ZipArchive::new(reader).map_err(|error| Error::new(ErrorKind::InvalidData, error))?;
+
+    for index in 0..archive.len() {
+        let mut entry = archive
+            .by_index(index)
+            .map_err(|error| Error::new(ErrorKind::InvalidData, error))?;
+        if entry.is_file() && has_extension(entry.name(), &["ch8", "c8"]) {
+            let mut rom = Vec::with_capacity(entry.size() as usize);
+            entry.read_to_end(&mut rom)?;
+            return Ok(rom);
+        }
+    }
+
+    Err(Error::new(ErrorKind::NotFound, "no .ch8 or .c8 file in archive"))
+}
+
+fn is_url(filename: &str) -> bool {
+    filename.starts_with("http://") || filename.starts_with("https://")
+}
+
+/// Downloads `url` into the cache directory, reusing an earlier download of
+/// the same URL. The file keeps the URL's file name so the extension still
+/// tells zips apart from plain ROMs.
+fn download(url: &str) -> std::io::Result<PathBuf> {
+    let dir = paths::cache_dir()
+        .map(|dir| dir.join("roms"))
+        .ok_or_else(|| Error::new(ErrorKind::NotFound, "no cache directory"))?;
+    let name = url
+        .split(['?', '#'])
+        .next()
+        .and_then(|path| path.rsplit('/').next())
+        .filter(|name| !name.is_empty())
+        .unwrap_or("rom.ch8");
+    let path = dir.join(format!("{}-{}", sha1_smol::Sha1::from(url).digest(), name));
+    if path.exists() {
+        return Ok(path);
+    }
+
+    let response = ureq::get(url)
+        .call()
+        .map_err(|error| Error::other(error.to_string()))?;
+    let mut bytes = Vec::new();
+    response
+        .into_reader()
+        .take(MAX_DOWNLOAD_SIZE + 1)
+        .read_to_end(&mut bytes)?;
+    if bytes.len() as u64 > MAX_DOWNLOAD_SIZE {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("the download is larger than {} bytes", MAX_DOWNLOAD_SIZE),
+        ));
+    }
+
+    std::fs::create_dir_all(&dir)?;
+    std::fs::write(&path, bytes)?;
+    Ok(path)
+}
+
+/// Whether the ROM is an Octo source, which `read_rom` assembles.
+pub fn is_source(filename: &str) -> bool {
+    has_extension(filename, &["8o"])
+}
+
+/// The labels of an .8o source, or `None` for ROMs that are not sources.
+pub fn source_symbols(filename: &str) -> Option<Symbols> {
+    if !is_source(filename) {
+        return None;
+    }
+    let source = std::fs::read_to_string(filename).ok()?;
+    let program = chip8::asm::assemble(&source).ok()?;
+    Some(Symbols::from_labels(&program.labels))
+}
+
+/// Reads a ROM from disk, an http(s) URL or stdin (`-`), looking inside .zip
+/// archives for the first ROM file and assembling .8o sources.
+pub fn read_rom(filename: &str) -> std::io::Result<Vec<u8>> {
+    if filename == "-" {
+        let mut bytes = Vec::new();
+        std::io::stdin().read_to_end(&mut bytes)?;
+        if bytes.starts_with(ZIP_MAGIC) {
+            return read_zipped_rom(Cursor::new(bytes));
+        }
+        return Ok(bytes);
+    }
+
+    if is_url(filename) {
+        let path = download(filename)?;
+        return read_rom(&path.to_string_lossy());
+    }
+
+    if has_extension(filename, &["zip"]) {
+        read_zipped_rom(std::fs::File::open(filename)?)
+    } else if is_source(filename) {
+        let source = std::fs::read_to_string(filename)?;
+        chip8::asm::assemble(&source)
+            .map(|program| program.bytes)
+            .map_err(|error| Error::new(ErrorKind::InvalidData, error))
+    } else {
+        std::fs::read(filename)
+    }
+}
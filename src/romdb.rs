@@ -0,0 +1,177 @@
+//! ROM metadata database: maps the SHA-1 of a ROM file to its title, author,
+//! target platform, required quirks, recommended speed and key bindings.
+
+use std::collections::{BTreeMap, HashMap};
+
+use raylib::prelude::KeyboardKey;
+use serde::Deserialize;
+
+use crate::chip8::{MemoryIncrement, Quirks};
+
+const BUILTIN_DATABASE: &str = include_str!("../assets/romdb.toml");
+
+/// Host keys that press a keypad key too, by the action the keymap gives it.
+const ACTION_KEYS: [(&str, KeyboardKey); 6] = [
+    ("up", KeyboardKey::KEY_UP),
+    ("down", KeyboardKey::KEY_DOWN),
+    ("left", KeyboardKey::KEY_LEFT),
+    ("right", KeyboardKey::KEY_RIGHT),
+    ("a", KeyboardKey::KEY_Z),
+    ("b", KeyboardKey::KEY_X),
+];
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RomInfo {
+    pub sha1: String,
+    pub title: String,
+    #[serde(default)]
+    pub author: Option<String>,
+    #[serde(default)]
+    pub platform: Option<String>,
+    #[serde(default)]
+    pub quirks: Vec<String>,
+    /// Instructions per 60Hz frame.
+    #[serde(default)]
+    pub tickrate: Option<u32>,
+    /// CHIP-8 key (hex digit) to the action it performs in the game.
+    #[serde(default)]
+    pub keymap: BTreeMap<String, String>,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+impl RomInfo {
+    /// The quirks the ROM needs: its platform's preset with the listed quirks
+    /// turned on, or `None` if the entry names neither. Unknown names are
+    /// ignored.
+    pub fn quirks(&self) -> Option<Quirks> {
+        if self.platform.is_none() && self.quirks.is_empty() {
+            return None;
+        }
+        let mut quirks: Quirks = self
+            .platform
+            .as_deref()
+            .and_then(|platform| platform.parse().ok())
+            .unwrap_or_default();
+        for name in &self.quirks {
+            match name.as_str() {
+                "shift_vy" => quirks.shift_vy = true,
+                "jump_with_vx" => quirks.jump_with_vx = true,
+                "vf_reset" => quirks.vf_reset = true,
+                "display_wait" => quirks.display_wait = true,
+                "index_overflow" => quirks.index_overflow = true,
+                "key_release" => quirks.key_release = true,
+                "collision_row_count" => quirks.collision_row_count = true,
+                "memory_increment_none" => quirks.memory_increment_i = MemoryIncrement::None,
+                "memory_increment_x" => quirks.memory_increment_i = MemoryIncrement::X,
+                "memory_increment_x_plus_one" => quirks.memory_increment_i = MemoryIncrement::XPlusOne,
+                _ => {}
+            }
+        }
+        Some(quirks)
+    }
+
+    /// The recommended speed in instructions per second.
+    pub fn cycles_per_second(&self) -> Option<u32> {
+        self.tickrate.map(|tickrate| tickrate * 60)
+    }
+
+    /// The extra host keys for the keymap's actions, as (keypad key, host
+    /// key) pairs: the arrow keys for directions, Z and X for the a and b
+    /// buttons. Other actions and bad key digits are skipped.
+    pub fn key_bindings(&self) -> Vec<(usize, KeyboardKey)> {
+        self.keymap
+            .iter()
+            .filter_map(|(key, action)| {
+                let key = usize::from_str_radix(key, 16).ok().filter(|key| *key < 16)?;
+                let (_, host_key) = ACTION_KEYS.iter().find(|(name, _)| name.eq_ignore_ascii_case(action))?;
+                Some((key, *host_key))
+            })
+            .collect()
+    }
+}
+
+#[derive(Deserialize)]
+struct DatabaseFile {
+    #[serde(default)]
+    rom: Vec<RomInfo>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RomDatabase {
+    roms: HashMap<String, RomInfo>,
+}
+
+impl RomDatabase {
+    /// The database compiled into the binary.
+    pub fn builtin() -> RomDatabase {
+        let mut database = RomDatabase::default();
+        database
+            .add_overlay(BUILTIN_DATABASE)
+            .expect("built-in ROM database is valid");
+        database
+    }
+
+    /// Adds the entries from a TOML overlay, replacing any existing entries
+    /// with the same hash.
+    pub fn add_overlay(&mut self, text: &str) -> Result<(), String> {
+        let file: DatabaseFile = toml::from_str(text).map_err(|error| error.to_string())?;
+        for info in file.rom {
+            self.roms.insert(info.sha1.to_lowercase(), info);
+        }
+        Ok(())
+    }
+
+    pub fn lookup(&self, sha1: &str) -> Option<&RomInfo> {
+        self.roms.get(&sha1.to_lowercase())
+    }
+
+    pub fn lookup_rom(&self, rom: &[u8]) -> Option<&RomInfo> {
+        self.lookup(&rom_hash(rom))
+    }
+}
+
+/// Lowercase hex SHA-1 of the ROM, the key used by the database.
+pub fn rom_hash(rom: &[u8]) -> String {
+    sha1_smol::Sha1::from(rom).digest().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_database_entries_are_valid() {
+        let database = RomDatabase::builtin();
+        for (sha1, info) in &database.roms {
+            assert_eq!(sha1.len(), 40, "{}", info.title);
+            assert!(sha1.chars().all(|c| c.is_ascii_hexdigit()), "{}", info.title);
+            assert_eq!(database.lookup(sha1).map(|found| &found.title), Some(&info.title));
+        }
+    }
+
+    #[test]
+    fn keymap_actions_bind_host_keys() {
+        let mut database = RomDatabase::default();
+        database
+            .add_overlay(
+                r#"
+                [[rom]]
+                sha1 = "0123456789abcdef0123456789abcdef01234567"
+                title = "Example"
+
+                [rom.keymap]
+                5 = "up"
+                8 = "down"
+                A = "a"
+                6 = "jump"
+                "#,
+            )
+            .unwrap();
+        let info = database.lookup("0123456789ABCDEF0123456789ABCDEF01234567").unwrap();
+        assert_eq!(
+            info.key_bindings(),
+            vec![(5, KeyboardKey::KEY_UP), (8, KeyboardKey::KEY_DOWN), (0xA, KeyboardKey::KEY_Z)]
+        );
+    }
+}
@@ -0,0 +1,119 @@
+//! `chip8 romdb-import`: turns the community CHIP-8 database's
+//! `programs.json` (github.com/chip-8/chip-8-database) into `[[rom]]`
+//! entries for `assets/romdb.toml` or the user overlay.
+
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::exit::ExitStatus;
+
+#[derive(Serialize)]
+struct Entry {
+    sha1: String,
+    title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    author: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    platform: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    quirks: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tickrate: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    keymap: BTreeMap<String, String>,
+}
+
+#[derive(Serialize)]
+struct DatabaseFile {
+    rom: Vec<Entry>,
+}
+
+/// Our platform name for one of the database's platform ids.
+fn platform_name(id: &str) -> Option<&'static str> {
+    match id {
+        "originalChip8" | "hybridVIP" | "modernChip8" => Some("chip8"),
+        "chip48" => Some("chip48"),
+        "superchip1" | "superchip" => Some("schip"),
+        "xochip" => Some("xochip"),
+        "megachip8" => Some("megachip"),
+        _ => None,
+    }
+}
+
+/// Our names for the database's quirks the ROM needs turned on.
+fn quirk_names(quirks: &serde_json::Map<String, Value>) -> Vec<String> {
+    let names = [
+        ("shift", "shift_vy"),
+        ("jump", "jump_with_vx"),
+        ("logic", "vf_reset"),
+        ("vblank", "display_wait"),
+        ("memoryLeaveIUnchanged", "memory_increment_none"),
+        ("memoryIncrementByX", "memory_increment_x"),
+    ];
+    names
+        .iter()
+        .filter(|(key, _)| quirks.get(*key).and_then(Value::as_bool) == Some(true))
+        .map(|(_, name)| name.to_string())
+        .collect()
+}
+
+/// The entries for every ROM of every program in `programs`.
+fn entries(programs: &Value) -> Vec<Entry> {
+    let mut entries = Vec::new();
+    for program in programs.as_array().into_iter().flatten() {
+        let Some(title) = program["title"].as_str() else {
+            continue;
+        };
+        let authors: Vec<&str> = program["authors"].as_array().into_iter().flatten().filter_map(Value::as_str).collect();
+        for (sha1, rom) in program["roms"].as_object().into_iter().flatten() {
+            let platform_id = rom["platforms"].as_array().and_then(|platforms| platforms.first()).and_then(Value::as_str);
+            let quirks = platform_id
+                .and_then(|id| rom["quirkyPlatforms"][id].as_object())
+                .map(quirk_names)
+                .unwrap_or_default();
+            let keymap = rom["keys"]
+                .as_object()
+                .into_iter()
+                .flatten()
+                .filter_map(|(action, key)| Some((format!("{:X}", key.as_u64()?), action.clone())))
+                .collect();
+            entries.push(Entry {
+                sha1: sha1.to_lowercase(),
+                title: title.to_string(),
+                author: (!authors.is_empty()).then(|| authors.join(", ")),
+                platform: platform_id.and_then(platform_name).map(str::to_string),
+                quirks,
+                tickrate: rom["tickrate"].as_u64().and_then(|tickrate| tickrate.try_into().ok()),
+                description: program["description"].as_str().map(str::to_string),
+                keymap,
+            });
+        }
+    }
+    entries.sort_by(|a, b| a.title.cmp(&b.title).then(a.sha1.cmp(&b.sha1)));
+    entries
+}
+
+/// Prints the entries for `programs.json` as TOML.
+pub fn run(path: &str) {
+    let programs: Value = match std::fs::read_to_string(path).map_err(|error| error.to_string()).and_then(|text| {
+        serde_json::from_str(&text).map_err(|error| error.to_string())
+    }) {
+        Ok(programs) => programs,
+        Err(error) => {
+            eprintln!("Unable to read {}: {}", path, error);
+            ExitStatus::Error.exit();
+        }
+    };
+    let file = DatabaseFile { rom: entries(&programs) };
+    match toml::to_string(&file) {
+        Ok(text) => print!("{}", text),
+        Err(error) => {
+            eprintln!("Unable to write the entries: {}", error);
+            ExitStatus::Error.exit();
+        }
+    }
+}
@@ -0,0 +1,224 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use chip8::chip8::Chip8;
+use chip8::chip8::initialize;
+use mlua::{Function, Lua, RegistryKey, Table};
+
+/// Sets up the `emu` table and the subscription helpers. Handlers are kept in
+/// Lua so they can be called without crossing back into Rust.
+const PRELUDE: &str = r#"
+emu = { handlers = { frame = {}, instruction = {} } }
+function emu.on_frame(handler) table.insert(emu.handlers.frame, handler) end
+function emu.on_instruction(handler) table.insert(emu.handlers.instruction, handler) end
+"#;
+
+/// A user Lua script driving the emulator through the `emu` API:
+///
+/// * `emu.on_frame(fn())`, `emu.on_instruction(fn(pc, opcode))` subscribe to events
+/// * `emu.reg(x)`, `emu.set_reg(x, value)`, `emu.pc()`, `emu.set_pc(addr)`,
+///   `emu.i()`, `emu.set_i(addr)`, `emu.delay()`, `emu.sound()` access registers
+/// * `emu.read(addr)`, `emu.write(addr, value)` access memory
+/// * `emu.press(key)`, `emu.release(key)` hold or release keypad keys
+/// * `emu.hud(text)` shows a line of text on screen for the current frame
+///
+/// Register and memory access is available while the script loads and inside
+/// handlers.
+pub struct Script {
+    lua: Lua,
+    /// The machine the register and memory functions work on. The caller's
+    /// machine is swapped in while the script runs and back out after, so
+    /// the functions are bound once rather than on every call.
+    machine: Rc<RefCell<Chip8>>,
+    /// `emu.handlers.frame` and `emu.handlers.instruction`.
+    frame_handlers: RegistryKey,
+    instruction_handlers: RegistryKey,
+    held_keys: Rc<RefCell<[bool; 16]>>,
+    hud: Rc<RefCell<Vec<String>>>,
+    failed: bool,
+}
+
+fn install_machine_api(lua: &Lua, emu: &Table, machine: &Rc<RefCell<Chip8>>) -> mlua::Result<()> {
+    let m = machine.clone();
+    emu.set("reg", lua.create_function(move |_, x: usize| Ok(m.borrow().v[x & 0xF]))?)?;
+    let m = machine.clone();
+    emu.set(
+        "set_reg",
+        lua.create_function(move |_, (x, value): (usize, u8)| {
+            m.borrow_mut().v[x & 0xF] = value;
+            Ok(())
+        })?,
+    )?;
+    let m = machine.clone();
+    emu.set("pc", lua.create_function(move |_, ()| Ok(m.borrow().pc))?)?;
+    let m = machine.clone();
+    emu.set(
+        "set_pc",
+        lua.create_function(move |_, address: u16| {
+            m.borrow_mut().pc = address;
+            Ok(())
+        })?,
+    )?;
+    let m = machine.clone();
+    emu.set("i", lua.create_function(move |_, ()| Ok(m.borrow().i))?)?;
+    let m = machine.clone();
+    emu.set(
+        "set_i",
+        lua.create_function(move |_, address: u32| {
+            m.borrow_mut().i = address & 0xFF_FFFF;
+            Ok(())
+        })?,
+    )?;
+    let m = machine.clone();
+    emu.set("delay", lua.create_function(move |_, ()| Ok(m.borrow().timer_delay))?)?;
+    let m = machine.clone();
+    emu.set("sound", lua.create_function(move |_, ()| Ok(m.borrow().timer_sound))?)?;
+    let m = machine.clone();
+    emu.set(
+        "read",
+        lua.create_function(move |_, address: usize| {
+            let machine = m.borrow();
+            Ok(machine.memory[address % machine.memory.len()])
+        })?,
+    )?;
+    let m = machine.clone();
+    emu.set(
+        "write",
+        lua.create_function(move |_, (address, value): (usize, u8)| {
+            let mut machine = m.borrow_mut();
+            let len = machine.memory.len();
+            machine.memory[address % len] = value;
+            Ok(())
+        })?,
+    )?;
+    Ok(())
+}
+
+impl Script {
+    /// Loads and runs the script at `path`.
+    pub fn load(path: &str, chip8: &mut Chip8) -> Result<Script, String> {
+        let source = std::fs::read_to_string(path).map_err(|error| error.to_string())?;
+        let script = Script::new().map_err(|error| error.to_string())?;
+        script
+            .with_machine(chip8, || script.lua.load(&source).set_name(path).exec())
+            .map_err(|error| error.to_string())?;
+        Ok(script)
+    }
+
+    /// A script with the prelude run and the API installed.
+    fn new() -> mlua::Result<Script> {
+        let lua = Lua::new();
+        lua.load(PRELUDE).set_name("prelude").exec()?;
+        let (frame_handlers, instruction_handlers) = {
+            let handlers: Table = lua.globals().get::<_, Table>("emu")?.get("handlers")?;
+            (
+                lua.create_registry_value(handlers.get::<_, Table>("frame")?)?,
+                lua.create_registry_value(handlers.get::<_, Table>("instruction")?)?,
+            )
+        };
+        let script = Script {
+            lua,
+            machine: Rc::new(RefCell::new(initialize())),
+            frame_handlers,
+            instruction_handlers,
+            held_keys: Rc::new(RefCell::new([false; 16])),
+            hud: Rc::new(RefCell::new(Vec::new())),
+            failed: false,
+        };
+        script.install_api()?;
+        Ok(script)
+    }
+
+    fn install_api(&self) -> mlua::Result<()> {
+        let emu: Table = self.lua.globals().get("emu")?;
+        install_machine_api(&self.lua, &emu, &self.machine)?;
+
+        let held_keys = self.held_keys.clone();
+        emu.set(
+            "press",
+            self.lua.create_function(move |_, key: usize| {
+                held_keys.borrow_mut()[key & 0xF] = true;
+                Ok(())
+            })?,
+        )?;
+        let held_keys = self.held_keys.clone();
+        emu.set(
+            "release",
+            self.lua.create_function(move |_, key: usize| {
+                held_keys.borrow_mut()[key & 0xF] = false;
+                Ok(())
+            })?,
+        )?;
+        let hud = self.hud.clone();
+        emu.set(
+            "hud",
+            self.lua.create_function(move |_, text: String| {
+                hud.borrow_mut().push(text);
+                Ok(())
+            })?,
+        )?;
+        Ok(())
+    }
+
+    /// Runs `f` with the register and memory functions working on `chip8`.
+    fn with_machine<R>(&self, chip8: &mut Chip8, f: impl FnOnce() -> R) -> R {
+        std::mem::swap(chip8, &mut self.machine.borrow_mut());
+        let result = f();
+        std::mem::swap(chip8, &mut self.machine.borrow_mut());
+        result
+    }
+
+    /// Calls the handlers in `handlers` with `args`, without touching the
+    /// machine when there are none.
+    fn call_handlers(&self, chip8: &mut Chip8, handlers: &RegistryKey, args: (u16, u16)) -> mlua::Result<()> {
+        let handlers: Table = self.lua.registry_value(handlers)?;
+        if handlers.raw_len() == 0 {
+            return Ok(());
+        }
+        self.with_machine(chip8, || {
+            for handler in handlers.sequence_values::<Function>() {
+                handler?.call::<_, ()>(args)?;
+            }
+            Ok(())
+        })
+    }
+
+    fn stop_on_error(&mut self, result: mlua::Result<()>) {
+        if let Err(error) = result {
+            tracing::error!(target: "script", "Script stopped: {}", error);
+            self.failed = true;
+        }
+    }
+
+    /// Calls the frame handlers; the HUD is rebuilt every frame.
+    pub fn frame(&mut self, chip8: &mut Chip8) {
+        self.hud.borrow_mut().clear();
+        if !self.failed {
+            let result = self.call_handlers(chip8, &self.frame_handlers, (0, 0));
+            self.stop_on_error(result);
+        }
+    }
+
+    /// Calls the instruction handlers with the instruction about to run.
+    pub fn instruction(&mut self, chip8: &mut Chip8, pc: u16, opcode: u16) {
+        if !self.failed {
+            let result = self.call_handlers(chip8, &self.instruction_handlers, (pc, opcode));
+            self.stop_on_error(result);
+        }
+    }
+
+    /// The keys the script is holding down, one bit per key, to press on top
+    /// of the user's.
+    pub fn held_keys(&self) -> u16 {
+        self.held_keys
+            .borrow()
+            .iter()
+            .enumerate()
+            .filter(|(_, held)| **held)
+            .fold(0, |down, (key, _)| down | 1 << key)
+    }
+
+    pub fn hud(&self) -> std::cell::Ref<'_, Vec<String>> {
+        self.hud.borrow()
+    }
+}
@@ -0,0 +1,81 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::paths;
+
+/// Runtime preferences restored on launch and saved on exit.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub cycles_per_second: u32,
+    pub color: usize,
+    pub show_cycles_info: bool,
+    pub show_registers: bool,
+    pub show_display: bool,
+    pub show_disassembly: bool,
+    pub show_keypad: bool,
+    pub show_profile: bool,
+    pub show_heatmap: bool,
+    pub show_calls: bool,
+    pub show_register_history: bool,
+    pub window_width: i32,
+    pub window_height: i32,
+    /// UI language code, `en` or `pl`.
+    pub language: String,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            cycles_per_second: 1000,
+            color: 0,
+            show_cycles_info: false,
+            show_registers: true,
+            show_display: true,
+            show_disassembly: false,
+            show_keypad: false,
+            show_profile: false,
+            show_heatmap: false,
+            show_calls: false,
+            show_register_history: false,
+            window_width: 1280,
+            window_height: 720,
+            language: "en".to_owned(),
+        }
+    }
+}
+
+fn settings_file() -> Option<PathBuf> {
+    paths::config_dir().map(|dir| dir.join("settings.toml"))
+}
+
+/// Loads the saved settings, falling back to the defaults for anything
+/// missing or unreadable.
+pub fn load() -> Settings {
+    let Some(path) = settings_file() else {
+        return Settings::default();
+    };
+    let Ok(text) = std::fs::read_to_string(&path) else {
+        return Settings::default();
+    };
+    toml::from_str(&text).unwrap_or_else(|error| {
+        tracing::warn!(target: "frontend", "Ignoring {}: {}", path.display(), error);
+        Settings::default()
+    })
+}
+
+pub fn save(settings: &Settings) {
+    let Some(path) = settings_file() else {
+        return;
+    };
+    let result = toml::to_string(settings)
+        .map_err(std::io::Error::other)
+        .and_then(|text| {
+            std::fs::create_dir_all(path.parent().unwrap())?;
+            std::fs::write(&path, text)
+        });
+    if let Err(error) = result {
+        tracing::warn!(target: "frontend", "Unable to save settings to {}: {}", path.display(), error);
+    }
+}
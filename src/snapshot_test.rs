@@ -0,0 +1,54 @@
+use chip8::harness;
+
+use crate::exit::ExitStatus;
+
+pub fn run(
+    filename: &str,
+    cycles: u64,
+    cycles_per_frame: u64,
+    input: Option<&str>,
+    golden: &str,
+    update: bool,
+) {
+    let rom = crate::rom::read_rom(filename).unwrap_or_else(|error| {
+        eprintln!("Unable to read {}: {}", filename, error);
+        ExitStatus::Error.exit();
+    });
+    let events = match input {
+        Some(path) => {
+            let script = std::fs::read_to_string(path).unwrap_or_else(|error| {
+                eprintln!("Unable to read {}: {}", path, error);
+                ExitStatus::Error.exit();
+            });
+            harness::parse_input_script(&script).unwrap_or_else(|error| {
+                eprintln!("{}: {}", path, error);
+                ExitStatus::Error.exit();
+            })
+        }
+        None => Vec::new(),
+    };
+
+    let chip8 = harness::run(&rom, cycles, cycles_per_frame, &events);
+    let actual = harness::display_snapshot(&chip8);
+
+    if update {
+        if let Err(error) = std::fs::write(golden, &actual) {
+            eprintln!("Unable to write {}: {}", golden, error);
+            ExitStatus::Error.exit();
+        }
+        println!("Updated {}", golden);
+        return;
+    }
+
+    let expected = std::fs::read_to_string(golden).unwrap_or_else(|error| {
+        eprintln!("Unable to read {}: {}", golden, error);
+        ExitStatus::Error.exit();
+    });
+    match harness::compare_snapshots(&actual, &expected) {
+        Ok(()) => println!("PASS {}", filename),
+        Err(report) => {
+            println!("FAIL {}\n{}", filename, report);
+            ExitStatus::TestFailed.exit();
+        }
+    }
+}
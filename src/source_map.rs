@@ -0,0 +1,56 @@
+//! Maps addresses back to the lines of the `.8o` source they were assembled
+//! from, for showing the source line at PC and setting breakpoints by line.
+
+use std::collections::BTreeMap;
+
+use crate::asm::Program;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SourceMap {
+    /// The source file's path, as given.
+    pub file: String,
+    source: Vec<String>,
+    /// Address of each instruction to its 1-based line.
+    lines: BTreeMap<u16, usize>,
+}
+
+impl SourceMap {
+    /// The map of `program`, assembled from `source` read from `file`.
+    pub fn new(file: &str, source: &str, program: &Program) -> SourceMap {
+        SourceMap {
+            file: file.to_string(),
+            source: source.lines().map(str::to_string).collect(),
+            lines: program.lines.clone(),
+        }
+    }
+
+    /// The line the instruction at `address` was assembled from.
+    pub fn line(&self, address: u16) -> Option<usize> {
+        self.lines.get(&address).copied()
+    }
+
+    /// The text of a 1-based line.
+    pub fn text(&self, line: usize) -> Option<&str> {
+        self.source.get(line.checked_sub(1)?).map(String::as_str)
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.source.len()
+    }
+
+    /// The address of the first instruction of `line`, or of the next line
+    /// with code on it when it has none, e.g. a label or comment.
+    pub fn address(&self, line: usize) -> Option<u16> {
+        let code_line = self.lines.values().filter(|&&other| other >= line).min()?;
+        self.lines
+            .iter()
+            .find(|(_, &other)| other == *code_line)
+            .map(|(&address, _)| address)
+    }
+
+    /// Whether `file` names the source, as its path or its file name.
+    pub fn is_file(&self, file: &str) -> bool {
+        let name = std::path::Path::new(&self.file).file_name();
+        self.file == file || name.is_some_and(|name| name == file)
+    }
+}
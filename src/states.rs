@@ -0,0 +1,66 @@
+use std::path::{Path, PathBuf};
+
+use chip8::chip8::{state, Chip8};
+
+use crate::paths;
+
+/// `slot` is empty for the F5/F9 state, or names another state kept for the
+/// same ROM, e.g. `resume`.
+fn state_file(rom_hash: &str, slot: &str) -> Option<PathBuf> {
+    let name = match slot {
+        "" => format!("{}.state", rom_hash),
+        slot => format!("{}.{}.state", rom_hash, slot),
+    };
+    paths::data_dir().map(|dir| dir.join("states").join(name))
+}
+
+fn save_to(chip8: &Chip8, rom_hash: &str, path: &Path) -> Result<(), String> {
+    let bytes = state::encode(&state::snapshot(chip8), rom_hash);
+    std::fs::create_dir_all(path.parent().unwrap())
+        .and_then(|_| std::fs::write(path, bytes))
+        .map_err(|error| format!("{}: {}", path.display(), error))
+}
+
+fn load_from(chip8: &mut Chip8, rom_hash: &str, path: &Path) -> Result<(), String> {
+    let bytes = std::fs::read(path).map_err(|error| format!("{}: {}", path.display(), error))?;
+    let (saved_for, saved) = state::decode(&bytes).map_err(|error| format!("{}: {}", path.display(), error))?;
+    if saved_for != rom_hash {
+        return Err(format!("{}: saved for a different ROM", path.display()));
+    }
+    state::restore(chip8, &saved)
+}
+
+/// Saves the machine's state for the ROM, replacing any earlier save.
+pub fn save(chip8: &Chip8, rom_hash: &str) -> Result<PathBuf, String> {
+    let path = state_file(rom_hash, "").ok_or("no data directory")?;
+    save_to(chip8, rom_hash, &path)?;
+    Ok(path)
+}
+
+/// Restores the state last saved for the ROM.
+pub fn load(chip8: &mut Chip8, rom_hash: &str) -> Result<(), String> {
+    let path = state_file(rom_hash, "").ok_or("no data directory")?;
+    load_from(chip8, rom_hash, &path)
+}
+
+/// Saves where the ROM was left when the emulator closed, separately from the
+/// F5 state so closing never overwrites a save the user made on purpose.
+pub fn save_resume(chip8: &Chip8, rom_hash: &str) {
+    let Some(path) = state_file(rom_hash, "resume") else {
+        return;
+    };
+    if let Err(error) = save_to(chip8, rom_hash, &path) {
+        tracing::warn!(target: "frontend", "Unable to save resume state: {}", error);
+    }
+}
+
+/// Whether the ROM was left running last time and can be resumed.
+pub fn can_resume(rom_hash: &str) -> bool {
+    state_file(rom_hash, "resume").is_some_and(|path| path.exists())
+}
+
+/// Picks the ROM up where it was left when the emulator last closed.
+pub fn resume(chip8: &mut Chip8, rom_hash: &str) -> Result<(), String> {
+    let path = state_file(rom_hash, "resume").ok_or("no data directory")?;
+    load_from(chip8, rom_hash, &path)
+}
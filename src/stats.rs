@@ -0,0 +1,57 @@
+use std::collections::BTreeMap;
+use std::time::Instant;
+
+use chip8::chip8::{decode, Chip8};
+use serde::Serialize;
+
+#[derive(Serialize)]
+pub struct RunStats {
+    pub instructions: u64,
+    pub opcodes: BTreeMap<&'static str, u64>,
+    pub draw_calls: u64,
+    pub frames: u64,
+    pub average_ips: f64,
+    /// Faults the machine raised since the ROM was last loaded or reset.
+    pub faults: u64,
+    #[serde(skip)]
+    started: Instant,
+}
+
+impl RunStats {
+    pub fn new() -> RunStats {
+        RunStats {
+            instructions: 0,
+            opcodes: BTreeMap::new(),
+            draw_calls: 0,
+            frames: 0,
+            average_ips: 0.0,
+            faults: 0,
+            started: Instant::now(),
+        }
+    }
+
+    pub fn record(&mut self, opcode: u16) {
+        self.instructions += 1;
+        if let Some(pattern) = decode(opcode).pattern() {
+            *self.opcodes.entry(pattern).or_insert(0) += 1;
+            if pattern == "Dxyn" {
+                self.draw_calls += 1;
+            }
+        }
+    }
+
+    /// Writes the statistics as JSON to `path`, with the faults `chip8` has
+    /// raised.
+    pub fn write(&mut self, path: &str, frames: u64, chip8: &Chip8) -> std::io::Result<()> {
+        self.frames = frames;
+        self.faults = chip8.fault_count;
+        let seconds = self.started.elapsed().as_secs_f64();
+        self.average_ips = if seconds > 0.0 {
+            self.instructions as f64 / seconds
+        } else {
+            0.0
+        };
+        let json = serde_json::to_string_pretty(self).map_err(std::io::Error::other)?;
+        std::fs::write(path, json + "\n")
+    }
+}
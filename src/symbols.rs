@@ -0,0 +1,84 @@
+//! Label names for addresses, read from the `.sym` files Octo can write next
+//! to a ROM or taken from assembling an `.8o` source, so the disassembler,
+//! traces and breakpoints can say `main_loop` instead of `0x21A`.
+
+use std::collections::{BTreeMap, HashMap};
+
+/// Labels and the addresses they mark.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Symbols {
+    names: BTreeMap<u16, String>,
+    addresses: HashMap<String, u16>,
+}
+
+/// Parses `0x21a` as hex and `538` as decimal.
+fn parse_address(value: &str) -> Option<u16> {
+    match value.strip_prefix("0x").or_else(|| value.strip_prefix("0X")) {
+        Some(digits) => u16::from_str_radix(digits, 16).ok(),
+        None if value.bytes().all(|byte| byte.is_ascii_digit()) => value.parse().ok(),
+        None => None,
+    }
+}
+
+impl Symbols {
+    /// The labels of an assembled program.
+    pub fn from_labels(labels: &BTreeMap<String, u16>) -> Symbols {
+        let mut symbols = Symbols::default();
+        for (name, &address) in labels {
+            symbols.insert(name, address);
+        }
+        symbols
+    }
+
+    /// Parses a symbol file: a label and its address on each line, e.g.
+    /// `main_loop 0x21A`, in either order and separated by spaces, `:` or
+    /// `=`. Addresses are hex with a `0x` prefix, or decimal. `#` starts a
+    /// comment, and lines that are not a label and an address, such as
+    /// section headers, are skipped.
+    pub fn parse(text: &str) -> Result<Symbols, String> {
+        let mut symbols = Symbols::default();
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap_or_default();
+            let words: Vec<&str> = line
+                .split(|c: char| c.is_whitespace() || c == ':' || c == '=')
+                .filter(|word| !word.is_empty())
+                .collect();
+            let [first, second] = words[..] else {
+                continue;
+            };
+            match (parse_address(first), parse_address(second)) {
+                (None, Some(address)) => symbols.insert(first, address),
+                (Some(address), None) => symbols.insert(second, address),
+                _ => continue,
+            }
+        }
+        match symbols.is_empty() {
+            true => Err("no labels found".to_string()),
+            false => Ok(symbols),
+        }
+    }
+
+    /// Adds a label. An address with several labels is shown by the first.
+    pub fn insert(&mut self, name: &str, address: u16) {
+        self.names.entry(address).or_insert_with(|| name.to_string());
+        self.addresses.insert(name.to_string(), address);
+    }
+
+    /// The label at `address`, if any.
+    pub fn name(&self, address: u16) -> Option<&str> {
+        self.names.get(&address).map(String::as_str)
+    }
+
+    /// The address `name` marks, if it is a label.
+    pub fn address(&self, name: &str) -> Option<u16> {
+        self.addresses.get(name).copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.addresses.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.addresses.is_empty()
+    }
+}
@@ -0,0 +1,218 @@
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::{Duration, Instant};
+
+use chip8::chip8::*;
+use raylib::prelude::*;
+
+use crate::{
+    handle_draw_debug, handle_draw_disassembly, handle_draw_source_line, handle_draw_emulator, handle_draw_keypad, instruction_period_for, pause_pressed, COLORS,
+    DebugInfo, MAX_CATCH_UP, TIMER_PERIOD,
+};
+
+/// How much emulated time the CPU thread runs before checking for new input.
+const SLICE: Duration = Duration::from_millis(1);
+
+enum Control {
+    Keypad(u16),
+    Pause(bool),
+    Speed(u32),
+    Reset,
+    Quit,
+}
+
+/// Machine state published by the CPU thread about once per 60Hz frame.
+struct Snapshot {
+    chip8: Chip8,
+    cycles_executed: u64,
+}
+
+fn emulate(
+    mut chip8: Chip8,
+    target_cycles_per_second: u32,
+    mut paused: bool,
+    controls: Receiver<Control>,
+    snapshots: Sender<Snapshot>,
+) {
+    let mut instruction_period = instruction_period_for(target_cycles_per_second);
+    let mut instruction_accumulator = Duration::ZERO;
+    let mut timer_accumulator = Duration::ZERO;
+    let mut cycles_executed: u64 = 0;
+    let mut last_tick = Instant::now();
+    let mut last_snapshot = Instant::now();
+
+    loop {
+        for control in controls.try_iter() {
+            match control {
+                Control::Keypad(keys) => set_keypad(&mut chip8, keys),
+                Control::Pause(pause) => paused = pause,
+                Control::Speed(cycles_per_second) => {
+                    instruction_period = instruction_period_for(cycles_per_second)
+                }
+                Control::Reset => reset(&mut chip8),
+                Control::Quit => return,
+            }
+        }
+
+        let now = Instant::now();
+        let delta = (now - last_tick).min(MAX_CATCH_UP);
+        last_tick = now;
+
+        if !paused {
+            instruction_accumulator += delta;
+            timer_accumulator += delta;
+            while instruction_accumulator >= instruction_period {
+                instruction_accumulator -= instruction_period;
+                step(&mut chip8);
+                cycles_executed += 1;
+            }
+            while timer_accumulator >= TIMER_PERIOD {
+                timer_accumulator -= TIMER_PERIOD;
+                update_timers(&mut chip8);
+            }
+            for event in take_events(&mut chip8) {
+                tracing::debug!(target: "audio", ?event, "sound");
+            }
+        }
+
+        if last_snapshot.elapsed() >= TIMER_PERIOD {
+            last_snapshot = Instant::now();
+            let snapshot = Snapshot {
+                chip8: chip8.clone(),
+                cycles_executed,
+            };
+            if snapshots.send(snapshot).is_err() {
+                return;
+            }
+        }
+
+        crate::pacing::sleep_coarse(SLICE.saturating_sub(last_tick.elapsed()));
+    }
+}
+
+/// Runs the CPU on its own thread while this thread renders and polls input,
+/// so heavy debug drawing or vsync waits never stall emulation timing. Only
+/// the core hotkeys are available in this mode.
+#[allow(clippy::too_many_arguments)]
+pub fn run(
+    chip8: Chip8,
+    debug_info: DebugInfo,
+    key_bindings: Vec<(usize, KeyboardKey)>,
+    target_cycles_per_second: u32,
+    start_paused: bool,
+    pixel_size: usize,
+    mut rl: RaylibHandle,
+    thread: RaylibThread,
+) {
+    let (control_sender, controls) = mpsc::channel();
+    let (snapshot_sender, snapshots) = mpsc::channel();
+    let mut latest = Snapshot {
+        chip8: chip8.clone(),
+        cycles_executed: 0,
+    };
+    let emulation = std::thread::spawn(move || {
+        emulate(chip8, target_cycles_per_second, start_paused, controls, snapshot_sender)
+    });
+    rl.set_target_fps(60);
+
+    let screen_width = rl.get_screen_width();
+    let screen_height = rl.get_screen_height();
+    let mut paused = start_paused;
+    let mut speed = target_cycles_per_second;
+    let mut current_color_index = 0;
+    let mut draw_debug_cycles_info = false;
+    let mut draw_debug_registers_info = true;
+    let mut draw_emulator = true;
+    let mut draw_disassembly = false;
+    let mut draw_keypad = false;
+    let mut last_second = Instant::now();
+    let mut cycles_at_last_second = 0;
+    let mut cycles_per_second = 0;
+
+    while !rl.window_should_close() {
+        if let Some(snapshot) = snapshots.try_iter().last() {
+            latest = snapshot;
+        }
+        if last_second.elapsed().as_secs() >= 1 {
+            cycles_per_second = latest.cycles_executed - cycles_at_last_second;
+            cycles_at_last_second = latest.cycles_executed;
+            last_second = Instant::now();
+        }
+
+        let previous_keypad = latest.chip8.keypad.down();
+        set_keypad(&mut latest.chip8, read_keypad(&rl) | read_bound_keys(&rl, &key_bindings));
+        if latest.chip8.keypad.down() != previous_keypad {
+            let _ = control_sender.send(Control::Keypad(latest.chip8.keypad.down()));
+        }
+
+        if pause_pressed(&rl) {
+            paused = !paused;
+            let _ = control_sender.send(Control::Pause(paused));
+        }
+        if rl.is_key_pressed(KeyboardKey::KEY_COMMA) {
+            speed = speed.saturating_sub(100).max(100);
+            let _ = control_sender.send(Control::Speed(speed));
+        }
+        if rl.is_key_pressed(KeyboardKey::KEY_PERIOD) {
+            speed = target_cycles_per_second;
+            let _ = control_sender.send(Control::Speed(speed));
+        }
+        if rl.is_key_pressed(KeyboardKey::KEY_SLASH) {
+            speed += 100;
+            let _ = control_sender.send(Control::Speed(speed));
+        }
+        if rl.is_key_pressed(KeyboardKey::KEY_LEFT_BRACKET) {
+            current_color_index = (current_color_index + COLORS.len() - 1) % COLORS.len();
+        }
+        if rl.is_key_pressed(KeyboardKey::KEY_RIGHT_BRACKET) {
+            current_color_index = (current_color_index + 1) % COLORS.len();
+        }
+        if rl.is_key_pressed(KeyboardKey::KEY_F1) {
+            draw_debug_cycles_info = !draw_debug_cycles_info;
+        }
+        if rl.is_key_pressed(KeyboardKey::KEY_F2) {
+            draw_debug_registers_info = !draw_debug_registers_info;
+        }
+        if rl.is_key_pressed(KeyboardKey::KEY_F3) {
+            draw_emulator = !draw_emulator;
+        }
+        if rl.is_key_pressed(KeyboardKey::KEY_F7) {
+            draw_disassembly = !draw_disassembly;
+        }
+        if rl.is_key_pressed(KeyboardKey::KEY_K) {
+            draw_keypad = !draw_keypad;
+        }
+        if rl.is_key_pressed(KeyboardKey::KEY_F4) {
+            let _ = control_sender.send(Control::Reset);
+        }
+
+        let mut d = rl.begin_drawing(&thread);
+        d.clear_background(Color::BLACK);
+        if draw_emulator {
+            handle_draw_emulator(&mut d, &latest.chip8, pixel_size, COLORS[current_color_index]);
+        }
+        handle_draw_debug(
+            &mut d,
+            &latest.chip8,
+            draw_debug_cycles_info,
+            draw_debug_registers_info,
+            screen_width,
+            cycles_per_second,
+            latest.cycles_executed,
+        );
+        if draw_disassembly {
+            handle_draw_disassembly(&mut d, &latest.chip8, &debug_info.symbols, screen_width);
+        }
+        if let (true, Some(source_map)) = (draw_debug_registers_info, &debug_info.source_map) {
+            handle_draw_source_line(&mut d, &latest.chip8, source_map, screen_width);
+        }
+        if draw_keypad {
+            handle_draw_keypad(&mut d, &latest.chip8, screen_width, screen_height, COLORS[current_color_index]);
+        }
+        if paused {
+            d.draw_text(crate::i18n::tr("paused"), 10, screen_height - 30, 20, Color::WHITE);
+        }
+    }
+
+    let _ = control_sender.send(Control::Quit);
+    let _ = emulation.join();
+}
@@ -0,0 +1,85 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use chip8::chip8::{decode, Chip8};
+use chip8::disasm::instruction_text;
+use chip8::symbols::Symbols;
+
+/// How `--trace` lays out each line.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TraceFormat {
+    /// `0x200  6305  v3 := 0x05  V: 00 .. 00  I: 0x000`, for reading, with
+    /// a `: label` line before each labelled address.
+    #[default]
+    Native,
+    /// Lowercase hex in the layout of Octo's register display, one field per
+    /// register, so traces from both emulators can be diffed line by line.
+    Octo,
+}
+
+impl std::str::FromStr for TraceFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "native" => Ok(TraceFormat::Native),
+            "octo" => Ok(TraceFormat::Octo),
+            _ => Err(format!("expected native or octo, got {}", value)),
+        }
+    }
+}
+
+/// Writes a line for every instruction executed, with the registers as they
+/// were before it ran.
+pub struct Tracer {
+    out: BufWriter<Box<dyn Write>>,
+    format: TraceFormat,
+}
+
+impl Tracer {
+    /// Traces to `path`, or to stdout for `-`.
+    pub fn create(path: &str, format: TraceFormat) -> std::io::Result<Tracer> {
+        let out: Box<dyn Write> = match path {
+            "-" => Box::new(std::io::stdout()),
+            path => Box::new(File::create(path)?),
+        };
+        Ok(Tracer {
+            out: BufWriter::new(out),
+            format,
+        })
+    }
+
+    pub fn record(&mut self, chip8: &Chip8, pc: u16, opcode: u16, symbols: &Symbols) -> std::io::Result<()> {
+        match self.format {
+            TraceFormat::Native => {
+                if let Some(name) = symbols.name(pc) {
+                    writeln!(self.out, ": {}", name)?;
+                }
+                let text = instruction_text(decode(opcode), symbols);
+                write!(self.out, "{:#05X}  {:04X}  {:<24}  V:", pc, opcode, text)?;
+                for v in chip8.v {
+                    write!(self.out, " {:02X}", v)?;
+                }
+                writeln!(
+                    self.out,
+                    "  I: {:#05X}  DT: {:02X}  ST: {:02X}  SP: {}",
+                    chip8.i,
+                    chip8.timer_delay,
+                    chip8.timer_sound,
+                    chip8.stack.len()
+                )
+            }
+            TraceFormat::Octo => {
+                write!(self.out, "pc: {:04x} op: {:04x}", pc, opcode)?;
+                for (x, v) in chip8.v.iter().enumerate() {
+                    write!(self.out, " v{:x}: {:02x}", x, v)?;
+                }
+                writeln!(self.out, " i: {:04x}", chip8.i)
+            }
+        }
+    }
+
+    pub fn finish(mut self) -> std::io::Result<()> {
+        self.out.flush()
+    }
+}
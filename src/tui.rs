@@ -0,0 +1,608 @@
+//! `--backend tui-debug`: the machine in a terminal, with the display drawn
+//! in half blocks next to the registers, stack, disassembly and memory, and a
+//! gdb-like command prompt while paused.
+
+use std::time::Instant;
+
+use chip8::chip8::cheats::{apply_cheats, Cheat, CheatKind};
+use chip8::chip8::*;
+use chip8::disasm::{disassemble, instruction_text};
+use chip8::expr::{Expr, Watch};
+use chip8::source_map::SourceMap;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Paragraph, Sparkline};
+use ratatui::Frame;
+
+use crate::calls::{call_text, CallTimeline};
+use crate::{DebugInfo, TIMER_PERIOD};
+
+/// Frames a keypad key typed while running stays held, as terminals only
+/// report key presses.
+const KEY_HOLD_FRAMES: u32 = 6;
+
+const HELP: &str = "break/b ADDR|LABEL|FILE:LINE [if EXPR], delete/d ADDR|LABEL|FILE:LINE, step/s [N], next/n, \
+                    continue/c, poke ADDR|V0-VF|I VALUE..., freeze ADDR VALUE, cheat [N], history/h [V0-VF], \
+                    watch/w [EXPR], unwatch N, print/p EXPR, mem/x ADDR|LABEL, reset, quit/q";
+
+struct Debugger {
+    chip8: Chip8,
+    debug_info: DebugInfo,
+    cheats: Vec<Cheat>,
+    calls: CallTimeline,
+    paused: bool,
+    target_cycles_per_second: u32,
+    /// Instructions per second not yet run, carried between frames.
+    budget: u32,
+    /// Frames left before each keypad key typed while running is released.
+    held: [u32; 16],
+    input: String,
+    last_command: String,
+    output: String,
+    /// First address of the memory panel.
+    memory_view: u32,
+    fault_reported: bool,
+    quit: bool,
+}
+
+/// Parses a hex number, with or without a 0x prefix.
+fn parse_hex(value: &str) -> Result<u32, String> {
+    let digits = value.trim_start_matches("0x").trim_start_matches("0X");
+    u32::from_str_radix(digits, 16).map_err(|_| format!("expected a hex number, got {}", value))
+}
+
+impl Debugger {
+    /// Runs the instructions and timer tick of one 60Hz frame.
+    fn frame(&mut self) {
+        for (key, frames) in self.held.iter_mut().enumerate() {
+            *frames = frames.saturating_sub(1);
+            self.chip8.keypad.set(key, *frames > 0);
+        }
+        if self.paused {
+            return;
+        }
+        apply_cheats(&mut self.chip8, &mut self.cheats);
+        self.budget += self.target_cycles_per_second;
+        for _ in 0..self.budget / 60 {
+            if self.chip8.vblank_wait || check_breakpoint(&mut self.chip8) {
+                break;
+            }
+            step(&mut self.chip8);
+            apply_cheats(&mut self.chip8, &mut self.cheats);
+            self.calls.record(self.chip8.stack.len());
+            if self.chip8.stop.is_some() {
+                break;
+            }
+        }
+        self.calls.end_frame();
+        self.budget %= 60;
+        update_timers(&mut self.chip8);
+        take_events(&mut self.chip8);
+        self.check_stopped();
+    }
+
+    /// Pauses for breakpoints, watchpoints and the first fault, returning
+    /// whether anything stopped execution.
+    fn check_stopped(&mut self) -> bool {
+        if let Some(stop) = take_stop(&mut self.chip8) {
+            self.paused = true;
+            self.output = stop.to_string();
+            return true;
+        }
+        if let (Some(fault), false) = (self.chip8.fault, self.fault_reported) {
+            self.fault_reported = true;
+            self.paused = true;
+            self.output = fault.to_string();
+            return true;
+        }
+        false
+    }
+
+    fn key(&mut self, key: KeyEvent) {
+        let interrupt = key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL);
+        if !self.paused {
+            match key.code {
+                KeyCode::Esc => self.pause(),
+                _ if interrupt => self.pause(),
+                KeyCode::Char(c) if c.is_ascii_hexdigit() => {
+                    self.held[c.to_digit(16).unwrap() as usize] = KEY_HOLD_FRAMES;
+                }
+                _ => {}
+            }
+            return;
+        }
+        match key.code {
+            _ if interrupt => self.quit = true,
+            KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => self.input.push(c),
+            KeyCode::Backspace => {
+                self.input.pop();
+            }
+            KeyCode::Esc => self.input.clear(),
+            KeyCode::Enter => {
+                // An empty line repeats the last command, e.g. to keep stepping.
+                let line = match std::mem::take(&mut self.input) {
+                    line if line.trim().is_empty() => self.last_command.clone(),
+                    line => line,
+                };
+                self.output = match self.command(&line) {
+                    Ok(output) => output,
+                    Err(error) => error,
+                };
+                self.last_command = line;
+            }
+            _ => {}
+        }
+    }
+
+    fn pause(&mut self) {
+        self.paused = true;
+        self.output = format!("Paused at {:#05X}", self.chip8.pc);
+    }
+
+    /// Runs a prompt command, returning what to show under the prompt.
+    fn command(&mut self, line: &str) -> Result<String, String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let Some((&name, arguments)) = words.split_first() else {
+            return Ok(String::new());
+        };
+        let address = || match arguments {
+            [address] => crate::resolve_address(address, &self.debug_info),
+            _ => Err(format!("usage: {} ADDR|LABEL", name)),
+        };
+        match name {
+            "b" | "break" => {
+                if arguments.is_empty() {
+                    return Err("usage: break ADDR|LABEL|FILE:LINE [if EXPR]".to_string());
+                }
+                let address = crate::set_breakpoint(&mut self.chip8, &arguments.join(" "), &self.debug_info)?;
+                match self.chip8.breakpoint_conditions.contains_key(&address) {
+                    true => Ok(format!("Conditional breakpoint at {:#05X}", address)),
+                    false => Ok(format!("Breakpoint at {:#05X}", address)),
+                }
+            }
+            "w" | "watch" if arguments.is_empty() => match self.debug_info.watches.is_empty() {
+                true => Ok("No watch expressions; add one with watch EXPR".to_string()),
+                false => Ok(self
+                    .debug_info
+                    .watches
+                    .iter()
+                    .enumerate()
+                    .map(|(index, watch)| format!("{} {}", index + 1, watch.text))
+                    .collect::<Vec<_>>()
+                    .join(", ")),
+            },
+            "w" | "watch" => {
+                let watch = Watch::new(&arguments.join(" "), &self.debug_info.symbols);
+                let output = watch.show(&self.chip8);
+                self.debug_info.watches.push(watch);
+                Ok(output)
+            }
+            "unwatch" => {
+                let index = match arguments {
+                    [number] => number.parse::<usize>().ok().filter(|&number| number >= 1),
+                    _ => None,
+                };
+                match index.filter(|&number| number <= self.debug_info.watches.len()) {
+                    Some(number) => Ok(format!("Removed {}", self.debug_info.watches.remove(number - 1).text)),
+                    None => Err("usage: unwatch N, with N from watch".to_string()),
+                }
+            }
+            "p" | "print" => {
+                let expr = Expr::parse(&arguments.join(" "), &self.debug_info.symbols)?;
+                let value = expr.eval(&self.chip8)?;
+                Ok(format!("{} ({:#X})", value, value))
+            }
+            "d" | "delete" => {
+                let address = address()?;
+                match remove_breakpoint(&mut self.chip8, address) {
+                    true => Ok(format!("Deleted the breakpoint at {:#05X}", address)),
+                    false => Err(format!("No breakpoint at {:#05X}", address)),
+                }
+            }
+            "s" | "step" => {
+                let count = match arguments {
+                    [] => 1,
+                    [count] => count.parse().map_err(|_| "usage: step [N]".to_string())?,
+                    _ => return Err("usage: step [N]".to_string()),
+                };
+                for _ in 0..count {
+                    step_into(&mut self.chip8);
+                    apply_cheats(&mut self.chip8, &mut self.cheats);
+                    self.calls.record(self.chip8.stack.len());
+                    if self.chip8.stop.is_some() {
+                        break;
+                    }
+                }
+                self.calls.end_frame();
+                match self.check_stopped() {
+                    true => Ok(self.output.clone()),
+                    false => Ok(format!("At {:#05X}", self.chip8.pc)),
+                }
+            }
+            "n" | "next" => {
+                if step_over(&mut self.chip8) {
+                    self.paused = false;
+                    Ok(String::new())
+                } else {
+                    self.command("step")
+                }
+            }
+            "c" | "continue" => {
+                self.paused = false;
+                Ok(String::new())
+            }
+            "poke" => self.poke(arguments),
+            "h" | "history" => self.history(arguments),
+            "freeze" => {
+                let [address, value] = arguments else {
+                    return Err("usage: freeze ADDR VALUE".to_string());
+                };
+                let address = crate::check_address(&self.chip8, crate::parse_address(address)?)?;
+                let value = parse_hex(value)?;
+                let value = u8::try_from(value).map_err(|_| format!("expected a byte, got {:#X}", value))?;
+                self.cheats.push(Cheat {
+                    name: format!("{:#05X} = {:#04X}", address, value),
+                    address,
+                    value,
+                    kind: CheatKind::Freeze,
+                    enabled: true,
+                    applied: false,
+                });
+                apply_cheats(&mut self.chip8, &mut self.cheats);
+                Ok(format!("Froze {:#05X} at {:#04X} as cheat {}", address, value, self.cheats.len()))
+            }
+            "cheat" => match arguments {
+                [] if self.cheats.is_empty() => Ok("No cheats; add one with freeze ADDR VALUE".to_string()),
+                [] => Ok(self
+                    .cheats
+                    .iter()
+                    .enumerate()
+                    .map(|(index, cheat)| format!("{} [{}] {}", index + 1, if cheat.enabled { "x" } else { " " }, cheat.name))
+                    .collect::<Vec<_>>()
+                    .join(", ")),
+                [number] => {
+                    let cheat = number
+                        .parse::<usize>()
+                        .ok()
+                        .and_then(|number| self.cheats.get_mut(number.checked_sub(1)?))
+                        .ok_or_else(|| format!("No cheat {}", number))?;
+                    cheat.toggle();
+                    let state = if cheat.enabled { "on" } else { "off" };
+                    Ok(format!("{} is {}", cheat.name, state))
+                }
+                _ => Err("usage: cheat [N]".to_string()),
+            },
+            "x" | "mem" => {
+                let [address] = arguments else {
+                    return Err("usage: mem ADDR".to_string());
+                };
+                self.memory_view = match self.debug_info.symbols.address(address) {
+                    Some(address) => address as u32,
+                    None => parse_hex(address)?,
+                } & !0xF;
+                Ok(String::new())
+            }
+            "reset" => {
+                reset(&mut self.chip8);
+                self.fault_reported = false;
+                Ok("Reset".to_string())
+            }
+            "q" | "quit" => {
+                self.quit = true;
+                Ok(String::new())
+            }
+            "help" => Ok(HELP.to_string()),
+            _ => Err(format!("Unknown command {}; {}", name, HELP)),
+        }
+    }
+
+    /// `poke ADDR BYTE...` writes memory, `poke V3 VALUE` or `poke I VALUE`
+    /// a register.
+    fn poke(&mut self, arguments: &[&str]) -> Result<String, String> {
+        let usage = || "usage: poke ADDR BYTE... or poke V0-VF|I VALUE".to_string();
+        let [target, values @ ..] = arguments else {
+            return Err(usage());
+        };
+        if values.is_empty() {
+            return Err(usage());
+        }
+        if let Ok(register) = target.parse::<Register>() {
+            let [value] = values else {
+                return Err(usage());
+            };
+            let value = parse_hex(value)?;
+            if value > register.max() {
+                return Err(format!("{} holds at most {:#X}, got {:#X}", register, register.max(), value));
+            }
+            register.set(&mut self.chip8, value);
+            return Ok(format!("{} = {:#04X}", register, register.value(&self.chip8)));
+        }
+        let address = u16::try_from(parse_hex(target)?).map_err(|_| format!("{} is not an address", target))?;
+        let address = crate::check_address(&self.chip8, address)?;
+        let bytes = values
+            .iter()
+            .map(|value| parse_hex(value).map(|value| value as u8))
+            .collect::<Result<Vec<u8>, String>>()?;
+        write_memory(&mut self.chip8, address, &bytes);
+        Ok(format!("Wrote {} bytes at {:#05X}", bytes.len(), address))
+    }
+
+    /// The latest change of each register, or every change of one kept,
+    /// the newest first.
+    fn history(&self, arguments: &[&str]) -> Result<String, String> {
+        let history = &self.chip8.register_history;
+        let changes: Vec<String> = match arguments {
+            [] => (0..16)
+                .filter_map(|x| history.writes(x).next_back().map(|write| format!("V{:X} {}", x, write)))
+                .collect(),
+            [register] => match register.parse::<Register>() {
+                Ok(Register::V(x)) => history.writes(x as usize).rev().map(ToString::to_string).collect(),
+                _ => return Err("usage: history [V0-VF]".to_string()),
+            },
+            _ => return Err("usage: history [V0-VF]".to_string()),
+        };
+        match changes.is_empty() {
+            true => Ok("No register changes yet".to_string()),
+            false => Ok(changes.join(", ")),
+        }
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let display_rows = self.chip8.display_height.div_ceil(2) as u16;
+        let [top, prompt] = Layout::vertical([Constraint::Min(0), Constraint::Length(4)]).areas(frame.area());
+        let [left, middle, right] = Layout::horizontal([
+            Constraint::Length(self.chip8.display_width as u16 + 2),
+            Constraint::Min(30),
+            Constraint::Length(26),
+        ])
+        .areas(top);
+        let [display, memory] =
+            Layout::vertical([Constraint::Length(display_rows + 2), Constraint::Min(0)]).areas(left);
+        let watches = match self.debug_info.watches.len() {
+            0 => 0,
+            count => count as u16 + 2,
+        };
+        let [registers, watch, stack, timeline] = Layout::vertical([
+            Constraint::Length(16),
+            Constraint::Length(watches),
+            Constraint::Min(0),
+            Constraint::Length(6),
+        ])
+        .areas(right);
+
+        frame.render_widget(self.display(), display);
+        frame.render_widget(self.memory(memory), memory);
+        match &self.debug_info.source_map {
+            Some(source_map) => {
+                let [disassembly, source] = Layout::vertical([Constraint::Fill(1), Constraint::Fill(1)]).areas(middle);
+                frame.render_widget(self.disassembly(disassembly), disassembly);
+                frame.render_widget(self.source(source_map, source), source);
+            }
+            None => frame.render_widget(self.disassembly(middle), middle),
+        }
+        frame.render_widget(self.registers(), registers);
+        if watches > 0 {
+            frame.render_widget(self.watches(), watch);
+        }
+        frame.render_widget(self.stack(), stack);
+        frame.render_widget(self.timeline(timeline), timeline);
+        frame.render_widget(self.prompt(), prompt);
+    }
+
+    /// Two display rows per line, with half blocks.
+    fn display(&self) -> Paragraph<'_> {
+        let width = self.chip8.display_width;
+        let lit = |x: usize, y: usize| y < self.chip8.display_height && self.chip8.display[y * width + x] != 0;
+        let lines: Vec<Line> = (0..self.chip8.display_height)
+            .step_by(2)
+            .map(|y| {
+                let row: String = (0..width)
+                    .map(|x| match (lit(x, y), lit(x, y + 1)) {
+                        (true, true) => '█',
+                        (true, false) => '▀',
+                        (false, true) => '▄',
+                        (false, false) => ' ',
+                    })
+                    .collect();
+                Line::from(row)
+            })
+            .collect();
+        Paragraph::new(lines).block(Block::bordered().title(" Display "))
+    }
+
+    /// A hex dump from `memory_view`, the byte at I highlighted.
+    fn memory(&self, area: Rect) -> Paragraph<'_> {
+        let rows = area.height.saturating_sub(2) as u32;
+        let lines: Vec<Line> = (0..rows)
+            .map(|row| self.memory_view + row * 16)
+            .filter(|&address| (address as usize) < self.chip8.memory.len())
+            .map(|address| {
+                let mut spans = vec![Span::raw(format!("{:03X}:", address))];
+                for offset in 0..16 {
+                    let at = address + offset;
+                    let byte = self.chip8.memory.get(at as usize).copied().unwrap_or(0);
+                    let span = Span::raw(format!(" {:02X}", byte));
+                    spans.push(if at == self.chip8.i { span.cyan().bold() } else { span });
+                }
+                Line::from(spans)
+            })
+            .collect();
+        Paragraph::new(lines).block(Block::bordered().title(" Memory "))
+    }
+
+    /// Instructions around PC, the one at PC highlighted and breakpoints
+    /// marked with `*`.
+    fn disassembly(&self, area: Rect) -> Paragraph<'_> {
+        let context = area.height.saturating_sub(3) as usize / 2;
+        let pc = self.chip8.pc as usize;
+        let start = pc.saturating_sub(context * 2);
+        let end = (pc + (context + 1) * 2).min(self.chip8.memory.len());
+        // Disassembled in two parts so the instruction at PC always starts a
+        // line.
+        let mut disassembled = disassemble(&self.chip8.memory[start..pc], start as u16);
+        disassembled.extend(disassemble(&self.chip8.memory[pc..end], self.chip8.pc));
+        let lines: Vec<Line> = disassembled
+            .iter()
+            .map(|line| {
+                let breakpoint = self.chip8.breakpoints.contains(&(line.address as u16));
+                let hex: String = line.bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+                let text = match line.instruction {
+                    Some(instruction) => instruction_text(instruction, &self.debug_info.symbols),
+                    None => String::new(),
+                };
+                let label = self.debug_info.symbols.name(line.address as u16).map_or(String::new(), |name| format!("{}: ", name));
+                let marker = if breakpoint { "*" } else { " " };
+                let line_text = format!("{} {:03X}  {:<8}  {}{}", marker, line.address, hex, label, text);
+                let style = if line.address as usize == pc {
+                    Style::new().black().on_yellow()
+                } else if breakpoint {
+                    Style::new().red()
+                } else {
+                    Style::new()
+                };
+                Line::styled(line_text, style)
+            })
+            .collect();
+        Paragraph::new(lines).block(Block::bordered().title(" Disassembly "))
+    }
+
+    /// The .8o source around the line of the instruction at PC, that line
+    /// highlighted and lines with a breakpoint marked with `*`.
+    fn source<'a>(&self, source_map: &'a SourceMap, area: Rect) -> Paragraph<'a> {
+        let current = source_map.line(self.chip8.pc);
+        let context = area.height.saturating_sub(3) as usize / 2;
+        let first = current.unwrap_or(1).saturating_sub(context).max(1);
+        let last = (first + area.height.saturating_sub(2) as usize).min(source_map.line_count() + 1);
+        let breakpoints: Vec<usize> = self
+            .chip8
+            .breakpoints
+            .iter()
+            .filter_map(|&address| source_map.line(address))
+            .collect();
+        let lines: Vec<Line> = (first..last)
+            .map(|line| {
+                let breakpoint = breakpoints.contains(&line);
+                let marker = if breakpoint { "*" } else { " " };
+                let text = format!("{} {:>4}  {}", marker, line, source_map.text(line).unwrap_or_default());
+                let style = if Some(line) == current {
+                    Style::new().black().on_yellow()
+                } else if breakpoint {
+                    Style::new().red()
+                } else {
+                    Style::new()
+                };
+                Line::styled(text, style)
+            })
+            .collect();
+        let name = std::path::Path::new(&source_map.file).file_name().unwrap_or_default();
+        Paragraph::new(lines).block(Block::bordered().title(format!(" {} ", name.to_string_lossy())))
+    }
+
+    fn registers(&self) -> Paragraph<'_> {
+        let chip8 = &self.chip8;
+        let mut lines: Vec<Line> = (0..8)
+            .map(|x| Line::from(format!("V{:X}: {:02X}   V{:X}: {:02X}", x, chip8.v[x], x + 8, chip8.v[x + 8])))
+            .collect();
+        lines.push(Line::from(""));
+        lines.push(Line::from(format!("PC: {:03X}   I: {:03X}", chip8.pc, chip8.i)));
+        lines.push(Line::from(format!("DT: {:02X}    ST: {:02X}", chip8.timer_delay, chip8.timer_sound)));
+        lines.push(Line::from(format!("SP: {}", chip8.stack.len())));
+        let status = match (chip8.fault, self.paused) {
+            (Some(fault), _) => Span::raw(fault.to_string()).red(),
+            (None, true) => Span::raw("Paused").yellow(),
+            (None, false) => Span::raw("Running").green(),
+        };
+        lines.push(Line::from(status));
+        Paragraph::new(lines).block(Block::bordered().title(" Registers "))
+    }
+
+    /// Watch expressions and their values, numbered for `unwatch`.
+    fn watches(&self) -> Paragraph<'_> {
+        let lines: Vec<Line> = self
+            .debug_info
+            .watches
+            .iter()
+            .enumerate()
+            .map(|(index, watch)| Line::from(format!("{} {}", index + 1, watch.show(&self.chip8))))
+            .collect();
+        Paragraph::new(lines).block(Block::bordered().title(" Watch "))
+    }
+
+    /// The call chain, the most recent call first.
+    fn stack(&self) -> Paragraph<'_> {
+        let lines: Vec<Line> = call_chain(&self.chip8)
+            .iter()
+            .map(|call| Line::from(call_text(call, &self.debug_info.symbols)))
+            .collect();
+        Paragraph::new(lines).block(Block::bordered().title(" Stack "))
+    }
+
+    /// The deepest the stack got in each of the latest frames that fit.
+    fn timeline(&self, area: Rect) -> Sparkline<'_> {
+        let width = area.width.saturating_sub(2) as usize;
+        let depths: Vec<u64> = self.calls.frames().map(|(_, high)| high as u64).collect();
+        let title = format!(" Call depth, deepest {} ", self.calls.deepest());
+        Sparkline::default()
+            .data(depths[depths.len().saturating_sub(width)..].to_vec())
+            .max(self.calls.deepest().max(4) as u64)
+            .block(Block::bordered().title(title))
+    }
+
+    fn prompt(&self) -> Paragraph<'_> {
+        let (title, input) = if self.paused {
+            (" Command (help for a list) ", format!("> {}█", self.input))
+        } else {
+            (" Running: hex keys press keypad keys, Esc pauses ", String::new())
+        };
+        Paragraph::new(vec![Line::from(self.output.as_str()), Line::from(input)])
+            .block(Block::bordered().title(title))
+    }
+}
+
+/// Runs the machine in the terminal until `quit`, or Ctrl-C while paused.
+pub fn run(chip8: Chip8, debug_info: DebugInfo, cheats: Vec<Cheat>, target_cycles_per_second: u32, start_paused: bool) {
+    let mut debugger = Debugger {
+        chip8,
+        debug_info,
+        cheats,
+        calls: CallTimeline::new(),
+        paused: start_paused,
+        target_cycles_per_second,
+        budget: 0,
+        held: [0; 16],
+        input: String::new(),
+        last_command: String::new(),
+        output: HELP.to_string(),
+        memory_view: 0x200,
+        fault_reported: false,
+        quit: false,
+    };
+    let mut terminal = ratatui::init();
+    let mut next_frame = Instant::now();
+    while !debugger.quit {
+        if let Err(error) = terminal.draw(|frame| debugger.draw(frame)) {
+            tracing::error!(target: "frontend", "Unable to draw the terminal: {}", error);
+            break;
+        }
+        // Handle input until the next frame is due. Frames that were missed,
+        // e.g. while the terminal was suspended, are skipped.
+        next_frame = (next_frame + TIMER_PERIOD).max(Instant::now());
+        while let Ok(true) = event::poll(next_frame.saturating_duration_since(Instant::now())) {
+            match event::read() {
+                Ok(Event::Key(key)) if key.kind == KeyEventKind::Press => debugger.key(key),
+                Ok(_) => {}
+                Err(error) => {
+                    tracing::error!(target: "frontend", "Unable to read the terminal: {}", error);
+                    debugger.quit = true;
+                }
+            }
+            if debugger.quit {
+                break;
+            }
+        }
+        debugger.frame();
+    }
+    ratatui::restore();
+}
@@ -0,0 +1,94 @@
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver};
+use std::time::{Duration, Instant, SystemTime};
+
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+
+/// How long the file must stay untouched before a change is reported, so a
+/// build that writes the ROM in several steps is loaded once, complete.
+const SETTLE_TIME: Duration = Duration::from_millis(50);
+
+/// Used only when the platform can't notify us of changes.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Watches a ROM file so it can be reloaded as soon as it is rebuilt.
+pub struct RomWatcher {
+    pub path: String,
+    modified: Option<SystemTime>,
+    last_check: Instant,
+    // The directory is watched rather than the file, as many editors and
+    // build tools replace the file instead of writing to it.
+    notifications: Option<(RecommendedWatcher, Receiver<notify::Result<notify::Event>>)>,
+    last_event: Option<Instant>,
+}
+
+fn modified(path: &str) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+fn watch_directory(path: &str) -> notify::Result<(RecommendedWatcher, Receiver<notify::Result<notify::Event>>)> {
+    let (sender, receiver) = channel();
+    let mut watcher = notify::recommended_watcher(sender)?;
+    let directory = match Path::new(path).parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    watcher.watch(&directory, RecursiveMode::NonRecursive)?;
+    Ok((watcher, receiver))
+}
+
+impl RomWatcher {
+    pub fn new(path: &str) -> RomWatcher {
+        let notifications = watch_directory(path)
+            .inspect_err(|error| {
+                tracing::warn!(target: "frontend", "Unable to watch {} for changes, polling instead: {}", path, error)
+            })
+            .ok();
+        RomWatcher {
+            path: path.to_owned(),
+            modified: modified(path),
+            last_check: Instant::now(),
+            notifications,
+            last_event: None,
+        }
+    }
+
+    /// Returns true once per change of the file. A file that is briefly
+    /// missing while being rewritten is not treated as a change.
+    pub fn changed(&mut self) -> bool {
+        let Some((_, receiver)) = &self.notifications else {
+            return self.poll();
+        };
+
+        let file_name = Path::new(&self.path).file_name();
+        for event in receiver.try_iter().flatten() {
+            let touches_rom = event.paths.iter().any(|path| path.file_name() == file_name);
+            if touches_rom && (event.kind.is_modify() || event.kind.is_create()) {
+                self.last_event = Some(Instant::now());
+            }
+        }
+
+        match self.last_event {
+            Some(at) if at.elapsed() >= SETTLE_TIME => {
+                self.last_event = None;
+                Path::new(&self.path).exists()
+            }
+            _ => false,
+        }
+    }
+
+    fn poll(&mut self) -> bool {
+        if self.last_check.elapsed() < POLL_INTERVAL {
+            return false;
+        }
+        self.last_check = Instant::now();
+
+        match modified(&self.path) {
+            Some(time) if Some(time) != self.modified => {
+                self.modified = Some(time);
+                true
+            }
+            _ => false,
+        }
+    }
+}
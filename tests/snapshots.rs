@@ -0,0 +1,172 @@
+//! Golden display snapshots of small ROMs exercising the drawing opcodes,
+//! run through the same harness as `chip8 test`. Set `UPDATE_SNAPSHOTS=1`
+//! to rewrite the files in `tests/snapshots` after an intended change.
+
+use chip8::chip8::Chip8;
+use chip8::harness;
+
+/// Instructions each ROM runs; all of them end in a jump to itself well
+/// before this.
+const CYCLES: u64 = 64;
+
+fn rom(opcodes: &[u16]) -> Vec<u8> {
+    opcodes.iter().flat_map(|opcode| opcode.to_be_bytes()).collect()
+}
+
+/// Runs `opcodes` and checks the display against `tests/snapshots/<name>.txt`.
+fn assert_snapshot(name: &str, opcodes: &[u16]) -> Chip8 {
+    let chip8 = harness::run(&rom(opcodes), CYCLES, 0, &[]);
+    let actual = harness::display_snapshot(&chip8);
+    let path = format!("{}/tests/snapshots/{}.txt", env!("CARGO_MANIFEST_DIR"), name);
+    if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        std::fs::write(&path, &actual).unwrap();
+        return chip8;
+    }
+    let expected = std::fs::read_to_string(&path).unwrap_or_else(|error| panic!("{}: {}", path, error));
+    if let Err(report) = harness::compare_snapshots(&actual, &expected) {
+        panic!("{} differs from the snapshot:\n{}", name, report);
+    }
+    chip8
+}
+
+#[test]
+fn clear_screen() {
+    // Draws a 0, clears the screen with 00E0 and draws a 1 next to where
+    // the 0 was; only the 1 is left.
+    assert_snapshot(
+        "clear_screen",
+        &[
+            0x6000, // V0 := 0
+            0x6100, // V1 := 0
+            0xF029, // I := font 0
+            0xD015, // sprite V0 V1 5
+            0x00E0, // clear
+            0x6001, // V0 := 1
+            0xF029, // I := font 1
+            0x6208, // V2 := 8
+            0xD215, // sprite V2 V1 5
+            0x1212, // jump to self
+        ],
+    );
+}
+
+#[test]
+fn draw_sprite() {
+    // Draws an F and an 8 apart from each other; neither draw collides.
+    let chip8 = assert_snapshot(
+        "draw_sprite",
+        &[
+            0x640F, // V4 := F
+            0xF429, // I := font F
+            0x6002, // V0 := 2
+            0x6103, // V1 := 3
+            0xD015, // sprite V0 V1 5
+            0x6408, // V4 := 8
+            0xF429, // I := font 8
+            0x6038, // V0 := 56, the sprite's right edge on the last column
+            0x611B, // V1 := 27, its bottom row on the last row
+            0xD015, // sprite V0 V1 5
+            0x1214, // jump to self
+        ],
+    );
+    assert_eq!(chip8.v[0xF], 0);
+}
+
+#[test]
+fn draw_sprite_collision() {
+    // Draws an F, then a 0 overlapping it one pixel right and down; the
+    // overlapping pixels go dark and the second draw sets VF.
+    let chip8 = assert_snapshot(
+        "draw_sprite_collision",
+        &[
+            0x640F, // V4 := F
+            0xF429, // I := font F
+            0x6000, // V0 := 0
+            0x6100, // V1 := 0
+            0xD015, // sprite V0 V1 5
+            0x83F0, // V3 := VF, from the first draw
+            0x6400, // V4 := 0
+            0xF429, // I := font 0
+            0x6001, // V0 := 1
+            0x6101, // V1 := 1
+            0xD015, // sprite V0 V1 5
+            0x1216, // jump to self
+        ],
+    );
+    assert_eq!(chip8.v[3], 0);
+    assert_eq!(chip8.v[0xF], 1);
+}
+
+#[test]
+fn high_resolution_big_font() {
+    // Switches to 128x64 with 00FF and draws the 8x10 big 8 from Fx30 in
+    // the bottom right corner.
+    assert_snapshot(
+        "high_resolution_big_font",
+        &[
+            0x00FF, // hires
+            0x6408, // V4 := 8
+            0xF430, // I := big font 8
+            0x6078, // V0 := 120
+            0x6136, // V1 := 54
+            0xD01A, // sprite V0 V1 10
+            0x120C, // jump to self
+        ],
+    );
+}
+
+#[test]
+fn scroll_down_and_right() {
+    // Draws a big 8 in the top left corner in hires, then scrolls down 4
+    // pixels with 00C4 and right 4 with 00FB.
+    assert_snapshot(
+        "scroll_down_and_right",
+        &[
+            0x00FF, // hires
+            0x6408, // V4 := 8
+            0xF430, // I := big font 8
+            0x6000, // V0 := 0
+            0x6100, // V1 := 0
+            0xD01A, // sprite V0 V1 10
+            0x00C4, // scroll-down 4
+            0x00FB, // scroll-right
+            0x1210, // jump to self
+        ],
+    );
+}
+
+#[test]
+fn scroll_left() {
+    // Draws a big 8 at column 4 in hires and scrolls it left 4 pixels with
+    // 00FC, back against the left edge.
+    assert_snapshot(
+        "scroll_left",
+        &[
+            0x00FF, // hires
+            0x6408, // V4 := 8
+            0xF430, // I := big font 8
+            0x6004, // V0 := 4
+            0x6100, // V1 := 0
+            0xD01A, // sprite V0 V1 10
+            0x00FC, // scroll-left
+            0x120E, // jump to self
+        ],
+    );
+}
+
+#[test]
+fn low_resolution_after_high() {
+    // Switches to hires and back to 64x32 with 00FE before drawing a 0.
+    assert_snapshot(
+        "low_resolution_after_high",
+        &[
+            0x00FF, // hires
+            0x00FE, // lores
+            0x6000, // V0 := 0
+            0x6100, // V1 := 0
+            0xF029, // I := font 0
+            0xD015, // sprite V0 V1 5
+            0x120C, // jump to self
+        ],
+    );
+}